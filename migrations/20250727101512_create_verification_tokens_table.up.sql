@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+create table verification_tokens(
+    id uuid primary key,
+    user_id uuid not null constraint user_verification_token_fk references users on delete cascade,
+    token_hash varchar(64) not null,
+    expires_at timestamp with time zone not null,
+    consumed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+    constraint unique_verification_token_hash unique(token_hash)
+);
+
+create index verification_tokens_user_id_idx on verification_tokens(user_id);
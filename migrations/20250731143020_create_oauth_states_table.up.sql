@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+create table oauth_states(
+    id uuid primary key,
+    state_hash varchar(64) not null,
+    code_verifier text not null,
+    nonce text not null,
+    expires_at timestamp with time zone not null,
+    created_at timestamp with time zone not null default now(),
+    constraint unique_oauth_state_hash unique(state_hash)
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index if exists token_user_id_idx;
+alter table token drop column session_id;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+-- tokens issued by the same login share a session id
+alter table token add column session_id uuid;
+update token set session_id = id where session_id is null;
+alter table token alter column session_id set not null;
+
+create index token_session_id_idx on token(session_id);
+create index token_user_id_idx on token(user_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+alter table token drop column rotated_at;
+alter table token drop column parent_id;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- session_id is the token family, parent_id links a rotated refresh token to its successor
+alter table token add column parent_id uuid constraint token_parent_fk references token on delete set null;
+alter table token add column rotated_at timestamp with time zone;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+-- digests can't be reversed, existing tokens stop working after this
+alter table token add column token varchar(255);
+alter table token drop column token_hash;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+-- only a SHA-256 digest of each JWT is kept, a leaked table holds no working credentials
+alter table token add column token_hash varchar(64);
+update token set token_hash = encode(sha256(convert_to(token, 'UTF8')), 'hex') where token is not null;
+delete from token where token_hash is null;
+alter table token alter column token_hash set not null;
+alter table token add constraint unique_token_hash unique(token_hash);
+alter table token drop column token;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table if exists ip_login_failures;
+alter table users drop column locked_until;
+alter table users drop column lockout_count;
+alter table users drop column failed_login_attempts;
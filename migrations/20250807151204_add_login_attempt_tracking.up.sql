@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+alter table users add column failed_login_attempts integer not null default 0;
+alter table users add column lockout_count integer not null default 0;
+alter table users add column locked_until timestamp with time zone;
+
+create table ip_login_failures(
+    ip varchar(45) primary key,
+    failed_attempts integer not null default 0,
+    lockout_count integer not null default 0,
+    blocked_until timestamp with time zone,
+    updated_at timestamp with time zone not null default now()
+);
+
+create trigger set_updated_at
+    before update on ip_login_failures
+    for each row execute function update_updated_at_column();
@@ -0,0 +1,3 @@
+-- Add up migration script here
+
+alter table users add column account_expires_at timestamp with time zone;
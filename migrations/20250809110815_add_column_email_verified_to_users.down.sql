@@ -0,0 +1,6 @@
+-- Add down migration script here
+update users set is_enabled = false where email_verified = false and source <> 'CLIENT';
+
+alter table users alter column is_enabled set default false;
+
+alter table users drop column if exists email_verified;
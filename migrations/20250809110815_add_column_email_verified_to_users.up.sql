@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- is_enabled only says whether the account may be used, a verified email is tracked on its own
+alter table users add column email_verified boolean not null default false;
+
+-- accounts from before the verification flow were never asked to verify and could sign in,
+-- only a signup still holding its verification token is waiting for its email
+update users set email_verified = true
+where is_enabled = true
+    or not exists (select 1 from verification_tokens where verification_tokens.user_id = users.id);
+
+-- until now a disabled account that isn't a revoked service client was an unverified signup
+update users set is_enabled = true where is_enabled = false and source <> 'CLIENT';
+
+alter table users alter column is_enabled set default true;
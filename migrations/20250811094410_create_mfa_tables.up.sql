@@ -0,0 +1,31 @@
+-- Add up migration script here
+
+create table user_mfa(
+    user_id uuid primary key constraint user_mfa_user_fk references users on delete cascade,
+    secret text not null,
+    is_enabled boolean not null default false,
+    last_used_step bigint,
+    confirmed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now()
+);
+
+create table mfa_recovery_codes(
+    id uuid primary key,
+    user_id uuid not null constraint user_mfa_recovery_code_fk references users on delete cascade,
+    code_hash varchar(64) not null,
+    used_at timestamp with time zone,
+    created_at timestamp with time zone not null default now()
+);
+
+create index mfa_recovery_codes_user_id_idx on mfa_recovery_codes(user_id);
+
+create table mfa_challenges(
+    id uuid primary key,
+    user_id uuid not null constraint user_mfa_challenge_fk references users on delete cascade,
+    token_hash varchar(64) not null,
+    attempts integer not null default 0,
+    expires_at timestamp with time zone not null,
+    consumed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+    constraint unique_mfa_challenge_token_hash unique(token_hash)
+);
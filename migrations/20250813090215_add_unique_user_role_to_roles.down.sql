@@ -0,0 +1,2 @@
+-- Add down migration script here
+alter table roles drop constraint user_role_unique;
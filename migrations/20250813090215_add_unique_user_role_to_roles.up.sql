@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+delete from roles a using roles b
+where a.user_id = b.user_id and a.role = b.role and a.id > b.id;
+
+alter table roles add constraint user_role_unique unique (user_id, role);
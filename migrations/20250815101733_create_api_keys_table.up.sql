@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+create table api_keys(
+    id uuid primary key,
+    user_id uuid not null constraint api_key_user_fk references users on delete cascade,
+    name text not null,
+    prefix varchar(16) not null,
+    key_hash varchar(64) not null unique,
+    scopes text[] not null default '{}',
+    expires_at timestamp with time zone,
+    last_used_at timestamp with time zone,
+    revoked_at timestamp with time zone,
+    created_at timestamp with time zone not null default now()
+);
+
+create index api_keys_user_id_idx on api_keys(user_id);
@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+create table oauth_clients(
+    id uuid primary key,
+    client_id varchar(64) not null unique,
+    client_secret_hash varchar(64) not null,
+    name text not null,
+    scopes text[] not null default '{}',
+    user_id uuid not null constraint oauth_client_user_fk references users,
+    created_at timestamp with time zone not null default now(),
+    revoked_at timestamp with time zone
+);
@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+create table user_sessions(
+    id uuid primary key,
+    user_id uuid not null constraint user_session_user_fk references users,
+    user_agent text,
+    ip_address varchar(45),
+    created_at timestamp with time zone not null default now(),
+    last_seen_at timestamp with time zone not null default now(),
+    expires_at timestamp with time zone not null,
+    revoked_at timestamp with time zone
+);
+
+create index user_sessions_user_id_idx on user_sessions(user_id);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index token_expires_at_idx;
+alter table token drop column expires_at;
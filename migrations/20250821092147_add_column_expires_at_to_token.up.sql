@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- rows issued before this column existed stay null, the purge falls back to created_at for them
+alter table token add column expires_at timestamp with time zone;
+
+create index token_expires_at_idx on token(expires_at);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- set while image_url points at an uploaded avatar, names the stored files so they can be cleaned up
+alter table users add column avatar_id uuid;
@@ -0,0 +1,12 @@
+-- Add down migration script here
+alter table token drop constraint user_token_fk,
+    add constraint user_token_fk foreign key (user_id) references users;
+
+alter table roles drop constraint user_role_fk,
+    add constraint user_role_fk foreign key (user_id) references users;
+
+alter table oauth_clients drop constraint oauth_client_user_fk,
+    add constraint oauth_client_user_fk foreign key (user_id) references users;
+
+alter table user_sessions drop constraint user_session_user_fk,
+    add constraint user_session_user_fk foreign key (user_id) references users;
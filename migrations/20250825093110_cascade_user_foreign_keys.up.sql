@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+-- deleting a user removes everything it owns
+alter table token drop constraint user_token_fk,
+    add constraint user_token_fk foreign key (user_id) references users on delete cascade;
+
+alter table roles drop constraint user_role_fk,
+    add constraint user_role_fk foreign key (user_id) references users on delete cascade;
+
+alter table oauth_clients drop constraint oauth_client_user_fk,
+    add constraint oauth_client_user_fk foreign key (user_id) references users on delete cascade;
+
+alter table user_sessions drop constraint user_session_user_fk,
+    add constraint user_session_user_fk foreign key (user_id) references users on delete cascade;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index users_deletion_scheduled_at_idx;
+alter table users drop column deletion_scheduled_at;
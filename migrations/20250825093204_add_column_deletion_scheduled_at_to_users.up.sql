@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- set while a requested account deletion waits out its grace period
+alter table users add column deletion_scheduled_at timestamp with time zone;
+
+create index users_deletion_scheduled_at_idx on users(deletion_scheduled_at) where deletion_scheduled_at is not null;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+-- actor_id is deliberately not a foreign key, events outlive the accounts they describe
+create table audit_events(
+    id uuid primary key,
+    actor_id uuid,
+    event_type varchar(64) not null,
+    outcome varchar(16) not null,
+    ip_address varchar(45),
+    user_agent text,
+    metadata jsonb not null default '{}',
+    created_at timestamp with time zone not null default now()
+);
+
+create index audit_events_actor_id_idx on audit_events(actor_id, created_at);
+create index audit_events_created_at_idx on audit_events(created_at);
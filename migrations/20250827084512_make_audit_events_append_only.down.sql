@@ -0,0 +1,4 @@
+-- Add down migration script here
+drop index audit_events_event_type_idx;
+drop trigger audit_events_append_only on audit_events;
+drop function prevent_audit_event_changes();
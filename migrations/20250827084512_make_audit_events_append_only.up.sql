@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- the audit trail is append only, rows can't be changed or removed once written
+create or replace function prevent_audit_event_changes()
+returns trigger as $$
+begin
+    raise exception 'audit events are append only';
+end;
+$$ language plpgsql;
+
+create trigger audit_events_append_only
+    before update or delete on audit_events
+    for each row execute function prevent_audit_event_changes();
+
+create index audit_events_event_type_idx on audit_events(event_type, created_at);
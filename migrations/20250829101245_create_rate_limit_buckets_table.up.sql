@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+-- shared token buckets of the postgres rate limit backend, unlogged as losing them on a crash only resets the limits
+create unlogged table rate_limit_buckets(
+    key text primary key,
+    tokens double precision not null,
+    allowed boolean not null,
+    updated_at timestamp with time zone not null default now()
+);
+
+create index rate_limit_buckets_updated_at_idx on rate_limit_buckets(updated_at);
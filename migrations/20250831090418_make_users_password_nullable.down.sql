@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+-- an empty hash never verifies, the accounts can still set a password with a reset
+update users set password = '' where password is null;
+alter table users alter column password set not null;
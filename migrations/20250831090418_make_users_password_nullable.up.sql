@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- accounts signing in with magic links only have no password
+alter table users alter column password drop not null;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- the link itself is a signed token naming the row, only the digest of the code is stored
+create table magic_links(
+    id uuid primary key,
+    email text not null,
+    name text,
+    code_hash varchar(64) not null,
+    attempts integer not null default 0,
+    expires_at timestamp with time zone not null,
+    consumed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now()
+);
+
+create index magic_links_email_idx on magic_links(email);
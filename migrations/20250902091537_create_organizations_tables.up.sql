@@ -0,0 +1,27 @@
+-- Add up migration script here
+
+create table organizations(
+    id uuid primary key,
+    name text not null,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now()
+);
+
+create trigger set_updated_at
+    before update on organizations
+    for each row execute function update_updated_at_column();
+
+create table memberships(
+    organization_id uuid not null constraint membership_organization_fk references organizations on delete cascade,
+    user_id uuid not null constraint membership_user_fk references users on delete cascade,
+    role varchar(20) not null constraint membership_role_check check (role in ('OWNER', 'ADMIN', 'MEMBER', 'VIEWER')),
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now(),
+    primary key (organization_id, user_id)
+);
+
+create index memberships_user_id_idx on memberships(user_id);
+
+create trigger set_updated_at
+    before update on memberships
+    for each row execute function update_updated_at_column();
@@ -0,0 +1,22 @@
+-- Add up migration script here
+
+create table invitations(
+    id uuid primary key,
+    organization_id uuid not null constraint invitation_organization_fk references organizations on delete cascade,
+    email text not null,
+    role varchar(20) not null constraint invitation_role_check check (role in ('OWNER', 'ADMIN', 'MEMBER', 'VIEWER')),
+    invited_by uuid constraint invitation_invited_by_fk references users on delete set null,
+    expires_at timestamp with time zone not null,
+    accepted_at timestamp with time zone,
+    revoked_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now()
+);
+
+-- one pending invitation per email and organization
+create unique index invitations_pending_idx on invitations(organization_id, email)
+    where accepted_at is null and revoked_at is null;
+
+create trigger set_updated_at
+    before update on invitations
+    for each row execute function update_updated_at_column();
@@ -1,5 +1,46 @@
+use crate::application::mail::mailer::Mailer;
+#[cfg(test)]
+use crate::application::mail::memory_mailer::MemoryMailer;
+use crate::application::storage::file_storage::FileStorage;
+#[cfg(test)]
+use crate::application::storage::local_storage::LocalStorage;
+use crate::application::time::clock::Clock;
+#[cfg(test)]
+use crate::application::time::clock::SystemClock;
+use crate::users::services::google_oidc_service::GoogleOidcClient;
 use sqlx::PgPool;
+use std::sync::Arc;
 
 pub struct AppState {
     pub pool: PgPool,
+    pub mailer: Arc<dyn Mailer>,
+    pub google_oidc: Option<GoogleOidcClient>,
+    pub storage: Arc<dyn FileStorage>,
+    pub clock: Arc<dyn Clock>,
+}
+
+#[cfg(test)]
+impl AppState {
+    ///state over a test database, outgoing mail is captured by the returned mailer
+    pub fn for_tests(pool: PgPool) -> (Arc<Self>, Arc<MemoryMailer>) {
+        Self::for_tests_with_clock(pool, Arc::new(SystemClock))
+    }
+
+    ///same as `for_tests` with the time read from the given clock
+    pub fn for_tests_with_clock(
+        pool: PgPool,
+        clock: Arc<dyn Clock>,
+    ) -> (Arc<Self>, Arc<MemoryMailer>) {
+        let mailer = Arc::new(MemoryMailer::new());
+        let state = Self {
+            pool,
+            mailer: mailer.clone(),
+            google_oidc: None,
+            storage: Arc::new(LocalStorage::new(
+                std::env::temp_dir().join("video-intelligence-tests"),
+            )),
+            clock,
+        };
+        (Arc::new(state), mailer)
+    }
 }
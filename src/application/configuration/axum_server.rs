@@ -1,10 +1,39 @@
 use crate::application::configuration::application_state::AppState;
 use crate::application::configuration::database::initialize_database;
+use crate::application::configuration::google_oidc::initialize_google_oidc;
+use crate::application::configuration::jwt_keys::initialize_jwt_keys;
+use crate::application::configuration::mailer::initialize_mailer;
+use crate::application::configuration::password_hasher::initialize_password_hasher;
+use crate::application::configuration::password_policy::initialize_password_policy;
+use crate::application::configuration::rate_limit::{
+    initialize_rate_limit_store, rate_limiter, spawn_rate_limit_prune_task,
+};
+use crate::application::configuration::session_policy::initialize_session_policy;
+use crate::application::configuration::storage::initialize_storage;
 use crate::application::errors::application_error::ApplicationError;
+use crate::application::rate_limit::rate_limit_policy::RateLimitKey;
+use crate::application::rate_limit::rate_limiter::rate_limit;
+use crate::application::time::clock::SystemClock;
+use crate::organizations::routes::invitation_routes::invitations;
+use crate::organizations::routes::organization_routes::organizations;
+use crate::users::routes::admin_routes::admin;
+use crate::users::routes::api_key_routes::api_keys;
 use crate::users::routes::authentication_routes::authentication;
+use crate::users::routes::avatar_routes::avatars;
+use crate::users::routes::oauth_routes::oauth;
+use crate::users::routes::profile_routes::profile;
+use crate::users::routes::session_routes::sessions;
+use crate::users::routes::well_known_routes::well_known;
+use crate::users::services::account_deletion_service::spawn_account_deletion_task;
+use crate::users::services::login_attempt_service::spawn_account_unlock_task;
+use crate::users::services::token_purge_service::{
+    TokenPurgePolicy, purge_tokens, spawn_token_purge_task,
+};
+use axum::middleware::from_fn_with_state;
 use axum::{Extension, Router};
 use log::error;
 use std::env;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 
@@ -12,43 +41,98 @@ async fn initialize_axum_server(
     listener: TcpListener,
     state: Arc<AppState>,
 ) -> Result<(), ApplicationError> {
+    //limits per route group, sign-in, signup, refresh and invitations are counted per address
+    let store = initialize_rate_limit_store(&state.pool)?;
+    let auth_limit = rate_limiter("AUTH", 20, 60_000, RateLimitKey::Ip, &store)?;
+    let oauth_limit = rate_limiter("OAUTH", 60, 60_000, RateLimitKey::Ip, &store)?;
+    let api_limit = rate_limiter("API", 300, 60_000, RateLimitKey::ApiKey, &store)?;
+    spawn_rate_limit_prune_task(store, &[&auth_limit, &oauth_limit, &api_limit]);
+
     let app = Router::new()
-        .nest("/auth", authentication())
+        .nest(
+            "/auth",
+            authentication().layer(from_fn_with_state(auth_limit.clone(), rate_limit)),
+        )
+        .nest(
+            "/invitations",
+            invitations().layer(from_fn_with_state(auth_limit, rate_limit)),
+        )
+        .nest(
+            "/oauth",
+            oauth().layer(from_fn_with_state(oauth_limit, rate_limit)),
+        )
+        .merge(
+            Router::new()
+                .nest("/admin", admin())
+                .nest("/api-keys", api_keys())
+                .nest("/me", profile().merge(sessions()))
+                .nest("/organizations", organizations())
+                .layer(from_fn_with_state(api_limit, rate_limit)),
+        )
+        .nest("/avatars", avatars())
+        .nest("/.well-known", well_known())
         .layer(Extension(state)); //state passed here
-    match axum::serve(listener, app).await {
+    match axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    {
         Ok(()) => Ok(()),
         Err(e) => {
             error!("{}", e);
-            Err(ApplicationError {
-                error: "Application Boot Error".into(),
-                description: e.to_string(),
-            })
+            Err(ApplicationError::new(
+                "Application Boot Error",
+                e.to_string(),
+            ))
         }
     }
 }
 
 pub async fn run() -> Result<(), ApplicationError> {
+    initialize_jwt_keys()?;
+    initialize_session_policy()?;
     let pool = initialize_database().await?;
-    let port;
-
-    match env::var("PORT") {
-        Ok(val) => port = val,
-        Err(_) => port = String::from("0.0.0.0:8080"),
+    let mailer = initialize_mailer()?;
+    let google_oidc = initialize_google_oidc()?;
+    let storage = initialize_storage()?;
+    initialize_password_hasher()?;
+    initialize_password_policy()?;
+    spawn_account_unlock_task(pool.clone())?;
+    spawn_token_purge_task(pool.clone())?;
+    spawn_account_deletion_task(pool.clone(), storage.clone())?;
+    let port = match env::var("PORT") {
+        Ok(val) => val,
+        Err(_) => String::from("0.0.0.0:8080"),
     };
     //if db initialization fails return error
     match TcpListener::bind(&port).await {
         Ok(listener) => {
-            let state = Arc::new(AppState { pool });
+            let state = Arc::new(AppState {
+                pool,
+                mailer,
+                google_oidc,
+                storage,
+                clock: Arc::new(SystemClock),
+            });
             initialize_axum_server(listener, state).await?;
             Ok(())
         }
         Err(e) => {
-            error!("{}", e.to_string());
-            Err(ApplicationError {
-                error: "Server Error".into(),
-                description: e.to_string(),
-            })
+            error!("{}", e);
+            Err(ApplicationError::new("Server Error", e.to_string()))
         }
     }
     //this is a shared state and can be extracted using extensions
 }
+
+///# Run Token Purge
+///
+/// one purge on demand, e.g. `video-intelligence purge-tokens` from cron or after an incident
+pub async fn run_token_purge() -> Result<(), ApplicationError> {
+    initialize_session_policy()?;
+    let pool = initialize_database().await?;
+    let policy = TokenPurgePolicy::from_env()?;
+    purge_tokens(&pool, &policy).await?;
+    Ok(())
+}
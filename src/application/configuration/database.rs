@@ -26,16 +26,16 @@ pub async fn initialize_database() -> Result<PgPool, ApplicationError> {
             Ok(pool)
         } else {
             error!("DATABASE CONNECTION FAILED");
-            Err(ApplicationError {
-                error: "Database Connection".into(),
-                description: "Database connection failed ".into(),
-            })
+            Err(ApplicationError::new(
+                "Database Connection",
+                "Database connection failed ",
+            ))
         }
     } else {
         error!("DATABASE URL IS NOT SET");
-        Err(ApplicationError {
-            error: "Database url".into(),
-            description: "Database url is not set ".into(),
-        })
+        Err(ApplicationError::new(
+            "Database url",
+            "Database url is not set ",
+        ))
     }
 }
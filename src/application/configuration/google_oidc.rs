@@ -0,0 +1,42 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::services::google_oidc_service::{GoogleOidcClient, GoogleOidcConfig};
+use log::{info, warn};
+use std::env;
+use std::time::Duration;
+
+///initialize google sign-in from envs, disabled when GOOGLE_CLIENT_ID is not set
+pub fn initialize_google_oidc() -> Result<Option<GoogleOidcClient>, ApplicationError> {
+    let client_id = match env::var("GOOGLE_CLIENT_ID") {
+        Ok(client_id) => client_id,
+        Err(_) => {
+            warn!("GOOGLE CLIENT ID IS NOT SET, GOOGLE SIGN-IN IS DISABLED");
+            return Ok(None);
+        }
+    };
+
+    let config = GoogleOidcConfig {
+        client_id,
+        client_secret: env::var("GOOGLE_CLIENT_SECRET")?,
+        redirect_uri: env::var("GOOGLE_REDIRECT_URI")?,
+        authorization_endpoint: env::var("GOOGLE_AUTHORIZATION_ENDPOINT")
+            .unwrap_or(String::from("https://accounts.google.com/o/oauth2/v2/auth")),
+        token_endpoint: env::var("GOOGLE_TOKEN_ENDPOINT")
+            .unwrap_or(String::from("https://oauth2.googleapis.com/token")),
+        jwks_uri: env::var("GOOGLE_JWKS_URI")
+            .unwrap_or(String::from("https://www.googleapis.com/oauth2/v3/certs")),
+        issuers: env::var("GOOGLE_ISSUER")
+            .unwrap_or(String::from(
+                "https://accounts.google.com,accounts.google.com",
+            ))
+            .split(',')
+            .map(|issuer| issuer.trim().to_owned())
+            .collect(),
+        jwks_cache_ttl: Duration::from_secs(
+            env::var("GOOGLE_JWKS_CACHE_TTL")
+                .unwrap_or(String::from("3600"))
+                .parse()?,
+        ),
+    };
+    info!("GOOGLE SIGN-IN INITIALIZED");
+    Ok(Some(GoogleOidcClient::new(config)))
+}
@@ -0,0 +1,52 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::jwt_key_set::{JwtKey, JwtKeySet};
+use log::{info, warn};
+use std::env;
+use std::fs;
+
+///# Initialize Jwt Keys
+///
+/// JWT_KEYS lists the PEM private keys as `kid=path` pairs separated by commas, JWT_SIGNING_KEY_ID picks
+/// the key new tokens are signed with (the first one by default), every listed key keeps validating
+///
+/// when JWT_SECRET is set as well its HS256 tokens stay valid until they expire, without JWT_KEYS
+/// tokens are signed with JWT_SECRET as before
+pub fn initialize_jwt_keys() -> Result<(), ApplicationError> {
+    let mut keys: Vec<JwtKey> = Vec::new();
+    for entry in env::var("JWT_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (kid, path) = entry.split_once('=').ok_or(ApplicationError::new(
+            "JWT Key Error",
+            format!("Invalid JWT_KEYS entry {}, expected kid=path", entry),
+        ))?;
+        let key = JwtKey::from_pem(kid.trim(), &fs::read(path.trim())?)?;
+        info!("JWT KEY {} LOADED ({:?})", kid.trim(), key.algorithm);
+        keys.push(key);
+    }
+
+    match env::var("JWT_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+    {
+        Some(secret) => {
+            if !keys.is_empty() {
+                warn!("JWT_SECRET IS STILL SET, HS256 TOKENS ARE ACCEPTED UNTIL IT IS REMOVED");
+            }
+            keys.push(JwtKey::from_secret(&secret));
+        }
+        None if keys.is_empty() => {
+            return Err(ApplicationError::new(
+                "JWT Key Error",
+                "Set JWT_KEYS or JWT_SECRET",
+            ));
+        }
+        None => {}
+    }
+
+    let signing_kid = env::var("JWT_SIGNING_KEY_ID").ok();
+    JwtKeySet::new(keys, signing_kid.as_deref())?.install()
+}
@@ -0,0 +1,27 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::log_mailer::LogMailer;
+use crate::application::mail::mailer::Mailer;
+use crate::application::mail::smtp_mailer::SmtpMailer;
+use log::{info, warn};
+use std::env;
+use std::sync::Arc;
+
+///initialize the mailer from SMTP envs, fall back to a mailer that only logs when SMTP_HOST is not set
+pub fn initialize_mailer() -> Result<Arc<dyn Mailer>, ApplicationError> {
+    match env::var("SMTP_HOST") {
+        Ok(host) => {
+            let mailer = SmtpMailer::new(
+                &host,
+                env::var("SMTP_USERNAME")?,
+                env::var("SMTP_PASSWORD")?,
+                env::var("MAIL_FROM")?,
+            )?;
+            info!("SMTP MAILER INITIALIZED");
+            Ok(Arc::new(mailer))
+        }
+        Err(_) => {
+            warn!("SMTP HOST IS NOT SET, MAILS WILL NOT BE DELIVERED");
+            Ok(Arc::new(LogMailer))
+        }
+    }
+}
@@ -1,3 +1,11 @@
 pub mod application_state;
 pub mod axum_server;
 pub mod database;
+pub mod google_oidc;
+pub mod jwt_keys;
+pub mod mailer;
+pub mod password_hasher;
+pub mod password_policy;
+pub mod rate_limit;
+pub mod session_policy;
+pub mod storage;
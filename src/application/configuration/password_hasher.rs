@@ -0,0 +1,48 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::password_hasher::{PasswordAlgorithm, PasswordHasher};
+use log::info;
+use std::env;
+
+///# Initialize Password Hasher
+///
+/// PASSWORD_HASH_ALGORITHM picks how new passwords are hashed, `argon2id` (default) or `bcrypt`
+///
+/// Argon2id is tuned with ARGON2_MEMORY_COST (KiB), ARGON2_TIME_COST and ARGON2_PARALLELISM, bcrypt
+/// with BCRYPT_COST, passwords hashed otherwise are re-hashed on the next successful login
+pub fn initialize_password_hasher() -> Result<(), ApplicationError> {
+    let algorithm = match env::var("PASSWORD_HASH_ALGORITHM")
+        .unwrap_or(String::from("argon2id"))
+        .to_lowercase()
+        .as_str()
+    {
+        "argon2id" => PasswordAlgorithm::Argon2id {
+            memory_cost: env::var("ARGON2_MEMORY_COST")
+                .unwrap_or(String::from("19456"))
+                .parse()?,
+            time_cost: env::var("ARGON2_TIME_COST")
+                .unwrap_or(String::from("2"))
+                .parse()?,
+            parallelism: env::var("ARGON2_PARALLELISM")
+                .unwrap_or(String::from("1"))
+                .parse()?,
+        },
+        "bcrypt" => PasswordAlgorithm::Bcrypt {
+            cost: env::var("BCRYPT_COST")
+                .unwrap_or(String::from("12"))
+                .parse()?,
+        },
+        algorithm => {
+            return Err(ApplicationError::new(
+                "Password Hash Error",
+                format!(
+                    "Unknown PASSWORD_HASH_ALGORITHM {}, expected argon2id or bcrypt",
+                    algorithm
+                ),
+            ));
+        }
+    };
+
+    let hasher = PasswordHasher::new(algorithm)?;
+    info!("PASSWORDS HASHED WITH {:?}", hasher.algorithm());
+    hasher.install()
+}
@@ -0,0 +1,64 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::breached_passwords::BreachedPasswords;
+use crate::application::security::password_policy::PasswordPolicy;
+use log::{info, warn};
+use std::env;
+
+///# Initialize Password Policy
+///
+/// PASSWORD_MIN_LENGTH and PASSWORD_MAX_LENGTH bound the length in characters, PASSWORD_MIN_ENTROPY
+/// (bits) rejects guessable passwords and PASSWORD_BANNED_WORDS lists comma separated words no
+/// password may contain
+///
+/// BREACHED_PASSWORDS_PATH points at a file of SHA-1 digests of breached passwords sorted by
+/// digest, it is searched on disk and never leaves the server
+pub fn initialize_password_policy() -> Result<(), ApplicationError> {
+    let min_length: usize = env::var("PASSWORD_MIN_LENGTH")
+        .unwrap_or(String::from("8"))
+        .parse()?;
+    let max_length: usize = env::var("PASSWORD_MAX_LENGTH")
+        .unwrap_or(String::from("128"))
+        .parse()?;
+    let min_entropy: f64 = env::var("PASSWORD_MIN_ENTROPY")
+        .unwrap_or(String::from("35"))
+        .parse()?;
+    if min_length == 0 || max_length < min_length {
+        return Err(ApplicationError::new(
+            "Password Policy Error",
+            "PASSWORD_MIN_LENGTH must be positive and at most PASSWORD_MAX_LENGTH",
+        ));
+    }
+
+    let breached = match env::var("BREACHED_PASSWORDS_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+    {
+        Some(path) => {
+            let breached = BreachedPasswords::load(&path)?;
+            if breached.is_empty() {
+                warn!("NO BREACHED PASSWORD HASHES FOUND IN {}", path);
+            } else {
+                info!("CHECKING PASSWORDS AGAINST THE BREACHED HASHES IN {}", path);
+            }
+            breached
+        }
+        None => {
+            warn!("BREACHED_PASSWORDS_PATH IS NOT SET, PASSWORDS ARE NOT CHECKED AGAINST BREACHES");
+            BreachedPasswords::default()
+        }
+    };
+
+    PasswordPolicy {
+        min_length,
+        max_length,
+        min_entropy,
+        banned_words: env::var("PASSWORD_BANNED_WORDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|word| word.trim().to_lowercase())
+            .filter(|word| !word.is_empty())
+            .collect(),
+        breached,
+    }
+    .install()
+}
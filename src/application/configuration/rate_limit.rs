@@ -0,0 +1,114 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::rate_limit::memory_rate_limit_store::MemoryRateLimitStore;
+use crate::application::rate_limit::postgres_rate_limit_store::PostgresRateLimitStore;
+use crate::application::rate_limit::rate_limit_policy::{RateLimitKey, RateLimitPolicy};
+use crate::application::rate_limit::rate_limit_store::RateLimitStore;
+use crate::application::rate_limit::rate_limiter::RateLimiter;
+use log::{error, info};
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+///initialize the rate limit store from RATE_LIMIT_BACKEND, `memory` (default) or `postgres` to share limits between instances
+///
+/// the memory store keeps at most RATE_LIMIT_MEMORY_MAX_BUCKETS buckets
+pub fn initialize_rate_limit_store(
+    pool: &PgPool,
+) -> Result<Arc<dyn RateLimitStore>, ApplicationError> {
+    match env::var("RATE_LIMIT_BACKEND")
+        .unwrap_or(String::from("memory"))
+        .as_str()
+    {
+        "memory" => {
+            let max_buckets: usize = env::var("RATE_LIMIT_MEMORY_MAX_BUCKETS")
+                .unwrap_or(String::from("100000"))
+                .parse()?;
+            if max_buckets == 0 {
+                return Err(ApplicationError::new(
+                    "Configuration Error",
+                    "RATE_LIMIT_MEMORY_MAX_BUCKETS must be greater than 0",
+                ));
+            }
+            Ok(Arc::new(MemoryRateLimitStore::new(max_buckets)))
+        }
+        "postgres" => {
+            info!("RATE LIMITS ARE SHARED THROUGH POSTGRES");
+            Ok(Arc::new(PostgresRateLimitStore::new(pool.clone())))
+        }
+        backend => Err(ApplicationError::new(
+            "Configuration Error",
+            format!("Unknown RATE_LIMIT_BACKEND {}", backend),
+        )),
+    }
+}
+
+///# Rate Limiter
+///
+/// the limiter of a route group, configured by RATE_LIMIT_{GROUP}_REQUESTS,
+/// RATE_LIMIT_{GROUP}_PERIOD (ms) and RATE_LIMIT_{GROUP}_KEY (`ip`, `user` or `api_key`)
+///
+/// 0 requests turns the limit off for the group
+pub fn rate_limiter(
+    group: &str,
+    requests: u32,
+    period: u64,
+    key: RateLimitKey,
+    store: &Arc<dyn RateLimitStore>,
+) -> Result<RateLimiter, ApplicationError> {
+    let requests: u32 = match env::var(format!("RATE_LIMIT_{}_REQUESTS", group)) {
+        Ok(requests) => requests.parse()?,
+        Err(_) => requests,
+    };
+    let period: u64 = match env::var(format!("RATE_LIMIT_{}_PERIOD", group)) {
+        Ok(period) => period.parse()?,
+        Err(_) => period,
+    };
+    let key = match env::var(format!("RATE_LIMIT_{}_KEY", group)).as_deref() {
+        Ok("ip") => RateLimitKey::Ip,
+        Ok("user") => RateLimitKey::User,
+        Ok("api_key") => RateLimitKey::ApiKey,
+        Ok(key) => {
+            return Err(ApplicationError::new(
+                "Configuration Error",
+                format!("Unknown RATE_LIMIT_{}_KEY {}", group, key),
+            ));
+        }
+        Err(_) => key,
+    };
+    if requests > 0 && period == 0 {
+        return Err(ApplicationError::new(
+            "Configuration Error",
+            format!("RATE_LIMIT_{}_PERIOD must be positive", group),
+        ));
+    }
+
+    Ok(RateLimiter {
+        policy: (requests > 0).then(|| RateLimitPolicy {
+            group: group.to_lowercase(),
+            requests,
+            period: Duration::from_millis(period),
+            key,
+        }),
+        store: store.clone(),
+    })
+}
+
+///drop idle buckets every minute, a bucket idle for its whole period is full and can be recreated as is
+pub fn spawn_rate_limit_prune_task(store: Arc<dyn RateLimitStore>, limiters: &[&RateLimiter]) {
+    let idle = limiters
+        .iter()
+        .filter_map(|limiter| limiter.policy.as_ref().map(|policy| policy.period))
+        .max()
+        .unwrap_or(Duration::from_secs(60));
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = store.prune(idle).await {
+                error!("{:?}", error);
+            }
+        }
+    });
+}
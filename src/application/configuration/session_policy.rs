@@ -0,0 +1,41 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::session_policy::SessionPolicy;
+use log::info;
+use std::env;
+use time::Duration;
+
+///# Initialize Session Policy
+///
+/// JWT_ACCESS_EXPIRATION and JWT_REFRESH_EXPIRATION are the lifetimes of access and refresh tokens (ms)
+///
+/// MAX_SESSIONS_PER_USER caps the active sessions of a user, without it there is no cap
+pub fn initialize_session_policy() -> Result<(), ApplicationError> {
+    let access_lifetime: i64 = env::var("JWT_ACCESS_EXPIRATION")?.parse()?;
+    let refresh_lifetime: i64 = env::var("JWT_REFRESH_EXPIRATION")?.parse()?;
+    if access_lifetime <= 0 || refresh_lifetime <= 0 {
+        return Err(ApplicationError::new(
+            "Session Policy Error",
+            "JWT_ACCESS_EXPIRATION and JWT_REFRESH_EXPIRATION must be positive",
+        ));
+    }
+    let max_sessions_per_user: Option<i64> = match env::var("MAX_SESSIONS_PER_USER") {
+        Ok(cap) => Some(cap.parse()?),
+        Err(_) => None,
+    };
+    if max_sessions_per_user.is_some_and(|cap| cap <= 0) {
+        return Err(ApplicationError::new(
+            "Session Policy Error",
+            "MAX_SESSIONS_PER_USER must be positive",
+        ));
+    }
+    if let Some(cap) = max_sessions_per_user {
+        info!("SESSIONS CAPPED AT {} PER USER", cap);
+    }
+
+    SessionPolicy {
+        access_lifetime: Duration::milliseconds(access_lifetime),
+        refresh_lifetime: Duration::milliseconds(refresh_lifetime),
+        max_sessions_per_user,
+    }
+    .install()
+}
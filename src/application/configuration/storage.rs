@@ -0,0 +1,14 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::storage::file_storage::FileStorage;
+use crate::application::storage::local_storage::LocalStorage;
+use log::info;
+use std::env;
+use std::sync::Arc;
+
+///initialize file storage on the local disk below STORAGE_PATH
+pub fn initialize_storage() -> Result<Arc<dyn FileStorage>, ApplicationError> {
+    let path = env::var("STORAGE_PATH").unwrap_or(String::from("./storage"));
+    std::fs::create_dir_all(&path)?;
+    info!("LOCAL STORAGE INITIALIZED AT {}", path);
+    Ok(Arc::new(LocalStorage::new(path)))
+}
@@ -1,11 +1,28 @@
+use crate::application::errors::field_error::FieldError;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+///# Error Kind
+///
+/// whether the request was refused or the server failed to handle it, so callers can pick
+/// between a 4xx and a 500 without matching on the message
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ErrorKind {
+    #[default]
+    Rejected,
+    //the database or another dependency failed, never the caller's fault
+    Internal,
+}
+
 #[derive(Debug, Error, Serialize, Deserialize)]
 #[error("{error}: {description}")]
 pub struct ApplicationError {
     pub error: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+    #[serde(skip)]
+    pub kind: ErrorKind,
 }
 
 impl ApplicationError {
@@ -13,15 +30,31 @@ impl ApplicationError {
         Self {
             error: error.into(),
             description: description.into(),
+            fields: Vec::new(),
+            kind: ErrorKind::Rejected,
         }
     }
 
-    pub fn generic(description: impl Into<String>) -> Self {
+    ///an error of the server itself, see `ErrorKind::Internal`
+    pub fn internal(error: impl Into<String>, description: impl Into<String>) -> Self {
         Self {
-            error: "An error occurred".to_string(),
-            description: description.into(),
+            kind: ErrorKind::Internal,
+            ..Self::new(error, description)
         }
     }
+
+    pub fn is_internal(&self) -> bool {
+        self.kind == ErrorKind::Internal
+    }
+
+    pub fn generic(description: impl Into<String>) -> Self {
+        Self::new("An error occurred", description)
+    }
+
+    ///attach the offending fields of a rejected request
+    pub fn with_fields(self, fields: Vec<FieldError>) -> Self {
+        Self { fields, ..self }
+    }
 }
 
 // Macro to generate From implementations
@@ -29,42 +62,63 @@ macro_rules! impl_from_error {
     ($error_type:ty, $error_name:expr) => {
         impl From<$error_type> for ApplicationError {
             fn from(err: $error_type) -> Self {
-                ApplicationError {
-                    error: $error_name.to_string(),
-                    description: err.to_string(),
-                }
+                ApplicationError::new($error_name, err.to_string())
+            }
+        }
+    };
+    ($error_type:ty, $error_name:expr, internal) => {
+        impl From<$error_type> for ApplicationError {
+            fn from(err: $error_type) -> Self {
+                ApplicationError::internal($error_name, err.to_string())
             }
         }
     };
 }
 
+//a missing row is an answer to the query, any other database error is a failure of the server
+impl From<sqlx::Error> for ApplicationError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => ApplicationError::new("Database error", err.to_string()),
+            _ => ApplicationError::internal("Database error", err.to_string()),
+        }
+    }
+}
+
 // Use the macro to implement conversions
-impl_from_error!(sqlx::Error, "Database error");
 impl_from_error!(serde_json::Error, "JSON processing error");
 impl_from_error!(uuid::Error, "UUID error");
-impl_from_error!(std::io::Error, "IO error");
+impl_from_error!(std::io::Error, "IO error", internal);
 impl_from_error!(time::error::Parse, "Time parsing error");
+impl_from_error!(time::error::ComponentRange, "Time range error");
 impl_from_error!(std::num::ParseIntError, "Number parsing error");
 impl_from_error!(std::num::ParseFloatError, "Float parsing error");
 impl_from_error!(bcrypt::BcryptError, "Hashing Password Error");
+impl_from_error!(argon2::Error, "Hashing Password Error");
+impl_from_error!(argon2::password_hash::Error, "Hashing Password Error");
 impl_from_error!(jsonwebtoken::errors::Error, "JWT Error");
 impl_from_error!(std::env::VarError, "JWT Error");
+impl_from_error!(lettre::address::AddressError, "Mail Address Error");
+impl_from_error!(lettre::error::Error, "Mail Error");
+impl_from_error!(lettre::transport::smtp::Error, "Mail Transport Error");
+impl_from_error!(reqwest::Error, "HTTP Client Error");
+impl_from_error!(url::ParseError, "URL Error");
+impl_from_error!(totp_rs::TotpUrlError, "MFA Error");
+impl_from_error!(totp_rs::SecretParseError, "MFA Error");
+impl_from_error!(pem::PemError, "JWT Key Error");
+impl_from_error!(ring::error::KeyRejected, "JWT Key Error");
+impl_from_error!(image::ImageError, "Image Error");
+impl_from_error!(tokio::task::JoinError, "Task Error", internal);
 
 // Special cases for string types
 impl From<String> for ApplicationError {
     fn from(err: String) -> Self {
-        ApplicationError {
-            error: "An error occurred".to_string(),
-            description: err,
-        }
+        ApplicationError::generic(err)
     }
 }
 
 impl From<&str> for ApplicationError {
     fn from(err: &str) -> Self {
-        ApplicationError {
-            error: "An error occurred".to_string(),
-            description: err.to_string(),
-        }
+        ApplicationError::generic(err)
     }
 }
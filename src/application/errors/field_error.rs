@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+///# Field Error
+///
+/// why one field of a request was rejected, `code` is stable for clients to match on
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(
+        field: impl Into<String>,
+        code: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+}
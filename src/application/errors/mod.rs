@@ -1 +1,2 @@
 pub mod application_error;
+pub mod field_error;
@@ -0,0 +1,21 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::mail::mailer::Mailer;
+use async_trait::async_trait;
+use log::warn;
+
+///# Log Mailer
+///
+/// logs the recipient and subject of every message and drops it
+///
+/// used when SMTP is not configured, the body is never logged since it carries live tokens
+#[derive(Debug, Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: MailMessage) -> Result<(), ApplicationError> {
+        warn!("mail to {} not delivered: {}", message.to, message.subject);
+        Ok(())
+    }
+}
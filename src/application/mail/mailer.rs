@@ -0,0 +1,13 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use async_trait::async_trait;
+
+///# Mailer
+///
+/// every outgoing mail goes through this trait so the transport can be swapped,
+/// e.g. SMTP in production, a log only mailer when SMTP is not configured
+/// and an in memory mailbox in tests
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: MailMessage) -> Result<(), ApplicationError>;
+}
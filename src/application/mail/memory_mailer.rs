@@ -0,0 +1,41 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::mail::mailer::Mailer;
+use async_trait::async_trait;
+use log::info;
+use std::sync::Mutex;
+
+///# Memory Mailer
+///
+/// keeps sent messages in memory instead of delivering them
+///
+/// only compiled for tests that assert on outgoing mail
+#[derive(Debug, Default)]
+pub struct MemoryMailer {
+    messages: Mutex<Vec<MailMessage>>,
+}
+
+impl MemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(&self) -> Vec<MailMessage> {
+        self.messages
+            .lock()
+            .map(|messages| messages.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, message: MailMessage) -> Result<(), ApplicationError> {
+        info!("mail to {} captured: {}", message.to, message.subject);
+        self.messages
+            .lock()
+            .map_err(|e| ApplicationError::new("Mail Error", e.to_string()))?
+            .push(message);
+        Ok(())
+    }
+}
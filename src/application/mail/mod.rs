@@ -0,0 +1,6 @@
+pub mod log_mailer;
+pub mod mail_message;
+pub mod mailer;
+#[cfg(test)]
+pub mod memory_mailer;
+pub mod smtp_mailer;
@@ -0,0 +1,40 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::mail::mailer::Mailer;
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        username: String,
+        password: String,
+        from: String,
+    ) -> Result<Self, ApplicationError> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+            .credentials(Credentials::new(username, password))
+            .build();
+        Ok(Self { transport, from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: MailMessage) -> Result<(), ApplicationError> {
+        let email = Message::builder()
+            .from(self.from.parse()?)
+            .to(message.to.parse()?)
+            .subject(message.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body)?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
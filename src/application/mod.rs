@@ -1,3 +1,8 @@
 pub mod configuration;
 pub mod errors;
+pub mod mail;
+pub mod rate_limit;
+pub mod security;
+pub mod storage;
 pub mod test;
+pub mod time;
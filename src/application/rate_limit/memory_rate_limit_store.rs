@@ -0,0 +1,122 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::rate_limit::rate_limit_policy::{RateLimitDecision, RateLimitPolicy};
+use crate::application::rate_limit::rate_limit_store::RateLimitStore;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+///# Memory Rate Limit Store
+///
+/// buckets of this instance only, used when RATE_LIMIT_BACKEND is not set
+///
+/// at most `max_buckets` are kept, once full the least recently used half is dropped so a flood
+/// of new addresses can't grow the map without bound
+#[derive(Debug)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    max_buckets: usize,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new(max_buckets: usize) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            max_buckets,
+        }
+    }
+}
+
+//keep the more recently used half, sorting once per half a map keeps eviction cheap on average
+fn evict_least_recently_used(buckets: &mut HashMap<String, Bucket>) {
+    let mut updated: Vec<Instant> = buckets.values().map(|bucket| bucket.updated_at).collect();
+    let middle = updated.len() / 2;
+    let (_, cutoff, _) = updated.select_nth_unstable(middle);
+    let cutoff = *cutoff;
+    buckets.retain(|_, bucket| bucket.updated_at > cutoff);
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, ApplicationError> {
+        let now = Instant::now();
+        let capacity = policy.requests as f64;
+        let mut buckets = self
+            .buckets
+            .lock()
+            .map_err(|e| ApplicationError::new("Rate Limit Error", e.to_string()))?;
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(key) {
+            evict_least_recently_used(&mut buckets);
+        }
+        let bucket = buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        let tokens = (bucket.tokens + elapsed * policy.refill_per_second()).min(capacity);
+        let allowed = tokens >= 1.0;
+        bucket.tokens = if allowed { tokens - 1.0 } else { tokens };
+        bucket.updated_at = now;
+
+        Ok(RateLimitDecision {
+            allowed,
+            remaining: bucket.tokens,
+        })
+    }
+
+    async fn prune(&self, idle: Duration) -> Result<u64, ApplicationError> {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .map_err(|e| ApplicationError::new("Rate Limit Error", e.to_string()))?;
+        let before = buckets.len();
+        buckets.retain(|_, bucket| bucket.updated_at.elapsed() < idle);
+        Ok((before - buckets.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::rate_limit::rate_limit_policy::RateLimitKey;
+
+    fn policy() -> RateLimitPolicy {
+        RateLimitPolicy {
+            group: String::from("test"),
+            requests: 2,
+            period: Duration::from_secs(60),
+            key: RateLimitKey::Ip,
+        }
+    }
+
+    #[tokio::test]
+    async fn bucket_runs_out_after_the_burst() {
+        let store = MemoryRateLimitStore::new(10);
+        assert!(store.hit("a", &policy()).await.unwrap().allowed);
+        assert!(store.hit("a", &policy()).await.unwrap().allowed);
+        assert!(!store.hit("a", &policy()).await.unwrap().allowed);
+        assert!(store.hit("b", &policy()).await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn map_never_grows_past_its_cap() {
+        let store = MemoryRateLimitStore::new(8);
+        for i in 0..100 {
+            store.hit(&format!("ip:{}", i), &policy()).await.unwrap();
+            assert!(store.buckets.lock().unwrap().len() <= 8);
+        }
+        //the newest bucket survives eviction
+        assert!(store.buckets.lock().unwrap().contains_key("ip:99"));
+    }
+}
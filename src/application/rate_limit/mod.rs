@@ -0,0 +1,5 @@
+pub mod memory_rate_limit_store;
+pub mod postgres_rate_limit_store;
+pub mod rate_limit_policy;
+pub mod rate_limit_store;
+pub mod rate_limiter;
@@ -0,0 +1,63 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::rate_limit::rate_limit_policy::{RateLimitDecision, RateLimitPolicy};
+use crate::application::rate_limit::rate_limit_store::RateLimitStore;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use std::time::Duration;
+
+///# Postgres Rate Limit Store
+///
+/// buckets shared by every instance on the same database, each hit is one atomic upsert
+pub struct PostgresRateLimitStore {
+    pool: PgPool,
+}
+
+impl PostgresRateLimitStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresRateLimitStore {
+    async fn hit(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, ApplicationError> {
+        //refill by the time since the last hit, then take a token when there is a whole one
+        let bucket = sqlx::query!(
+            "insert into rate_limit_buckets(key, tokens, allowed) values ($1, $2 - 1, true)
+            on conflict (key) do update set
+                allowed = least($2, rate_limit_buckets.tokens
+                    + extract(epoch from now() - rate_limit_buckets.updated_at)::float8 * $3) >= 1,
+                tokens = least($2, rate_limit_buckets.tokens
+                    + extract(epoch from now() - rate_limit_buckets.updated_at)::float8 * $3)
+                    - case when least($2, rate_limit_buckets.tokens
+                        + extract(epoch from now() - rate_limit_buckets.updated_at)::float8 * $3) >= 1
+                    then 1 else 0 end,
+                updated_at = now()
+            returning tokens, allowed",
+            key,
+            policy.requests as f64,
+            policy.refill_per_second()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(RateLimitDecision {
+            allowed: bucket.allowed,
+            remaining: bucket.tokens,
+        })
+    }
+
+    async fn prune(&self, idle: Duration) -> Result<u64, ApplicationError> {
+        Ok(sqlx::query!(
+            "delete from rate_limit_buckets where updated_at < now() - make_interval(secs => $1)",
+            idle.as_secs_f64()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected())
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+///what a request is counted against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateLimitKey {
+    //the client address
+    Ip,
+    //the subject of the bearer token, else the address
+    User,
+    //the presented api key if it is active, else the user, else the address
+    ApiKey,
+}
+
+///# Rate Limit Policy
+///
+/// token bucket holding `requests` tokens that refill evenly over `period`,
+/// a burst of `requests` is allowed and then one request per `period / requests`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitPolicy {
+    pub group: String,
+    pub requests: u32,
+    pub period: Duration,
+    pub key: RateLimitKey,
+}
+
+impl RateLimitPolicy {
+    pub fn refill_per_second(&self) -> f64 {
+        self.requests as f64 / self.period.as_secs_f64()
+    }
+}
+
+///outcome of counting one request
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    //tokens left in the bucket after this request
+    pub remaining: f64,
+}
+
+impl RateLimitDecision {
+    ///seconds until the bucket is full again
+    pub fn reset_after(&self, policy: &RateLimitPolicy) -> u64 {
+        ((policy.requests as f64 - self.remaining).max(0.0) / policy.refill_per_second()).ceil()
+            as u64
+    }
+
+    ///seconds until the next request is let through
+    pub fn retry_after(&self, policy: &RateLimitPolicy) -> u64 {
+        ((1.0 - self.remaining).max(0.0) / policy.refill_per_second())
+            .ceil()
+            .max(1.0) as u64
+    }
+}
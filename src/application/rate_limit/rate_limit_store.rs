@@ -0,0 +1,21 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::rate_limit::rate_limit_policy::{RateLimitDecision, RateLimitPolicy};
+use async_trait::async_trait;
+use std::time::Duration;
+
+///# Rate Limit Store
+///
+/// keeps the token buckets, in memory for a single instance or in a shared backend
+/// so several instances enforce one limit
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    ///take one token from the bucket of `key` if there is one
+    async fn hit(
+        &self,
+        key: &str,
+        policy: &RateLimitPolicy,
+    ) -> Result<RateLimitDecision, ApplicationError>;
+
+    ///forget buckets not used for `idle`, they would be full by now anyway
+    async fn prune(&self, idle: Duration) -> Result<u64, ApplicationError>;
+}
@@ -0,0 +1,135 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::rate_limit::rate_limit_policy::{RateLimitKey, RateLimitPolicy};
+use crate::application::rate_limit::rate_limit_store::RateLimitStore;
+use crate::application::security::client_ip::ClientIp;
+use crate::application::security::jwt_key_set::jwt_key_set;
+use crate::application::security::secure_token::hash_secure_token;
+use crate::users::repositories::api_key_repository::find_active_api_key_id;
+use crate::users::services::api_key_service::extract_api_key;
+use crate::users::services::jwt_service::extract_bearer_token;
+use axum::Json;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use log::{error, warn};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+struct Subject {
+    sub: String,
+}
+
+///# Rate Limiter
+///
+/// one policy and the store counting it, applied to a route group with
+/// `middleware::from_fn_with_state(limiter, rate_limit)`
+///
+/// without a policy every request passes
+#[derive(Clone)]
+pub struct RateLimiter {
+    pub policy: Option<RateLimitPolicy>,
+    pub store: Arc<dyn RateLimitStore>,
+}
+
+//the bucket a request counts against, secrets and emails are only kept hashed
+async fn bucket_key(policy: &RateLimitPolicy, parts: &mut Parts) -> String {
+    //only a key that exists gets its own bucket, made up keys would otherwise each get a fresh one
+    if policy.key == RateLimitKey::ApiKey
+        && let Some(api_key) = extract_api_key(&parts.headers)
+        && let Some(state) = parts.extensions.get::<Arc<AppState>>()
+    {
+        match find_active_api_key_id(&state.pool, &hash_secure_token(api_key)).await {
+            Ok(Some(id)) => return format!("{}:key:{}", policy.group, id),
+            Ok(None) => {}
+            Err(error) => error!("{:?}", error),
+        }
+    }
+
+    //only a validly signed token names a user, anything else is counted by address
+    if policy.key != RateLimitKey::Ip
+        && let Some(token) = extract_bearer_token(&parts.headers)
+        && let Ok(key_set) = jwt_key_set()
+        && let Ok(token) = key_set.decode::<Subject>(token)
+    {
+        return format!(
+            "{}:user:{}",
+            policy.group,
+            hash_secure_token(&token.claims.sub)
+        );
+    }
+
+    match ClientIp::from_request_parts(parts, &()).await {
+        Ok(ip) => format!("{}:ip:{}", policy.group, ip.0),
+        Err(_) => format!("{}:ip:unknown", policy.group),
+    }
+}
+
+fn header(name: &'static str, value: u64) -> (HeaderName, HeaderValue) {
+    (HeaderName::from_static(name), HeaderValue::from(value))
+}
+
+///# Rate Limit
+///
+/// count the request against its bucket, over the limit it is answered with 429 and `Retry-After`
+///
+/// every response of a limited group carries `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`
+///
+/// when the store fails the request is let through, a broken counter must not take the api down
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let policy = match &limiter.policy {
+        Some(policy) => policy,
+        None => return next.run(request).await,
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let key = bucket_key(policy, &mut parts).await;
+    let request = Request::from_parts(parts, body);
+
+    let decision = match limiter.store.hit(&key, policy).await {
+        Ok(decision) => decision,
+        Err(error) => {
+            error!("{:?}", error);
+            return next.run(request).await;
+        }
+    };
+
+    let headers = [
+        header("ratelimit-limit", policy.requests as u64),
+        header(
+            "ratelimit-remaining",
+            decision.remaining.floor().max(0.0) as u64,
+        ),
+        header("ratelimit-reset", decision.reset_after(policy)),
+    ];
+
+    if !decision.allowed {
+        let retry_after = decision.retry_after(policy);
+        warn!("rate limit of {} exceeded by {}", policy.group, key);
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ApplicationError::new(
+                "Too Many Requests",
+                format!("Rate limit exceeded, retry in {} seconds", retry_after),
+            )),
+        )
+            .into_response();
+        response.headers_mut().extend(headers);
+        response
+            .headers_mut()
+            .insert("retry-after", HeaderValue::from(retry_after));
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().extend(headers);
+    response
+}
@@ -0,0 +1,166 @@
+use crate::application::errors::application_error::ApplicationError;
+use log::error;
+use ring::digest::{SHA1_FOR_LEGACY_USE_ONLY, digest};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+
+//lines checked for being well formed and sorted when the file is loaded
+const SAMPLE_LINES: usize = 1000;
+
+///# Breached Passwords
+///
+/// SHA-1 digests of known breached passwords, binary searched in a file sorted by digest so the
+/// corpus never has to fit in memory, no network call is made
+///
+/// without a file nothing matches
+#[derive(Default)]
+pub struct BreachedPasswords {
+    path: Option<PathBuf>,
+    size: u64,
+}
+
+//the upper case hex digest at the start of a line, before an optional `:count`
+fn line_hash(line: &[u8]) -> Vec<u8> {
+    line.split(|b| *b == b':')
+        .next()
+        .unwrap_or_default()
+        .trim_ascii()
+        .to_ascii_uppercase()
+}
+
+impl BreachedPasswords {
+    ///# Load
+    ///
+    /// one upper or lower case hex SHA-1 per line sorted by digest, as in the Have I Been Pwned
+    /// "ordered by hash" download, an optional `:count` suffix is ignored
+    ///
+    /// only the start of the file is checked to be sorted, the rest is trusted
+    pub fn load(path: &str) -> Result<Self, ApplicationError> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut previous: Option<Vec<u8>> = None;
+        for line in BufReader::new(file).split(b'\n').take(SAMPLE_LINES) {
+            let hash = line_hash(&line?);
+            if hash.len() != 40 || !hash.iter().all(u8::is_ascii_hexdigit) {
+                return Err(ApplicationError::new(
+                    "Password Policy Error",
+                    format!("{} must hold one hex SHA-1 per line", path),
+                ));
+            }
+            if previous.as_ref().is_some_and(|previous| *previous > hash) {
+                return Err(ApplicationError::new(
+                    "Password Policy Error",
+                    format!("{} must be sorted by hash", path),
+                ));
+            }
+            previous = Some(hash);
+        }
+        Ok(Self {
+            path: Some(PathBuf::from(path)),
+            size,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let hash = hex::encode_upper(digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()));
+        match File::open(path).and_then(|file| self.search(file, hash.as_bytes())) {
+            Ok(found) => found,
+            Err(e) => {
+                error!("{:?}", e);
+                false
+            }
+        }
+    }
+
+    //binary search over byte offsets, [low, high) bounds where the lines that may still match start
+    fn search(&self, file: File, hash: &[u8]) -> std::io::Result<bool> {
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        let (mut low, mut high) = (0, self.size);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            //the first line starting at or after middle
+            let start = if middle == 0 {
+                reader.seek(SeekFrom::Start(0))?;
+                0
+            } else {
+                reader.seek(SeekFrom::Start(middle - 1))?;
+                line.clear();
+                middle - 1 + reader.read_until(b'\n', &mut line)? as u64
+            };
+            if start >= high {
+                high = middle;
+                continue;
+            }
+            line.clear();
+            let end = start + reader.read_until(b'\n', &mut line)? as u64;
+            match line_hash(&line).as_slice().cmp(hash) {
+                Ordering::Equal => return Ok(true),
+                Ordering::Less => low = end,
+                Ordering::Greater => high = middle,
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn write_corpus(lines: &[String]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("breached-{}.txt", Uuid::new_v4()));
+        fs::write(&path, lines.join("\n")).unwrap();
+        path
+    }
+
+    fn sha1(password: &str) -> String {
+        hex::encode_upper(digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()))
+    }
+
+    #[test]
+    fn finds_every_password_of_a_sorted_file() {
+        let passwords: Vec<String> = (0..500).map(|i| format!("password{}", i)).collect();
+        let mut lines: Vec<String> = passwords
+            .iter()
+            .enumerate()
+            .map(|(count, password)| format!("{}:{}", sha1(password), count))
+            .collect();
+        lines.sort();
+        lines[0] = lines[0].to_lowercase();
+        let path = write_corpus(&lines);
+
+        let breached = BreachedPasswords::load(path.to_str().unwrap()).unwrap();
+        assert!(passwords.iter().all(|password| breached.contains(password)));
+        assert!(!breached.contains("Quartz-Lamp-58"));
+        assert!(!breached.contains(""));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_an_unsorted_file() {
+        let mut lines = vec![sha1("a"), sha1("b")];
+        lines.sort();
+        lines.reverse();
+        let path = write_corpus(&lines);
+
+        assert!(BreachedPasswords::load(path.to_str().unwrap()).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn nothing_matches_without_a_file() {
+        assert!(!BreachedPasswords::default().contains("password"));
+    }
+}
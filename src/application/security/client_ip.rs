@@ -0,0 +1,101 @@
+use crate::application::errors::application_error::ApplicationError;
+use axum::Json;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use reqwest::StatusCode;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+///# Client Ip
+///
+/// address of the caller, the peer socket unless TRUSTED_PROXY_COUNT proxies sit in front of the server
+///
+/// behind `n` trusted proxies the caller is the `n`th `X-Forwarded-For` entry from the right, entries
+/// further left are whatever the client sent and can't be trusted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClientIp(pub IpAddr);
+
+impl ClientIp {
+    ///the forwarded address, or the peer when the header has fewer entries than trusted proxies
+    fn resolve(peer: IpAddr, forwarded_for: Option<&str>, trusted_proxies: usize) -> Self {
+        if trusted_proxies == 0 {
+            return ClientIp(peer);
+        }
+        forwarded_for
+            .and_then(|value| value.rsplit(',').nth(trusted_proxies - 1))
+            .and_then(|value| value.trim().parse::<IpAddr>().ok())
+            .map_or(ClientIp(peer), ClientIp)
+    }
+}
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let Some(ConnectInfo(address)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() else {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApplicationError::new(
+                    "Client Error",
+                    "Client address is unknown",
+                )),
+            ));
+        };
+        //an unparsable count trusts no proxy
+        let trusted_proxies = env::var("TRUSTED_PROXY_COUNT")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let forwarded_for = parts
+            .headers
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok());
+
+        Ok(Self::resolve(address.ip(), forwarded_for, trusted_proxies))
+    }
+}
+
+impl std::fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_header_is_ignored_without_trusted_proxies() {
+        let client = ClientIp::resolve(ip("10.0.0.1"), Some("1.2.3.4"), 0);
+        assert_eq!(client, ClientIp(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn entry_appended_by_the_trusted_proxy_is_used() {
+        let client = ClientIp::resolve(ip("10.0.0.1"), Some("6.6.6.6, 1.2.3.4"), 1);
+        assert_eq!(client, ClientIp(ip("1.2.3.4")));
+
+        let client = ClientIp::resolve(ip("10.0.0.1"), Some("6.6.6.6, 1.2.3.4, 10.0.0.2"), 2);
+        assert_eq!(client, ClientIp(ip("1.2.3.4")));
+    }
+
+    #[test]
+    fn short_or_invalid_header_falls_back_to_the_peer() {
+        let client = ClientIp::resolve(ip("10.0.0.1"), Some("1.2.3.4"), 2);
+        assert_eq!(client, ClientIp(ip("10.0.0.1")));
+
+        let client = ClientIp::resolve(ip("10.0.0.1"), Some("unknown"), 1);
+        assert_eq!(client, ClientIp(ip("10.0.0.1")));
+
+        let client = ClientIp::resolve(ip("10.0.0.1"), None, 1);
+        assert_eq!(client, ClientIp(ip("10.0.0.1")));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::application::errors::application_error::ApplicationError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation, decode, decode_header,
+    encode,
+};
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::sync::OnceLock;
+
+static JWT_KEY_SET: OnceLock<JwtKeySet> = OnceLock::new();
+
+///# Jwt Key
+///
+/// one signing key, the public half is published in the JWKS
+///
+/// the legacy HS256 secret has no kid and is never published
+pub struct JwtKey {
+    pub kid: Option<String>,
+    pub algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+impl JwtKey {
+    pub fn from_secret(secret: &str) -> Self {
+        Self {
+            kid: None,
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None,
+        }
+    }
+
+    ///# From Pem
+    ///
+    /// RSA (PKCS#1 or PKCS#8) keys sign with RS256, Ed25519 (PKCS#8) keys with EdDSA
+    pub fn from_pem(kid: &str, pem_bytes: &[u8]) -> Result<Self, ApplicationError> {
+        let parsed = pem::parse(pem_bytes)?;
+        let der = parsed.contents();
+        match parsed.tag() {
+            "RSA PRIVATE KEY" => Self::rsa(kid, &RsaKeyPair::from_der(der)?, pem_bytes),
+            "PRIVATE KEY" => match RsaKeyPair::from_pkcs8(der) {
+                Ok(key_pair) => Self::rsa(kid, &key_pair, pem_bytes),
+                Err(_) => Self::ed25519(
+                    kid,
+                    &Ed25519KeyPair::from_pkcs8_maybe_unchecked(der)?,
+                    pem_bytes,
+                ),
+            },
+            tag => Err(ApplicationError::new(
+                "JWT Key Error",
+                format!("Unsupported key {} for kid {}", tag, kid),
+            )),
+        }
+    }
+
+    fn rsa(kid: &str, key_pair: &RsaKeyPair, pem_bytes: &[u8]) -> Result<Self, ApplicationError> {
+        let components = PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+        let jwk = Self::public_jwk(
+            kid,
+            KeyAlgorithm::RS256,
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(components.n),
+                e: URL_SAFE_NO_PAD.encode(components.e),
+            }),
+        );
+        Ok(Self {
+            kid: Some(kid.to_owned()),
+            algorithm: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(pem_bytes)?,
+            decoding: DecodingKey::from_jwk(&jwk)?,
+            jwk: Some(jwk),
+        })
+    }
+
+    fn ed25519(
+        kid: &str,
+        key_pair: &Ed25519KeyPair,
+        pem_bytes: &[u8],
+    ) -> Result<Self, ApplicationError> {
+        let jwk = Self::public_jwk(
+            kid,
+            KeyAlgorithm::EdDSA,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+            }),
+        );
+        Ok(Self {
+            kid: Some(kid.to_owned()),
+            algorithm: Algorithm::EdDSA,
+            encoding: EncodingKey::from_ed_pem(pem_bytes)?,
+            decoding: DecodingKey::from_jwk(&jwk)?,
+            jwk: Some(jwk),
+        })
+    }
+
+    fn public_jwk(kid: &str, algorithm: KeyAlgorithm, parameters: AlgorithmParameters) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(algorithm),
+                key_id: Some(kid.to_owned()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        }
+    }
+}
+
+///# Jwt Key Set
+///
+/// every key that still validates tokens, one of them signs new tokens
+///
+/// rotation: publish the new key first, switch signing to it once JWKS caches have refreshed,
+/// then drop the old key after the longest token lifetime has passed
+pub struct JwtKeySet {
+    signing: usize,
+    keys: Vec<JwtKey>,
+}
+
+impl JwtKeySet {
+    pub fn new(keys: Vec<JwtKey>, signing_kid: Option<&str>) -> Result<Self, ApplicationError> {
+        let signing = match signing_kid {
+            Some(signing_kid) => keys
+                .iter()
+                .position(|key| key.kid.as_deref() == Some(signing_kid))
+                .ok_or(ApplicationError::new(
+                    "JWT Key Error",
+                    format!("Signing key {} is not configured", signing_kid),
+                ))?,
+            None => 0,
+        };
+        if keys.is_empty() {
+            return Err(ApplicationError::new(
+                "JWT Key Error",
+                "No JWT keys configured",
+            ));
+        }
+        Ok(Self { signing, keys })
+    }
+
+    ///make the key set available to `jwt_key_set`, it can only be installed once
+    pub fn install(self) -> Result<(), ApplicationError> {
+        JWT_KEY_SET
+            .set(self)
+            .map_err(|_| ApplicationError::new("JWT Key Error", "JWT keys already initialized"))
+    }
+
+    ///a HS256 key set for tests sharing the process, whichever test runs first installs it
+    #[cfg(test)]
+    pub fn install_for_tests() {
+        if jwt_key_set().is_err() {
+            let _ =
+                Self::new(vec![JwtKey::from_secret("test-secret")], None).and_then(Self::install);
+        }
+    }
+
+    pub fn signing_key(&self) -> &JwtKey {
+        &self.keys[self.signing]
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, ApplicationError> {
+        let key = self.signing_key();
+        let mut header = Header::new(key.algorithm);
+        header.kid = key.kid.clone();
+        Ok(encode(&header, claims, &key.encoding)?)
+    }
+
+    ///# Decode
+    ///
+    /// pick the key by the `kid` of the token header, tokens without kid can only match the HS256 secret
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, ApplicationError> {
+        let header = decode_header(token)?;
+        let key =
+            self.keys
+                .iter()
+                .find(|key| key.kid == header.kid)
+                .ok_or(ApplicationError::new(
+                    "JWT Token error",
+                    "JWT Token signed with an unknown key",
+                ))?;
+        let mut validation = Validation::new(key.algorithm);
+        validation.validate_exp = true;
+        Ok(decode::<T>(token, &key.decoding, &validation)?)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().filter_map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+///the key set loaded at startup by `initialize_jwt_keys`
+pub fn jwt_key_set() -> Result<&'static JwtKeySet, ApplicationError> {
+    JWT_KEY_SET.get().ok_or(ApplicationError::new(
+        "JWT Key Error",
+        "JWT keys not initialized",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use serde::Deserialize;
+
+    //a throwaway key only used by these tests, ring can't generate RSA keys
+    const RSA_PEM: &[u8] = include_bytes!("test_rsa_key.pem");
+
+    #[derive(Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+        exp: u64,
+    }
+
+    fn claims() -> Claims {
+        Claims {
+            sub: String::from("user"),
+            exp: jsonwebtoken::get_current_timestamp() + 60,
+        }
+    }
+
+    fn ed25519_pem() -> Vec<u8> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref())).into_bytes()
+    }
+
+    fn key_set(keys: &[(&str, &[u8])], signing_kid: &str) -> JwtKeySet {
+        let keys = keys
+            .iter()
+            .map(|(kid, pem_bytes)| JwtKey::from_pem(kid, pem_bytes).unwrap())
+            .collect();
+        JwtKeySet::new(keys, Some(signing_kid)).unwrap()
+    }
+
+    #[test]
+    fn tokens_of_the_previous_signing_key_verify_after_rotation() {
+        let ed25519 = ed25519_pem();
+        let keys: [(&str, &[u8]); 2] = [("old", &ed25519), ("new", RSA_PEM)];
+        let before = key_set(&keys, "old");
+        let after = key_set(&keys, "new");
+
+        let old_token = before.encode(&claims()).unwrap();
+        let new_token = after.encode(&claims()).unwrap();
+
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some("new")
+        );
+        assert_eq!(
+            after.decode::<Claims>(&old_token).unwrap().claims.sub,
+            "user"
+        );
+        assert_eq!(
+            before.decode::<Claims>(&new_token).unwrap().claims.sub,
+            "user"
+        );
+    }
+
+    #[test]
+    fn rejects_an_unknown_kid() {
+        let dropped = key_set(&[("dropped", &ed25519_pem())], "dropped");
+        let current = key_set(&[("current", RSA_PEM)], "current");
+
+        let token = dropped.encode(&claims()).unwrap();
+        assert!(current.decode::<Claims>(&token).is_err());
+    }
+
+    #[test]
+    fn rejects_a_token_without_kid_once_only_asymmetric_keys_remain() {
+        let legacy = JwtKeySet::new(vec![JwtKey::from_secret("legacy-secret")], None).unwrap();
+        let token = legacy.encode(&claims()).unwrap();
+        assert!(decode_header(&token).unwrap().kid.is_none());
+        assert!(legacy.decode::<Claims>(&token).is_ok());
+
+        let asymmetric = key_set(&[("rsa", RSA_PEM), ("ed25519", &ed25519_pem())], "rsa");
+        assert!(asymmetric.decode::<Claims>(&token).is_err());
+    }
+
+    #[test]
+    fn jwks_publishes_only_the_public_asymmetric_keys() {
+        let keys = vec![
+            JwtKey::from_secret("legacy-secret"),
+            JwtKey::from_pem("rsa", RSA_PEM).unwrap(),
+            JwtKey::from_pem("ed25519", &ed25519_pem()).unwrap(),
+        ];
+        let jwks = JwtKeySet::new(keys, Some("rsa")).unwrap().jwks();
+
+        let kids: Vec<_> = jwks
+            .keys
+            .iter()
+            .map(|jwk| jwk.common.key_id.as_deref())
+            .collect();
+        assert_eq!(kids, [Some("rsa"), Some("ed25519")]);
+        assert!(jwks.keys.iter().all(|jwk| matches!(
+            jwk.algorithm,
+            AlgorithmParameters::RSA(_) | AlgorithmParameters::OctetKeyPair(_)
+        )));
+
+        //no private member such as "d" and no trace of the HS256 secret
+        let json = serde_json::to_value(&jwks).unwrap();
+        for jwk in json["keys"].as_array().unwrap() {
+            let members: Vec<_> = jwk.as_object().unwrap().keys().collect();
+            assert!(
+                !members
+                    .iter()
+                    .any(|member| ["d", "p", "q", "k"].contains(&member.as_str()))
+            );
+        }
+        assert!(
+            !json
+                .to_string()
+                .contains(&URL_SAFE_NO_PAD.encode("legacy-secret"))
+        );
+    }
+}
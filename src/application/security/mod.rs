@@ -0,0 +1,7 @@
+pub mod breached_passwords;
+pub mod client_ip;
+pub mod jwt_key_set;
+pub mod password_hasher;
+pub mod password_policy;
+pub mod secure_token;
+pub mod session_policy;
@@ -0,0 +1,237 @@
+use crate::application::errors::application_error::ApplicationError;
+use argon2::password_hash::{PasswordHash, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHasher as _, Version};
+use rand::RngCore;
+use std::sync::OnceLock;
+
+static PASSWORD_HASHER: OnceLock<PasswordHasher> = OnceLock::new();
+
+///# Password Algorithm
+///
+/// how new password hashes are computed, every stored hash carries its own algorithm and
+/// parameters so older hashes keep verifying
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordAlgorithm {
+    Bcrypt {
+        cost: u32,
+    },
+    Argon2id {
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+    },
+}
+
+#[cfg(test)]
+const TEST_ALGORITHM: PasswordAlgorithm = PasswordAlgorithm::Argon2id {
+    memory_cost: 1024,
+    time_cost: 1,
+    parallelism: 1,
+};
+
+///# Password Verification
+///
+/// `Outdated` passwords are correct but hashed with another algorithm or other parameters
+/// than the current ones
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordVerification {
+    Invalid,
+    Valid,
+    Outdated,
+}
+
+///# Password Hasher
+///
+/// bcrypt hashes are stored in their `$2b$` form, Argon2id hashes as PHC strings (`$argon2id$v=19$m=..,t=..,p=..$`)
+pub struct PasswordHasher {
+    algorithm: PasswordAlgorithm,
+}
+
+fn argon2(
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+) -> Result<Argon2<'static>, ApplicationError> {
+    let params = Params::new(memory_cost, time_cost, parallelism, None)?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+impl PasswordHasher {
+    ///the parameters are checked here so a misconfiguration fails at startup, not on the first signup
+    pub fn new(algorithm: PasswordAlgorithm) -> Result<Self, ApplicationError> {
+        match algorithm {
+            PasswordAlgorithm::Bcrypt { cost } if !(4..=31).contains(&cost) => Err(
+                ApplicationError::new("Password Hash Error", "Bcrypt cost must be 4 to 31"),
+            ),
+            PasswordAlgorithm::Bcrypt { .. } => Ok(Self { algorithm }),
+            PasswordAlgorithm::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => {
+                argon2(memory_cost, time_cost, parallelism)?;
+                Ok(Self { algorithm })
+            }
+        }
+    }
+
+    pub fn algorithm(&self) -> PasswordAlgorithm {
+        self.algorithm
+    }
+
+    pub fn hash(&self, password: &str) -> Result<String, ApplicationError> {
+        match self.algorithm {
+            PasswordAlgorithm::Bcrypt { cost } => Ok(bcrypt::hash(password, cost)?),
+            PasswordAlgorithm::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => {
+                let mut salt = [0u8; 16];
+                rand::rng().fill_bytes(&mut salt);
+                Ok(argon2(memory_cost, time_cost, parallelism)?
+                    .hash_password(password.as_bytes(), &SaltString::encode_b64(&salt)?)?
+                    .to_string())
+            }
+        }
+    }
+
+    ///# Verify
+    ///
+    /// check the password against a hash of any supported algorithm, hashes in an unknown
+    /// format never match
+    pub fn verify(
+        &self,
+        password: &str,
+        hash: &str,
+    ) -> Result<PasswordVerification, ApplicationError> {
+        let valid = if hash.starts_with("$argon2") {
+            let parsed = PasswordHash::new(hash)?;
+            //the parameters are read from the hash, the configured ones only apply to new hashes
+            match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+                Ok(()) => true,
+                Err(argon2::password_hash::Error::Password) => false,
+                Err(error) => return Err(error.into()),
+            }
+        } else if hash.starts_with("$2") {
+            bcrypt::verify(password, hash)?
+        } else {
+            false
+        };
+
+        Ok(match valid {
+            false => PasswordVerification::Invalid,
+            true if self.is_current(hash) => PasswordVerification::Valid,
+            true => PasswordVerification::Outdated,
+        })
+    }
+
+    //whether the hash was made with the current algorithm and parameters
+    fn is_current(&self, hash: &str) -> bool {
+        match self.algorithm {
+            PasswordAlgorithm::Bcrypt { cost } => hash
+                .parse::<bcrypt::HashParts>()
+                .is_ok_and(|parts| parts.get_cost() == cost),
+            PasswordAlgorithm::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => PasswordHash::new(hash).is_ok_and(|parsed| {
+                parsed.algorithm == Algorithm::Argon2id.ident()
+                    && parsed.version == Some(Version::V0x13.into())
+                    && Params::try_from(&parsed).is_ok_and(|params| {
+                        params.m_cost() == memory_cost
+                            && params.t_cost() == time_cost
+                            && params.p_cost() == parallelism
+                    })
+            }),
+        }
+    }
+
+    ///make the hasher available to `password_hasher`, it can only be installed once
+    pub fn install(self) -> Result<(), ApplicationError> {
+        PASSWORD_HASHER.set(self).map_err(|_| {
+            ApplicationError::new("Password Hash Error", "Password hasher already initialized")
+        })
+    }
+
+    ///Argon2id as in production with cheap parameters for tests sharing the process, whichever test
+    ///runs first installs it, bcrypt hashes verify as outdated
+    #[cfg(test)]
+    pub fn install_for_tests() {
+        if password_hasher().is_err() {
+            let _ = Self::new(TEST_ALGORITHM).and_then(Self::install);
+        }
+    }
+}
+
+///the hasher configured at startup by `initialize_password_hasher`
+pub fn password_hasher() -> Result<&'static PasswordHasher, ApplicationError> {
+    PASSWORD_HASHER.get().ok_or(ApplicationError::new(
+        "Password Hash Error",
+        "Password hasher not initialized",
+    ))
+}
+
+///# Hash Password
+///
+/// hash with the current algorithm on the blocking pool, hashing is slow on purpose and would
+/// otherwise stall every other request on the same worker
+pub async fn hash_password(password: &str) -> Result<String, ApplicationError> {
+    let hasher = password_hasher()?;
+    let password = password.to_owned();
+    tokio::task::spawn_blocking(move || hasher.hash(&password)).await?
+}
+
+///# Verify Password
+///
+/// verify on the blocking pool, a missing hash never matches
+pub async fn verify_password(
+    password: &str,
+    hash: Option<&str>,
+) -> Result<PasswordVerification, ApplicationError> {
+    let hasher = password_hasher()?;
+    let Some(hash) = hash else {
+        return Ok(PasswordVerification::Invalid);
+    };
+    let (password, hash) = (password.to_owned(), hash.to_owned());
+    tokio::task::spawn_blocking(move || hasher.verify(&password, &hash)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_of_another_algorithm_or_cost_verify_as_outdated() {
+        let hasher = PasswordHasher::new(TEST_ALGORITHM).unwrap();
+        let stronger = PasswordHasher::new(PasswordAlgorithm::Argon2id {
+            memory_cost: 2048,
+            time_cost: 1,
+            parallelism: 1,
+        })
+        .unwrap();
+        let bcrypt = bcrypt::hash("Quartz-Lamp-58", 4).unwrap();
+        let current = hasher.hash("Quartz-Lamp-58").unwrap();
+
+        assert!(current.starts_with("$argon2id$"));
+        for (hash, expected) in [
+            (&current, PasswordVerification::Valid),
+            (&bcrypt, PasswordVerification::Outdated),
+            (
+                &stronger.hash("Quartz-Lamp-58").unwrap(),
+                PasswordVerification::Outdated,
+            ),
+        ] {
+            assert_eq!(hasher.verify("Quartz-Lamp-58", hash).unwrap(), expected);
+            assert_eq!(
+                hasher.verify("Quartz-Lamp-59", hash).unwrap(),
+                PasswordVerification::Invalid
+            );
+        }
+        assert_eq!(
+            hasher.verify("Quartz-Lamp-58", "plain").unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+}
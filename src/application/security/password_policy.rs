@@ -0,0 +1,355 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::errors::field_error::FieldError;
+use crate::application::security::breached_passwords::BreachedPasswords;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+static PASSWORD_POLICY: OnceLock<PasswordPolicy> = OnceLock::new();
+
+//context words shorter than this match too many unrelated passwords
+const MIN_CONTEXT_WORD_LENGTH: usize = 3;
+
+///# Password Policy
+///
+/// the rules every new password is checked against, on signup, password change and reset
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub min_entropy: f64,
+    pub banned_words: Vec<String>,
+    pub breached: BreachedPasswords,
+}
+
+//lower case words of at least MIN_CONTEXT_WORD_LENGTH characters
+fn context_words<'a>(values: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+    values
+        .into_iter()
+        .flat_map(|value| value.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.chars().count() >= MIN_CONTEXT_WORD_LENGTH)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+//most used passwords and keyboard walks, most common first
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+//shorter runs are scored character by character
+const MIN_COMMON_MATCH_LENGTH: usize = 4;
+
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+static COMMON_PASSWORD_RANKS: OnceLock<HashMap<&'static str, usize>> = OnceLock::new();
+
+fn common_password_ranks() -> &'static HashMap<&'static str, usize> {
+    COMMON_PASSWORD_RANKS.get_or_init(|| {
+        COMMON_PASSWORDS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(rank, line)| (line, rank + 1))
+            .collect()
+    })
+}
+
+fn common_password_rank(candidate: &str) -> Option<usize> {
+    common_password_ranks().get(candidate).copied()
+}
+
+static LONGEST_COMMON_PASSWORD: OnceLock<usize> = OnceLock::new();
+
+//no match is longer than this, so the window tried from every start stays short
+fn longest_common_password() -> usize {
+    *LONGEST_COMMON_PASSWORD.get_or_init(|| {
+        common_password_ranks()
+            .keys()
+            .map(|password| password.chars().count())
+            .max()
+            .unwrap_or_default()
+    })
+}
+
+//undo the usual letter for digit or symbol swaps, `p@ssw0rd` reads as `password`
+fn unleet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Code(i64),
+    Key(usize, i64),
+}
+
+//how `next` follows `previous`, either by character code (`abc`, `321`) or along a keyboard row (`qwe`)
+fn step(previous: char, next: char) -> Option<Step> {
+    let code = next as i64 - previous as i64;
+    if code.abs() <= 1 {
+        return Some(Step::Code(code));
+    }
+    let (previous, next) = (previous.to_ascii_lowercase(), next.to_ascii_lowercase());
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| {
+        let column = keys.find(next)? as i64 - keys.find(previous)? as i64;
+        (column.abs() == 1).then_some(Step::Key(row, column))
+    })
+}
+
+//bits of the longest common password or year starting at `start`, with the end of the match
+fn common_match(
+    lowercase: &[char],
+    unleeted: &[char],
+    uppercase: &[bool],
+    start: usize,
+) -> Option<(usize, f64)> {
+    let longest_end = lowercase.len().min(start + longest_common_password());
+    (start + MIN_COMMON_MATCH_LENGTH..=longest_end)
+        .rev()
+        .find_map(|end| {
+            let plain: String = lowercase[start..end].iter().collect();
+            let swapped: String = unleeted[start..end].iter().collect();
+            let (rank, leet) = match common_password_rank(&plain) {
+                Some(rank) => (rank, false),
+                None => (common_password_rank(&swapped)?, true),
+            };
+            let capitalized = uppercase[start..end].iter().any(|upper| *upper);
+            Some((
+                end,
+                (rank as f64).log2() + 1.0 + f64::from(capitalized) + f64::from(leet),
+            ))
+        })
+        .or_else(|| {
+            let year: String = lowercase.get(start..start + 4)?.iter().collect();
+            (year.chars().all(|c| c.is_ascii_digit())
+                && (year.starts_with("19") || year.starts_with("20")))
+            .then(|| (start + 4, 200f64.log2()))
+        })
+}
+
+///# Estimate Entropy
+///
+/// bits of the character pool the password draws from for every character, except that common
+/// passwords and years (also capitalized or with `0` for `o` and the like) cost only a few bits
+/// by how common they are
+///
+/// a character repeating or continuing the step of the previous one (`aaa`, `abc`, `321`, `qwe`)
+/// adds nothing, a character next to the previous one adds half
+pub fn estimate_entropy(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let mut pool = 0;
+    if chars.iter().any(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if chars.iter().any(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if chars.iter().any(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if chars.iter().any(char::is_ascii_punctuation) || chars.contains(&' ') {
+        pool += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+    let pool_bits = (pool as f64).log2();
+
+    let lowercase: Vec<char> = chars.iter().map(char::to_ascii_lowercase).collect();
+    let unleeted: Vec<char> = lowercase.iter().copied().map(unleet).collect();
+    let uppercase: Vec<bool> = chars.iter().map(char::is_ascii_uppercase).collect();
+    let mut bits = 0.0;
+    let mut index = 0;
+    let mut previous: Option<(char, Option<Step>)> = None;
+    while index < chars.len() {
+        if let Some((end, match_bits)) = common_match(&lowercase, &unleeted, &uppercase, index) {
+            bits += match_bits;
+            index = end;
+            previous = None;
+            continue;
+        }
+        let c = chars[index];
+        let current_step = previous.and_then(|(previous_char, _)| step(previous_char, c));
+        bits += pool_bits
+            * match (current_step, previous) {
+                (None, _) => 1.0,
+                (Some(current), Some((_, Some(last)))) if current == last => 0.0,
+                (Some(_), _) => 0.5,
+            };
+        previous = Some((c, current_step));
+        index += 1;
+    }
+    bits
+}
+
+impl PasswordPolicy {
+    ///# Check
+    ///
+    /// every rule the password breaks, `context` holds what is known about the user such as the name and email
+    ///
+    /// a password over the maximum length is refused for its length alone, it is never scored
+    pub fn check(&self, field: &str, password: &str, context: &[&str]) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let length = password.chars().count();
+        if length > self.max_length {
+            errors.push(FieldError::new(
+                field,
+                "too_long",
+                format!("Password must be at most {} characters", self.max_length),
+            ));
+            return errors;
+        }
+        if length < self.min_length {
+            errors.push(FieldError::new(
+                field,
+                "too_short",
+                format!("Password must be at least {} characters", self.min_length),
+            ));
+        }
+        if estimate_entropy(password) < self.min_entropy {
+            errors.push(FieldError::new(
+                field,
+                "too_weak",
+                "Password is too easy to guess, use a longer mix of unrelated words, numbers and symbols",
+            ));
+        }
+
+        let lowercase = password.to_lowercase();
+        if context_words(context.iter().copied())
+            .iter()
+            .any(|word| lowercase.contains(word.as_str()))
+        {
+            errors.push(FieldError::new(
+                field,
+                "contains_personal_info",
+                "Password must not contain your name or email",
+            ));
+        }
+        if self
+            .banned_words
+            .iter()
+            .any(|word| lowercase.contains(word.as_str()))
+        {
+            errors.push(FieldError::new(
+                field,
+                "contains_banned_word",
+                "Password contains a word that is not allowed",
+            ));
+        }
+        if self.breached.contains(password) {
+            errors.push(FieldError::new(
+                field,
+                "breached",
+                "Password has appeared in a data breach, choose a different one",
+            ));
+        }
+        errors
+    }
+
+    ///make the policy available to `password_policy`, it can only be installed once
+    pub fn install(self) -> Result<(), ApplicationError> {
+        PASSWORD_POLICY.set(self).map_err(|_| {
+            ApplicationError::new(
+                "Password Policy Error",
+                "Password policy already initialized",
+            )
+        })
+    }
+
+    ///the default policy without a breached list for tests sharing the process, whichever test
+    ///runs first installs it
+    #[cfg(test)]
+    pub fn install_for_tests() {
+        if password_policy().is_err() {
+            let _ = Self {
+                min_length: 8,
+                max_length: 128,
+                min_entropy: 35.0,
+                banned_words: Vec::new(),
+                breached: BreachedPasswords::default(),
+            }
+            .install();
+        }
+    }
+}
+
+///the policy loaded at startup by `initialize_password_policy`
+pub fn password_policy() -> Result<&'static PasswordPolicy, ApplicationError> {
+    PASSWORD_POLICY.get().ok_or(ApplicationError::new(
+        "Password Policy Error",
+        "Password policy not initialized",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn common_passwords_and_keyboard_walks_are_weak() {
+        for password in [
+            "password",
+            "qwertyui",
+            "P@ssw0rd1",
+            "Password2024",
+            "asdfghjkl;",
+        ] {
+            assert!(estimate_entropy(password) < 35.0, "{}", password);
+        }
+    }
+
+    #[test]
+    fn unrelated_words_numbers_and_symbols_are_strong() {
+        for password in [
+            "Quartz-Lamp-58",
+            "correct horse battery staple",
+            "v7#Kp2!mQz",
+        ] {
+            assert!(estimate_entropy(password) >= 35.0, "{}", password);
+        }
+    }
+
+    #[test]
+    fn sequences_add_less_than_random_characters() {
+        assert!(estimate_entropy("abcdefgh") < estimate_entropy("agdhbfce"));
+        assert!(estimate_entropy("zxcvbnmq") < estimate_entropy("zmxbqcvn"));
+        assert_eq!(estimate_entropy(""), 0.0);
+    }
+
+    #[test]
+    fn long_inputs_are_scored_in_linear_time() {
+        let started = Instant::now();
+        let entropy = estimate_entropy(&"Quartz-Lamp-58".repeat(1_000));
+        assert!(entropy > 35.0);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let policy = PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            min_entropy: 35.0,
+            banned_words: Vec::new(),
+            breached: BreachedPasswords::default(),
+        };
+        let started = Instant::now();
+        let errors = policy.check("password", &"a".repeat(2 * 1024 * 1024), &[]);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let codes: Vec<&str> = errors.iter().map(|error| error.code.as_str()).collect();
+        assert_eq!(codes, ["too_long"]);
+    }
+
+    #[test]
+    fn context_words_skip_short_parts() {
+        let words = context_words(["Ada Lo", "ada.lovelace@x.io"]);
+        assert!(words.contains("ada") && words.contains("lovelace"));
+        assert!(!words.contains("lo") && !words.contains("x"));
+    }
+}
@@ -0,0 +1,26 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+///# Generate Secure Token
+///
+/// 32 random bytes hex encoded, suitable for links sent by mail
+pub fn generate_secure_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+///# Hash Secure Token
+///
+/// SHA-256 digest of the token, only the digest is ever persisted
+pub fn hash_secure_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+///# Secure Tokens Equal
+///
+/// compare two secrets in constant time, the time taken doesn't tell how much of a guess was right
+pub fn secure_tokens_equal(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
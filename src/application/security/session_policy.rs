@@ -0,0 +1,47 @@
+use crate::application::errors::application_error::ApplicationError;
+use std::sync::OnceLock;
+use time::Duration;
+
+static SESSION_POLICY: OnceLock<SessionPolicy> = OnceLock::new();
+
+///# Session Policy
+///
+/// how long issued access and refresh tokens stay valid, a session lasts as long as its refresh token
+///
+/// with a cap the oldest sessions of a user are revoked when a new one would exceed it
+pub struct SessionPolicy {
+    pub access_lifetime: Duration,
+    pub refresh_lifetime: Duration,
+    pub max_sessions_per_user: Option<i64>,
+}
+
+impl SessionPolicy {
+    ///make the policy available to `session_policy`, it can only be installed once
+    pub fn install(self) -> Result<(), ApplicationError> {
+        SESSION_POLICY.set(self).map_err(|_| {
+            ApplicationError::new("Session Policy Error", "Session policy already initialized")
+        })
+    }
+
+    ///15 minute access and 7 day refresh tokens without a session cap for tests sharing the process,
+    ///whichever test runs first installs it
+    #[cfg(test)]
+    pub fn install_for_tests() {
+        if session_policy().is_err() {
+            let _ = Self {
+                access_lifetime: Duration::minutes(15),
+                refresh_lifetime: Duration::days(7),
+                max_sessions_per_user: None,
+            }
+            .install();
+        }
+    }
+}
+
+///the policy loaded at startup by `initialize_session_policy`
+pub fn session_policy() -> Result<&'static SessionPolicy, ApplicationError> {
+    SESSION_POLICY.get().ok_or(ApplicationError::new(
+        "Session Policy Error",
+        "Session policy not initialized",
+    ))
+}
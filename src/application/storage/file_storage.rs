@@ -0,0 +1,19 @@
+use crate::application::errors::application_error::ApplicationError;
+use async_trait::async_trait;
+
+///# File Storage
+///
+/// uploaded files are kept through this trait so the backend can be swapped,
+/// e.g. the local disk in development and an object store in production
+///
+/// keys are `/` separated relative paths such as `avatars/{user_id}/{avatar_id}/256.png`
+#[async_trait]
+pub trait FileStorage: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), ApplicationError>;
+
+    ///`None` when nothing is stored under the key
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ApplicationError>;
+
+    ///deleting a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<(), ApplicationError>;
+}
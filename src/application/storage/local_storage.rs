@@ -0,0 +1,100 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::storage::file_storage::FileStorage;
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+///# Local Storage
+///
+/// keeps files below a root directory on the local disk
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    //keys never leave the root, absolute paths and `..` are refused
+    fn path(&self, key: &str) -> Result<PathBuf, ApplicationError> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(ApplicationError::new(
+                "Storage Error",
+                format!("invalid storage key {}", key),
+            ));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl FileStorage for LocalStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), ApplicationError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        //written next to the target and renamed so readers never see a partial file
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, bytes).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ApplicationError> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), ApplicationError> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+        //drop directories left empty, stopping at the first one still in use
+        let mut directory = path.parent();
+        while let Some(current) = directory {
+            if current == self.root || tokio::fs::remove_dir(current).await.is_err() {
+                break;
+            }
+            directory = current.parent();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_keys_below_the_root() {
+        let storage = LocalStorage::new("/srv/storage");
+
+        assert_eq!(
+            storage.path("avatars/a/b/64.png").unwrap(),
+            PathBuf::from("/srv/storage/avatars/a/b/64.png")
+        );
+        for key in [
+            "",
+            "..",
+            "../etc/passwd",
+            "avatars/../../etc/passwd",
+            "/etc/passwd",
+            "./avatars/64.png",
+        ] {
+            assert!(storage.path(key).is_err(), "{} was accepted", key);
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod file_storage;
+pub mod local_storage;
@@ -0,0 +1,32 @@
+use time::OffsetDateTime;
+
+///# Clock
+///
+/// time sensitive checks read the current time through this trait so they can run against a
+/// fixed clock, e.g. the system clock in production and a frozen instant in tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+
+    ///seconds since the unix epoch
+    fn unix_time(&self) -> u64 {
+        self.now().unix_timestamp() as u64
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+#[cfg(test)]
+pub struct FixedClock(pub OffsetDateTime);
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> OffsetDateTime {
+        self.0
+    }
+}
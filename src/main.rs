@@ -1,15 +1,25 @@
-use crate::application::configuration::axum_server::run;
+use crate::application::configuration::axum_server::{run, run_token_purge};
 use dotenvy::dotenv;
-use env_logger;
 use log::error;
 use log::info;
 mod application;
+mod organizations;
 mod users;
 
 #[tokio::main]
 async fn main() {
     env_logger::init(); // Initialize the logger
     dotenv().ok(); //loads envs
+
+    //`purge-tokens` runs the token purge once and exits instead of starting the server
+    if std::env::args().nth(1).as_deref() == Some("purge-tokens") {
+        if let Err(err) = run_token_purge().await {
+            error!("Token Purge Error: {}", err.description);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     match run().await {
         Ok(_) => {
             info!("Application Has Started");
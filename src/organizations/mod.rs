@@ -0,0 +1,4 @@
+pub mod repositories;
+pub mod routes;
+pub mod services;
+pub mod types;
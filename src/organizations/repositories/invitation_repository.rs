@@ -0,0 +1,193 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::types::invitation::Invitation;
+use crate::organizations::types::membership_role::MembershipRole;
+use crate::organizations::types::organization::Organization;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///# Persist Invitation
+///
+/// expired invitations of the same email are dropped first, returns None while another one is pending
+pub async fn persist_invitation(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    email: &str,
+    role: MembershipRole,
+    invited_by: &Uuid,
+    expires_at: OffsetDateTime,
+) -> Result<Option<Invitation>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "delete from invitations
+        where organization_id = $1 and email = $2 and accepted_at is null and revoked_at is null
+        and expires_at <= now()",
+        organization_id,
+        email
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let invitation = sqlx::query_as!(
+        Invitation,
+        "insert into invitations(id, organization_id, email, role, invited_by, expires_at)
+        values ($1, $2, $3, $4, $5, $6)
+        on conflict (organization_id, email) where accepted_at is null and revoked_at is null do nothing
+        returning *",
+        Uuid::new_v4(),
+        organization_id,
+        email,
+        role.to_string(),
+        invited_by,
+        expires_at
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(invitation)
+}
+
+///# Get Pending Invitations
+///
+/// invitations neither accepted nor revoked, expired ones included so they can be resent
+pub async fn get_pending_invitations(
+    pool: &PgPool,
+    organization_id: &Uuid,
+) -> Result<Vec<Invitation>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        Invitation,
+        "select * from invitations
+        where organization_id = $1 and accepted_at is null and revoked_at is null
+        order by created_at",
+        organization_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///# Renew Invitation
+///
+/// push the expiry of a pending invitation out, None when it is no longer pending
+pub async fn renew_invitation(
+    pool: &PgPool,
+    id: &Uuid,
+    organization_id: &Uuid,
+    expires_at: OffsetDateTime,
+) -> Result<Option<Invitation>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        Invitation,
+        "update invitations set expires_at = $3
+        where id = $1 and organization_id = $2 and accepted_at is null and revoked_at is null
+        returning *",
+        id,
+        organization_id,
+        expires_at
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+///# Revoke Invitation
+///
+/// returns false when the invitation is no longer pending
+pub async fn revoke_invitation(
+    pool: &PgPool,
+    id: &Uuid,
+    organization_id: &Uuid,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update invitations set revoked_at = now()
+        where id = $1 and organization_id = $2 and accepted_at is null and revoked_at is null",
+        id,
+        organization_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1)
+}
+
+///# Find Acceptable Invitation
+///
+/// a pending, unexpired invitation along with its organization
+pub async fn find_acceptable_invitation(
+    pool: &PgPool,
+    id: &Uuid,
+) -> Result<Option<(Invitation, Organization)>, ApplicationError> {
+    let row = sqlx::query!(
+        "select invitations.*, organizations.name as organization_name,
+        organizations.created_at as organization_created_at,
+        organizations.updated_at as organization_updated_at
+        from invitations join organizations on organizations.id = invitations.organization_id
+        where invitations.id = $1 and invitations.accepted_at is null and invitations.revoked_at is null
+        and invitations.expires_at > now()",
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        (
+            Invitation {
+                id: row.id,
+                organization_id: row.organization_id,
+                email: row.email,
+                role: row.role,
+                invited_by: row.invited_by,
+                expires_at: row.expires_at,
+                accepted_at: row.accepted_at,
+                revoked_at: row.revoked_at,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+            Organization {
+                id: row.organization_id,
+                name: row.organization_name,
+                created_at: row.organization_created_at,
+                updated_at: row.organization_updated_at,
+            },
+        )
+    }))
+}
+
+///# Accept Invitation
+///
+/// use the invitation up and make the user a member with its role, None when it is no longer acceptable
+///
+/// an existing membership keeps its role
+pub async fn accept_invitation(
+    pool: &PgPool,
+    id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<Invitation>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let invitation = sqlx::query_as!(
+        Invitation,
+        "update invitations set accepted_at = now()
+        where id = $1 and accepted_at is null and revoked_at is null and expires_at > now()
+        returning *",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(invitation) = &invitation {
+        sqlx::query!(
+            "insert into memberships(organization_id, user_id, role) values ($1, $2, $3)
+            on conflict (organization_id, user_id) do nothing",
+            invitation.organization_id,
+            user_id,
+            invitation.role
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(invitation)
+}
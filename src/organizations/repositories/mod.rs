@@ -0,0 +1,2 @@
+pub mod invitation_repository;
+pub mod organization_repository;
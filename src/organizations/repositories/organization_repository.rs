@@ -0,0 +1,390 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::types::member_response::MemberResponse;
+use crate::organizations::types::membership::Membership;
+use crate::organizations::types::membership_role::MembershipRole;
+use crate::organizations::types::organization::Organization;
+use log::{info, warn};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+///# Persist Organization
+///
+/// the creator becomes its first owner
+pub async fn persist_organization(
+    pool: &PgPool,
+    name: &str,
+    owner_id: &Uuid,
+) -> Result<Organization, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let organization = sqlx::query_as!(
+        Organization,
+        "insert into organizations(id, name) values ($1, $2) returning *",
+        Uuid::new_v4(),
+        name
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "insert into memberships(organization_id, user_id, role) values ($1, $2, $3)",
+        organization.id,
+        owner_id,
+        MembershipRole::OWNER.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(organization)
+}
+
+///# Get Membership
+///
+/// the organization along with the membership of the user, None when the user is not a member
+pub async fn get_membership(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Option<(Organization, Membership)>, ApplicationError> {
+    let row = sqlx::query!(
+        "select organizations.*, memberships.role, memberships.created_at as joined_at,
+        memberships.updated_at as membership_updated_at
+        from memberships join organizations on organizations.id = memberships.organization_id
+        where memberships.organization_id = $1 and memberships.user_id = $2",
+        organization_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        (
+            Organization {
+                id: row.id,
+                name: row.name,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+            Membership {
+                organization_id: row.id,
+                user_id: *user_id,
+                role: row.role,
+                created_at: row.joined_at,
+                updated_at: row.membership_updated_at,
+            },
+        )
+    }))
+}
+
+///# Get Organizations By User Id
+///
+/// every organization the user is a member of with the role held there, unknown roles are skipped
+pub async fn get_organizations_by_user_id(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<Vec<(Organization, MembershipRole)>, ApplicationError> {
+    let rows = sqlx::query!(
+        "select organizations.*, memberships.role
+        from memberships join organizations on organizations.id = memberships.organization_id
+        where memberships.user_id = $1 order by organizations.name",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| match row.role.parse() {
+            Ok(role) => Some((
+                Organization {
+                    id: row.id,
+                    name: row.name,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                },
+                role,
+            )),
+            Err(_) => {
+                warn!("user {} has unknown membership role {}", user_id, row.role);
+                None
+            }
+        })
+        .collect())
+}
+
+///# Get Members
+///
+/// every member of the organization in the order they joined, unknown roles are skipped
+pub async fn get_members(
+    pool: &PgPool,
+    organization_id: &Uuid,
+) -> Result<Vec<MemberResponse>, ApplicationError> {
+    let rows = sqlx::query!(
+        "select memberships.user_id, memberships.role, memberships.created_at, users.name, users.email
+        from memberships join users on users.id = memberships.user_id
+        where memberships.organization_id = $1 order by memberships.created_at",
+        organization_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| match row.role.parse() {
+            Ok(role) => Some(MemberResponse {
+                user_id: row.user_id,
+                name: row.name,
+                email: row.email,
+                role,
+                joined_at: row.created_at,
+            }),
+            Err(_) => {
+                warn!(
+                    "user {} has unknown membership role {}",
+                    row.user_id, row.role
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+//membership changes of one organization are serialized, so two owners can't demote each other at once
+async fn lock_organization(
+    tx: &mut Transaction<'_, Postgres>,
+    organization_id: &Uuid,
+) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "select id from organizations where id = $1 for update",
+        organization_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn count_owners(
+    tx: &mut Transaction<'_, Postgres>,
+    organization_id: &Uuid,
+) -> Result<i64, ApplicationError> {
+    Ok(sqlx::query_scalar!(
+        "select count(*) from memberships where organization_id = $1 and role = $2",
+        organization_id,
+        MembershipRole::OWNER.to_string()
+    )
+    .fetch_one(&mut **tx)
+    .await?
+    .unwrap_or_default())
+}
+
+///# Update Membership Role
+///
+/// the role only changes while the member still has the `current` role the permission check was made for
+///
+/// returns None when the member does not exist, no longer has the `current` role or is the last owner
+/// being demoted
+pub async fn update_membership_role(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+    current: MembershipRole,
+    role: MembershipRole,
+) -> Result<Option<Membership>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+    lock_organization(&mut tx, organization_id).await?;
+
+    let owners = count_owners(&mut tx, organization_id).await?;
+    let membership = sqlx::query_as!(
+        Membership,
+        "update memberships set role = $3
+        where organization_id = $1 and user_id = $2 and role = $6
+        and (role <> $4 or $3 = $4 or $5::bigint > 1) returning *",
+        organization_id,
+        user_id,
+        role.to_string(),
+        MembershipRole::OWNER.to_string(),
+        owners,
+        current.to_string()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(membership)
+}
+
+///# Remove Membership
+///
+/// returns false when the member is the last owner while others remain, ownership has to be handed over first
+///
+/// the organization is deleted along with its last member
+pub async fn remove_membership(
+    pool: &PgPool,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<bool, ApplicationError> {
+    let mut tx = pool.begin().await?;
+    lock_organization(&mut tx, organization_id).await?;
+
+    let owners = count_owners(&mut tx, organization_id).await?;
+    let members = sqlx::query_scalar!(
+        "select count(*) from memberships where organization_id = $1",
+        organization_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .unwrap_or_default();
+
+    let removed = sqlx::query!(
+        "delete from memberships
+        where organization_id = $1 and user_id = $2 and (role <> $3 or $4::bigint > 1 or $5::bigint = 1)",
+        organization_id,
+        user_id,
+        MembershipRole::OWNER.to_string(),
+        owners,
+        members
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        == 1;
+
+    if removed && members == 1 {
+        sqlx::query!("delete from organizations where id = $1", organization_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(removed)
+}
+
+///# Hand Over Memberships
+///
+/// part of deleting the user, run in the transaction that deletes it
+///
+/// an organization the user is the last member of is deleted, where the user is the last owner the
+/// remaining member with the highest role, the longest standing among equals, becomes owner
+pub async fn hand_over_memberships(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+) -> Result<(), ApplicationError> {
+    //locked in a fixed order so two deletions sharing organizations can't deadlock
+    let organization_ids = sqlx::query_scalar!(
+        "select organization_id from memberships where user_id = $1 order by organization_id",
+        user_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    for organization_id in organization_ids {
+        lock_organization(tx, &organization_id).await?;
+        let members = sqlx::query_scalar!(
+            "select count(*) from memberships where organization_id = $1",
+            organization_id
+        )
+        .fetch_one(&mut **tx)
+        .await?
+        .unwrap_or_default();
+        if members == 1 {
+            sqlx::query!("delete from organizations where id = $1", organization_id)
+                .execute(&mut **tx)
+                .await?;
+            info!(
+                "organization {} deleted with its last member {}",
+                organization_id, user_id
+            );
+            continue;
+        }
+
+        let owners = count_owners(tx, &organization_id).await?;
+        let promoted = sqlx::query_scalar!(
+            "update memberships set role = $3
+            where organization_id = $1 and user_id = (
+                select user_id from memberships
+                where organization_id = $1 and user_id <> $2
+                order by case role when 'ADMIN' then 0 when 'MEMBER' then 1 else 2 end, created_at
+                limit 1
+            )
+            and $4::bigint = 1
+            and exists (
+                select 1 from memberships where organization_id = $1 and user_id = $2 and role = $3
+            )
+            returning user_id",
+            organization_id,
+            user_id,
+            MembershipRole::OWNER.to_string(),
+            owners
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        if let Some(promoted) = promoted {
+            info!(
+                "ownership of organization {} handed from {} to {}",
+                organization_id, user_id, promoted
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test]
+    async fn a_role_changed_since_the_check_is_kept(pool: PgPool) {
+        let (owner, member) = (Uuid::new_v4(), Uuid::new_v4());
+        for (id, email) in [(owner, "ada@x.io"), (member, "bob@x.io")] {
+            sqlx::query!(
+                "insert into users(id, name, email) values ($1, 'Ada', $2)",
+                id,
+                email
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let organization = persist_organization(&pool, "Acme", &owner).await.unwrap();
+        sqlx::query!(
+            "insert into memberships(organization_id, user_id, role) values ($1, $2, 'OWNER')",
+            organization.id,
+            member
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        //an admin checked the member while it was still a MEMBER
+        let updated = update_membership_role(
+            &pool,
+            &organization.id,
+            &member,
+            MembershipRole::MEMBER,
+            MembershipRole::ADMIN,
+        )
+        .await
+        .unwrap();
+        assert!(updated.is_none());
+        let (_, membership) = get_membership(&pool, &organization.id, &member)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(membership.role, "OWNER");
+
+        let updated = update_membership_role(
+            &pool,
+            &organization.id,
+            &member,
+            MembershipRole::OWNER,
+            MembershipRole::ADMIN,
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated.unwrap().role, "ADMIN");
+    }
+}
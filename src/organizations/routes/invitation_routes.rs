@@ -0,0 +1,10 @@
+use crate::organizations::services::invitation_service::{accept_invitation, preview_invitation};
+use axum::Router;
+use axum::routing::post;
+
+///opened from the invitation mail, the token is the credential, an existing account also has to be signed in
+pub fn invitations() -> Router {
+    Router::new()
+        .route("/preview", post(preview_invitation))
+        .route("/accept", post(accept_invitation))
+}
@@ -0,0 +1,2 @@
+pub mod invitation_routes;
+pub mod organization_routes;
@@ -0,0 +1,27 @@
+use crate::organizations::services::invitation_service::{
+    invite_member, list_invitations, resend_invitation, revoke_invitation,
+};
+use crate::organizations::services::organization_service::{
+    change_member_role, create_organization, get_current_organization, leave_organization,
+    list_members, list_members_for_client, list_organizations,
+};
+use axum::Router;
+use axum::routing::{delete, get, post, put};
+
+///`current` routes act in the organization named by the `X-Organization-Id` header, `/{id}/members`
+///is for machine clients
+pub fn organizations() -> Router {
+    Router::new()
+        .route("/", get(list_organizations).post(create_organization))
+        .route("/current", get(get_current_organization))
+        .route("/current/members", get(list_members))
+        .route("/current/members/{user_id}/role", put(change_member_role))
+        .route("/current/leave", post(leave_organization))
+        .route(
+            "/current/invitations",
+            get(list_invitations).post(invite_member),
+        )
+        .route("/current/invitations/{id}", delete(revoke_invitation))
+        .route("/current/invitations/{id}/resend", post(resend_invitation))
+        .route("/{id}/members", get(list_members_for_client))
+}
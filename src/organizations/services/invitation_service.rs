@@ -0,0 +1,586 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::security::jwt_key_set::jwt_key_set;
+use crate::application::security::password_hasher::hash_password;
+use crate::organizations::repositories::invitation_repository::{
+    accept_invitation as accept_pending_invitation, find_acceptable_invitation,
+    get_pending_invitations, persist_invitation, renew_invitation,
+    revoke_invitation as revoke_pending_invitation,
+};
+use crate::organizations::repositories::organization_repository::get_membership;
+use crate::organizations::types::accept_invitation_request::AcceptInvitationRequest;
+use crate::organizations::types::invitation::Invitation;
+use crate::organizations::types::invitation_accepted_response::InvitationAcceptedResponse;
+use crate::organizations::types::invitation_preview_response::InvitationPreviewResponse;
+use crate::organizations::types::invitation_request::InvitationRequest;
+use crate::organizations::types::invitation_response::InvitationResponse;
+use crate::organizations::types::invitation_token_request::InvitationTokenRequest;
+use crate::organizations::types::membership_role::MembershipRole;
+use crate::organizations::types::organization::Organization;
+use crate::organizations::types::organization_context::OrganizationContext;
+use crate::organizations::types::organization_response::OrganizationResponse;
+use crate::users::repositories::authentication_repository::save_new_user_and_allocate_a_role;
+use crate::users::repositories::user_repository::{
+    claim_unverified_account, find_user_by_email_ignoring_case,
+};
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::jwt_service::{Claim, TokenType, generate_token};
+use crate::users::services::password_policy_service::validate_password;
+use crate::users::services::profile_service::validate_profile_name;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_source::UserSource;
+use axum::extract::Path;
+use axum::{Extension, Json};
+use log::{error, info, warn};
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+fn internal_error(error: ApplicationError) -> (StatusCode, Json<ApplicationError>) {
+    error!("{:?}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error))
+}
+
+fn invitation_error(status: StatusCode, message: &str) -> (StatusCode, Json<ApplicationError>) {
+    (
+        status,
+        Json(ApplicationError::new("Invitation Error", message)),
+    )
+}
+
+fn invitation_expires_at() -> Result<OffsetDateTime, ApplicationError> {
+    let expiration: i64 = env::var("INVITATION_EXPIRATION")
+        .unwrap_or(String::from("604800000"))
+        .parse()?;
+    Ok(OffsetDateTime::now_utc() + Duration::milliseconds(expiration))
+}
+
+///# Send Invitation
+///
+/// mail a signed link to the invited email, it stops working at the expiry of the invitation
+///
+/// a resent invitation keeps its id, so earlier links of it work until their own expiry
+async fn send_invitation(
+    state: &AppState,
+    invitation: &Invitation,
+    organization: &Organization,
+    inviter: &User,
+) -> Result<(), ApplicationError> {
+    let url =
+        env::var("INVITATION_URL").unwrap_or(String::from("http://localhost:3000/invitations"));
+    let token = generate_token(Claim {
+        exp: invitation.expires_at.unix_timestamp() as usize,
+        jti: invitation.id.to_string(),
+        sub: invitation.email.clone(),
+        token_type: TokenType::INVITATION,
+        scope: None,
+    })?;
+
+    state
+        .mailer
+        .send(MailMessage {
+            to: invitation.email.clone(),
+            subject: format!("You have been invited to join {}", organization.name),
+            body: format!(
+                "Hi,\n\n{} invited you to join {} as {}.\n\
+                Open the link below to accept, you can create an account there if you don't have one yet.\n\
+                The invitation expires on {}.\n\n{url}?token={token}\n\n\
+                If you were not expecting this, you can ignore this email.\n",
+                inviter.name,
+                organization.name,
+                invitation.role.to_lowercase(),
+                invitation.expires_at.date()
+            ),
+        })
+        .await
+}
+
+///# Invite Member
+///
+/// admins invite by email with a role up to their own, the invitation expires after INVITATION_EXPIRATION (ms)
+pub async fn invite_member(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+    request: Json<InvitationRequest>,
+) -> Result<(StatusCode, Json<InvitationResponse>), (StatusCode, Json<ApplicationError>)> {
+    context.require(MembershipRole::ADMIN)?;
+
+    let role = request.0.role;
+    if !context.role.includes(role) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApplicationError::new(
+                "Authorization Error",
+                "You can't invite with a role above your own",
+            )),
+        ));
+    }
+
+    //emails are compared ignoring case, so the invitation is stored in lower case
+    let email = request.0.email.trim().to_lowercase();
+    let email = email.as_str();
+    if email.is_empty() || !email.contains('@') {
+        return Err(invitation_error(
+            StatusCode::BAD_REQUEST,
+            "A valid email is required",
+        ));
+    }
+
+    if let Some(user) = find_user_by_email_ignoring_case(&state.pool, email)
+        .await
+        .map_err(internal_error)?
+        && get_membership(&state.pool, &context.organization.id, &user.id)
+            .await
+            .map_err(internal_error)?
+            .is_some()
+    {
+        return Err(invitation_error(
+            StatusCode::CONFLICT,
+            "This email already belongs to a member",
+        ));
+    }
+
+    let expires_at = invitation_expires_at().map_err(internal_error)?;
+    let invitation = persist_invitation(
+        &state.pool,
+        &context.organization.id,
+        email,
+        role,
+        &context.user.id,
+        expires_at,
+    )
+    .await
+    .map_err(internal_error)?
+    .ok_or(invitation_error(
+        StatusCode::CONFLICT,
+        "This email already has a pending invitation, resend it instead",
+    ))?;
+
+    //the invitation can be resent if this fails
+    if let Err(error) =
+        send_invitation(&state, &invitation, &context.organization, &context.user).await
+    {
+        error!("{:?}", error);
+    }
+    info!(
+        "user {} invited {} to organization {} as {}",
+        context.user.id, invitation.id, context.organization.id, role
+    );
+
+    let response = InvitationResponse::try_from(invitation).map_err(internal_error)?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+///# List Invitations
+///
+/// pending invitations of the current organization, expired ones included so they can be resent
+pub async fn list_invitations(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<Vec<InvitationResponse>>, (StatusCode, Json<ApplicationError>)> {
+    context.require(MembershipRole::ADMIN)?;
+
+    let invitations = get_pending_invitations(&state.pool, &context.organization.id)
+        .await
+        .map_err(internal_error)?;
+    let invitations = invitations
+        .into_iter()
+        .map(InvitationResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal_error)?;
+    Ok(Json(invitations))
+}
+
+///# Resend Invitation
+///
+/// mail a pending invitation again, its expiry starts over
+pub async fn resend_invitation(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<InvitationResponse>, (StatusCode, Json<ApplicationError>)> {
+    context.require(MembershipRole::ADMIN)?;
+
+    let expires_at = invitation_expires_at().map_err(internal_error)?;
+    let invitation = renew_invitation(&state.pool, &id, &context.organization.id, expires_at)
+        .await
+        .map_err(internal_error)?
+        .ok_or(invitation_error(
+            StatusCode::NOT_FOUND,
+            "Pending invitation not found",
+        ))?;
+
+    send_invitation(&state, &invitation, &context.organization, &context.user)
+        .await
+        .map_err(internal_error)?;
+    info!("invitation {} resent by user {}", id, context.user.id);
+
+    Ok(Json(
+        InvitationResponse::try_from(invitation).map_err(internal_error)?,
+    ))
+}
+
+///# Revoke Invitation
+///
+/// links of a revoked invitation stop working right away
+pub async fn revoke_invitation(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    context.require(MembershipRole::ADMIN)?;
+
+    match revoke_pending_invitation(&state.pool, &id, &context.organization.id).await {
+        Ok(true) => {
+            info!("invitation {} revoked by user {}", id, context.user.id);
+            Ok(Json(MessageResponse::new("Invitation revoked")))
+        }
+        Ok(false) => Err(invitation_error(
+            StatusCode::NOT_FOUND,
+            "Pending invitation not found",
+        )),
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+//the token is signed, the invitation behind it still has to be pending and for the same email
+async fn find_invitation_by_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<(Invitation, Organization), (StatusCode, Json<ApplicationError>)> {
+    let invalid = || invitation_error(StatusCode::NOT_FOUND, "Invalid or expired invitation");
+    let claims = jwt_key_set()
+        .map_err(internal_error)?
+        .decode::<Claim>(token)
+        .map_err(|_| invalid())?
+        .claims;
+    if claims.token_type != TokenType::INVITATION {
+        return Err(invalid());
+    }
+    let id = Uuid::parse_str(&claims.jti).map_err(|_| invalid())?;
+    find_acceptable_invitation(pool, &id)
+        .await
+        .map_err(internal_error)?
+        .filter(|(invitation, _)| invitation.email.eq_ignore_ascii_case(&claims.sub))
+        .ok_or_else(invalid)
+}
+
+///# Preview Invitation
+///
+/// what the invitation grants and whether its email has an account, used to prefill the signup form
+pub async fn preview_invitation(
+    state: Extension<Arc<AppState>>,
+    request: Json<InvitationTokenRequest>,
+) -> Result<Json<InvitationPreviewResponse>, (StatusCode, Json<ApplicationError>)> {
+    let (invitation, organization) =
+        find_invitation_by_token(&state.pool, &request.0.token).await?;
+    let account_exists = find_user_by_email_ignoring_case(&state.pool, &invitation.email)
+        .await
+        .map_err(internal_error)?
+        .is_some();
+
+    Ok(Json(InvitationPreviewResponse {
+        organization_id: organization.id,
+        organization_name: organization.name,
+        role: invitation.role().map_err(internal_error)?,
+        email: invitation.email,
+        account_exists,
+    }))
+}
+
+///# Create Invited User
+///
+/// sign the invited email up, the invitation proves the email so the account starts verified
+///
+/// without a password the account signs in by magic link
+async fn create_invited_user(
+    state: &AppState,
+    email: &str,
+    request: &AcceptInvitationRequest,
+) -> Result<User, (StatusCode, Json<ApplicationError>)> {
+    let name = validate_profile_name(request.name.as_deref().unwrap_or_default())?;
+    let password = match &request.password {
+        Some(password) => {
+            if request.confirm_password.as_ref() != Some(password) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApplicationError::new(
+                        "Password Miss match",
+                        "Passwords must match",
+                    )),
+                ));
+            }
+            validate_password(password, &[&name, email])?;
+            Some(hash_password(password).await.map_err(internal_error)?)
+        }
+        None => None,
+    };
+
+    let user = User {
+        id: Uuid::new_v4(),
+        name,
+        email: email.to_owned(),
+        is_enabled: Some(true),
+        is_account_non_expired: Some(true),
+        is_account_non_locked: Some(true),
+        password,
+        image_url: None,
+        created_at: None,
+        updated_at: None,
+        source: UserSource::SYSTEM,
+        failed_login_attempts: 0,
+        lockout_count: 0,
+        locked_until: None,
+        account_expires_at: None,
+        avatar_id: None,
+        deletion_scheduled_at: None,
+        email_verified: true,
+    };
+    save_new_user_and_allocate_a_role(&state.pool, &user)
+        .await
+        .map_err(internal_error)?;
+    info!("created invited user {}", user.id);
+    Ok(user)
+}
+
+///# Accept Invitation
+///
+/// an existing account of the invited email has to be signed in to join the organization, otherwise the
+/// account is created from the name and optional password of the request
+///
+/// an unverified account may have been registered by someone else, the invitation proves the email so its
+/// password and sessions are dropped and it joins signing in by magic link
+pub async fn accept_invitation(
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    user: Option<User>,
+    request: Json<AcceptInvitationRequest>,
+) -> Result<Json<InvitationAcceptedResponse>, (StatusCode, Json<ApplicationError>)> {
+    let (invitation, _) = find_invitation_by_token(&state.pool, &request.0.token).await?;
+
+    let signed_in = user.is_some();
+    let existing = match user {
+        Some(user) if user.email.eq_ignore_ascii_case(&invitation.email) => Some(user),
+        Some(_) => {
+            return Err(invitation_error(
+                StatusCode::FORBIDDEN,
+                "The invitation is for another email, sign in with that account to accept it",
+            ));
+        }
+        None => find_user_by_email_ignoring_case(&state.pool, &invitation.email)
+            .await
+            .map_err(internal_error)?,
+    };
+    let account_created = existing.is_none();
+    let user = match existing {
+        Some(user) if signed_in => user,
+        Some(user) if !user.email_verified => {
+            if claim_unverified_account(&state.pool, &user.id)
+                .await
+                .map_err(internal_error)?
+            {
+                warn!(
+                    "unverified user {} claimed by accepting invitation {}, password and sessions dropped",
+                    user.id, invitation.id
+                );
+                User {
+                    password: None,
+                    email_verified: true,
+                    ..user
+                }
+            } else {
+                user
+            }
+        }
+        Some(_) => {
+            return Err(invitation_error(
+                StatusCode::UNAUTHORIZED,
+                "An account with this email exists, sign in to accept the invitation",
+            ));
+        }
+        None => {
+            let user = create_invited_user(&state, &invitation.email, &request.0).await?;
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(AuditEventType::SIGNUP, AuditOutcome::SUCCESS)
+                    .actor(user.id)
+                    .context(&context)
+                    .metadata(json!({"method": "invitation", "invitation_id": invitation.id})),
+            )
+            .await;
+            user
+        }
+    };
+
+    accept_pending_invitation(&state.pool, &invitation.id, &user.id)
+        .await
+        .map_err(internal_error)?
+        .ok_or(invitation_error(
+            StatusCode::NOT_FOUND,
+            "Invalid or expired invitation",
+        ))?;
+    info!(
+        "user {} accepted invitation {} to organization {}",
+        user.id, invitation.id, invitation.organization_id
+    );
+
+    //a member accepting keeps the role already held
+    let (organization, membership) =
+        get_membership(&state.pool, &invitation.organization_id, &user.id)
+            .await
+            .map_err(internal_error)?
+            .ok_or(invitation_error(
+                StatusCode::NOT_FOUND,
+                "Invalid or expired invitation",
+            ))?;
+    Ok(Json(InvitationAcceptedResponse {
+        organization: OrganizationResponse::new(
+            organization,
+            membership.role().map_err(internal_error)?,
+        ),
+        user_id: user.id,
+        account_created,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mail::memory_mailer::MemoryMailer;
+    use crate::application::security::jwt_key_set::{JwtKey, JwtKeySet};
+    use crate::organizations::repositories::organization_repository::persist_organization;
+    use crate::users::repositories::user_repository::get_user_by_id;
+
+    //tests share the process wide key set, whichever runs first installs it
+    fn install_test_key() {
+        if jwt_key_set().is_err() {
+            let _ = JwtKeySet::new(vec![JwtKey::from_secret("test-secret")], None)
+                .unwrap()
+                .install();
+        }
+    }
+
+    async fn insert_user(pool: &PgPool, email: &str, email_verified: bool) -> User {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', $2, 'hash', $3)",
+            id,
+            email,
+            email_verified
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        get_user_by_id(pool, &id).await.unwrap()
+    }
+
+    //invite Bob@x.io as the owner of a new organization, returns the token of the mailed link
+    async fn invite(state: &Arc<AppState>, mailer: &MemoryMailer) -> String {
+        install_test_key();
+        let owner = insert_user(&state.pool, "owner@x.io", true).await;
+        let organization = persist_organization(&state.pool, "Acme", &owner.id)
+            .await
+            .unwrap();
+        let context = OrganizationContext {
+            user: owner,
+            organization,
+            role: MembershipRole::OWNER,
+        };
+        let (status, _) = invite_member(
+            context,
+            Extension(state.clone()),
+            Json(InvitationRequest {
+                email: String::from(" Bob@X.io "),
+                role: MembershipRole::MEMBER,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let messages = mailer.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].to, "bob@x.io");
+        assert_eq!(messages[0].subject, "You have been invited to join Acme");
+        let (_, link) = messages[0].body.split_once("?token=").unwrap();
+        link.split_whitespace().next().unwrap().to_owned()
+    }
+
+    fn accept_request(token: String) -> Json<AcceptInvitationRequest> {
+        Json(AcceptInvitationRequest {
+            token,
+            name: None,
+            password: None,
+            confirm_password: None,
+        })
+    }
+
+    #[sqlx::test]
+    async fn an_existing_account_has_to_be_signed_in_to_accept(pool: PgPool) {
+        let (state, mailer) = AppState::for_tests(pool);
+        let bob = insert_user(&state.pool, "bob@X.io", true).await;
+        let token = invite(&state, &mailer).await;
+
+        let (status, _) = accept_invitation(
+            Extension(state.clone()),
+            SessionContext::default(),
+            None,
+            accept_request(token.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let other = insert_user(&state.pool, "eve@x.io", true).await;
+        let (status, _) = accept_invitation(
+            Extension(state.clone()),
+            SessionContext::default(),
+            Some(other),
+            accept_request(token.clone()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let accepted = accept_invitation(
+            Extension(state.clone()),
+            SessionContext::default(),
+            Some(bob.clone()),
+            accept_request(token),
+        )
+        .await
+        .unwrap();
+        assert_eq!(accepted.0.user_id, bob.id);
+        assert!(!accepted.0.account_created);
+    }
+
+    #[sqlx::test]
+    async fn an_unverified_account_is_claimed(pool: PgPool) {
+        let (state, mailer) = AppState::for_tests(pool);
+        let bob = insert_user(&state.pool, "bob@x.io", false).await;
+        let token = invite(&state, &mailer).await;
+
+        let accepted = accept_invitation(
+            Extension(state.clone()),
+            SessionContext::default(),
+            None,
+            accept_request(token),
+        )
+        .await
+        .unwrap();
+        assert_eq!(accepted.0.user_id, bob.id);
+        let stored = get_user_by_id(&state.pool, &bob.id).await.unwrap();
+        assert!(stored.password.is_none() && stored.email_verified);
+    }
+}
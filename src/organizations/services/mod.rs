@@ -0,0 +1,2 @@
+pub mod invitation_service;
+pub mod organization_service;
@@ -0,0 +1,223 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::repositories::organization_repository::{
+    get_members, get_membership, get_organizations_by_user_id, persist_organization,
+    remove_membership, update_membership_role,
+};
+use crate::organizations::types::member_response::MemberResponse;
+use crate::organizations::types::membership_role::MembershipRole;
+use crate::organizations::types::membership_role_request::MembershipRoleRequest;
+use crate::organizations::types::organization_context::OrganizationContext;
+use crate::organizations::types::organization_request::OrganizationRequest;
+use crate::organizations::types::organization_response::OrganizationResponse;
+use crate::users::services::profile_service::validate_profile_name;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::require_scope::{MembersRead, RequireScope};
+use crate::users::types::user::User;
+use axum::extract::Path;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn internal_error(error: ApplicationError) -> (StatusCode, Json<ApplicationError>) {
+    error!("{:?}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error))
+}
+
+///# Create Organization
+///
+/// the signed in user becomes its owner
+pub async fn create_organization(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    request: Json<OrganizationRequest>,
+) -> Result<(StatusCode, Json<OrganizationResponse>), (StatusCode, Json<ApplicationError>)> {
+    let name = validate_profile_name(&request.0.name)?;
+    let organization = persist_organization(&state.pool, &name, &user.id)
+        .await
+        .map_err(internal_error)?;
+    info!(
+        "organization {} created by user {}",
+        organization.id, user.id
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(OrganizationResponse::new(
+            organization,
+            MembershipRole::OWNER,
+        )),
+    ))
+}
+
+///# List Organizations
+///
+/// every organization the signed in user is a member of, with the role held there
+pub async fn list_organizations(
+    user: User,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<Vec<OrganizationResponse>>, (StatusCode, Json<ApplicationError>)> {
+    let organizations = get_organizations_by_user_id(&state.pool, &user.id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(
+        organizations
+            .into_iter()
+            .map(|(organization, role)| OrganizationResponse::new(organization, role))
+            .collect(),
+    ))
+}
+
+///# Get Current Organization
+///
+/// the organization named by the `X-Organization-Id` header
+pub async fn get_current_organization(context: OrganizationContext) -> Json<OrganizationResponse> {
+    Json(OrganizationResponse::new(
+        context.organization,
+        context.role,
+    ))
+}
+
+///# List Members
+///
+/// every member of the current organization, visible to all of its members
+pub async fn list_members(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<Vec<MemberResponse>>, (StatusCode, Json<ApplicationError>)> {
+    let members = get_members(&state.pool, &context.organization.id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(members))
+}
+
+///# List Members For Client
+///
+/// the member directory for machine clients holding the `members:read` scope, their service user
+/// must be a member of the organization
+pub async fn list_members_for_client(
+    RequireScope { user, .. }: RequireScope<MembersRead>,
+    state: Extension<Arc<AppState>>,
+    Path(organization_id): Path<Uuid>,
+) -> Result<Json<Vec<MemberResponse>>, (StatusCode, Json<ApplicationError>)> {
+    match get_membership(&state.pool, &organization_id, &user.id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ApplicationError::new("Not Found", "Organization not found")),
+            ));
+        }
+        Err(error) => return Err(internal_error(error)),
+    }
+    let members = get_members(&state.pool, &organization_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(members))
+}
+
+///# Change Member Role
+///
+/// admins manage members and viewers and can grant up to admin, owners manage everyone
+///
+/// the last owner can't be demoted
+pub async fn change_member_role(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+    Path(user_id): Path<Uuid>,
+    request: Json<MembershipRoleRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    context.require(MembershipRole::ADMIN)?;
+
+    let target = match get_membership(&state.pool, &context.organization.id, &user_id).await {
+        Ok(Some((_, membership))) => membership.role().map_err(internal_error)?,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ApplicationError::new("Not Found", "Member not found")),
+            ));
+        }
+        Err(error) => return Err(internal_error(error)),
+    };
+
+    let role = request.0.role;
+    let allowed = context.role == MembershipRole::OWNER
+        || (context.role.outranks(target) && context.role.includes(role));
+    if !allowed {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApplicationError::new(
+                "Authorization Error",
+                "You can't change the role of this member",
+            )),
+        ));
+    }
+
+    match update_membership_role(
+        &state.pool,
+        &context.organization.id,
+        &user_id,
+        target,
+        role,
+    )
+    .await
+    {
+        Ok(Some(_)) => {
+            info!(
+                "user {} is now {} in organization {}",
+                user_id, role, context.organization.id
+            );
+            Ok(Json(MessageResponse::new("Member role changed")))
+        }
+        //the member left or got another role since the check, otherwise it is the last owner
+        Ok(None) => match get_membership(&state.pool, &context.organization.id, &user_id).await {
+            Ok(None) => Err((
+                StatusCode::NOT_FOUND,
+                Json(ApplicationError::new("Not Found", "Member not found")),
+            )),
+            Ok(Some((_, membership))) if membership.role != target.to_string() => Err((
+                StatusCode::CONFLICT,
+                Json(ApplicationError::new(
+                    "Organization Error",
+                    "The member's role was changed meanwhile, try again",
+                )),
+            )),
+            Ok(Some(_)) => Err((
+                StatusCode::CONFLICT,
+                Json(ApplicationError::new(
+                    "Organization Error",
+                    "An organization needs at least one owner",
+                )),
+            )),
+            Err(error) => Err(internal_error(error)),
+        },
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+///# Leave Organization
+///
+/// the last owner has to hand over ownership first, unless nobody else is left
+pub async fn leave_organization(
+    context: OrganizationContext,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match remove_membership(&state.pool, &context.organization.id, &context.user.id).await {
+        Ok(true) => {
+            info!(
+                "user {} left organization {}",
+                context.user.id, context.organization.id
+            );
+            Ok(Json(MessageResponse::new("You left the organization")))
+        }
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            Json(ApplicationError::new(
+                "Organization Error",
+                "Make another member owner before leaving",
+            )),
+        )),
+        Err(error) => Err(internal_error(error)),
+    }
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+///name and password are only used when the invited email has no account yet,
+///without a password the new account signs in by magic link
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+    pub name: Option<String>,
+    pub password: Option<String>,
+    pub confirm_password: Option<String>,
+}
@@ -0,0 +1,25 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: OffsetDateTime,
+    pub accepted_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl Invitation {
+    pub fn role(&self) -> Result<MembershipRole, ApplicationError> {
+        self.role.parse()
+    }
+}
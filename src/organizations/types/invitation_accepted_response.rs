@@ -0,0 +1,11 @@
+use crate::organizations::types::organization_response::OrganizationResponse;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+///`account_created` is set when accepting signed the invited email up
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct InvitationAcceptedResponse {
+    pub organization: OrganizationResponse,
+    pub user_id: Uuid,
+    pub account_created: bool,
+}
@@ -0,0 +1,13 @@
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+///what an invitation grants, `account_exists` tells the client whether to show the signup form
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct InvitationPreviewResponse {
+    pub organization_id: Uuid,
+    pub organization_name: String,
+    pub email: String,
+    pub role: MembershipRole,
+    pub account_exists: bool,
+}
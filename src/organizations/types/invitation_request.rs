@@ -0,0 +1,8 @@
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct InvitationRequest {
+    pub email: String,
+    pub role: MembershipRole,
+}
@@ -0,0 +1,34 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::types::invitation::Invitation;
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///a pending invitation as seen by the admins of its organization, the token is only ever mailed
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct InvitationResponse {
+    pub id: Uuid,
+    pub email: String,
+    pub role: MembershipRole,
+    pub invited_by: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl TryFrom<Invitation> for InvitationResponse {
+    type Error = ApplicationError;
+
+    fn try_from(invitation: Invitation) -> Result<Self, Self::Error> {
+        Ok(Self {
+            role: invitation.role()?,
+            id: invitation.id,
+            email: invitation.email,
+            invited_by: invitation.invited_by,
+            expires_at: invitation.expires_at,
+            created_at: invitation.created_at,
+        })
+    }
+}
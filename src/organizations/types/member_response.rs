@@ -0,0 +1,14 @@
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MemberResponse {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub role: MembershipRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub joined_at: OffsetDateTime,
+}
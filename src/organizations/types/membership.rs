@@ -0,0 +1,20 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Membership {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
+
+impl Membership {
+    pub fn role(&self) -> Result<MembershipRole, ApplicationError> {
+        self.role.parse()
+    }
+}
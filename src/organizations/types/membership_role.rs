@@ -0,0 +1,63 @@
+use crate::application::errors::application_error::ApplicationError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+///# Membership Role
+///
+/// what a member may do within one organization, every role includes the ones below it
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum MembershipRole {
+    OWNER,
+    ADMIN,
+    MEMBER,
+    VIEWER,
+}
+
+impl MembershipRole {
+    fn rank(&self) -> u8 {
+        match self {
+            Self::OWNER => 3,
+            Self::ADMIN => 2,
+            Self::MEMBER => 1,
+            Self::VIEWER => 0,
+        }
+    }
+
+    pub fn includes(&self, role: MembershipRole) -> bool {
+        self.rank() >= role.rank()
+    }
+
+    pub fn outranks(&self, role: MembershipRole) -> bool {
+        self.rank() > role.rank()
+    }
+}
+
+impl FromStr for MembershipRole {
+    type Err = ApplicationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "OWNER" => Ok(Self::OWNER),
+            "ADMIN" => Ok(Self::ADMIN),
+            "MEMBER" => Ok(Self::MEMBER),
+            "VIEWER" => Ok(Self::VIEWER),
+            _ => Err(ApplicationError::new(
+                "Organization Error",
+                format!("Unknown membership role {}", value),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MembershipRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OWNER => write!(f, "OWNER"),
+            Self::ADMIN => write!(f, "ADMIN"),
+            Self::MEMBER => write!(f, "MEMBER"),
+            Self::VIEWER => write!(f, "VIEWER"),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+use crate::organizations::types::membership_role::MembershipRole;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MembershipRoleRequest {
+    pub role: MembershipRole,
+}
@@ -0,0 +1,15 @@
+pub mod accept_invitation_request;
+pub mod invitation;
+pub mod invitation_accepted_response;
+pub mod invitation_preview_response;
+pub mod invitation_request;
+pub mod invitation_response;
+pub mod invitation_token_request;
+pub mod member_response;
+pub mod membership;
+pub mod membership_role;
+pub mod membership_role_request;
+pub mod organization;
+pub mod organization_context;
+pub mod organization_request;
+pub mod organization_response;
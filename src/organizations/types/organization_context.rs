@@ -0,0 +1,100 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::repositories::organization_repository::get_membership;
+use crate::organizations::types::membership_role::MembershipRole;
+use crate::organizations::types::organization::Organization;
+use crate::users::types::user::User;
+use axum::Json;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use log::error;
+use reqwest::StatusCode;
+use std::sync::Arc;
+use uuid::Uuid;
+
+///header naming the organization a request acts in
+pub const ORGANIZATION_HEADER: &str = "X-Organization-Id";
+
+///# Organization Context
+///
+/// authenticated user acting in the organization named by the `X-Organization-Id` header
+///
+/// 400 without a valid header, 404 when the user is not a member, so other organizations can't be probed
+pub struct OrganizationContext {
+    pub user: User,
+    pub organization: Organization,
+    pub role: MembershipRole,
+}
+
+impl OrganizationContext {
+    ///403 unless the member holds at least `role`
+    pub fn require(
+        &self,
+        role: MembershipRole,
+    ) -> Result<(), (StatusCode, Json<ApplicationError>)> {
+        match self.role.includes(role) {
+            true => Ok(()),
+            false => Err((
+                StatusCode::FORBIDDEN,
+                Json(ApplicationError::new(
+                    "Authorization Error",
+                    format!("{} role required in this organization", role),
+                )),
+            )),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for OrganizationContext
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = User::from_request_parts(parts, state).await?;
+
+        let organization_id = parts
+            .headers
+            .get(ORGANIZATION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| Uuid::parse_str(value.trim()).ok())
+            .ok_or((
+                StatusCode::BAD_REQUEST,
+                Json(ApplicationError::new(
+                    "Organization Error",
+                    format!("A valid {} header is required", ORGANIZATION_HEADER),
+                )),
+            ))?;
+
+        let Some(app_state) = parts.extensions.get::<Arc<AppState>>() else {
+            error!("Application State Not Found");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApplicationError::generic("Application State Not Found")),
+            ));
+        };
+
+        let membership = get_membership(&app_state.pool, &organization_id, &user.id)
+            .await
+            .and_then(|membership| match membership {
+                Some((organization, membership)) => Ok(Some((organization, membership.role()?))),
+                None => Ok(None),
+            });
+        match membership {
+            Ok(Some((organization, role))) => Ok(Self {
+                user,
+                organization,
+                role,
+            }),
+            Ok(None) => Err((
+                StatusCode::NOT_FOUND,
+                Json(ApplicationError::new("Not Found", "Organization not found")),
+            )),
+            Err(error) => {
+                error!("{:?}", error);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use crate::organizations::types::membership_role::MembershipRole;
+use crate::organizations::types::organization::Organization;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///an organization as seen by one of its members
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OrganizationResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub role: MembershipRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl OrganizationResponse {
+    pub fn new(organization: Organization, role: MembershipRole) -> Self {
+        Self {
+            id: organization.id,
+            name: organization.name,
+            role,
+            created_at: organization.created_at,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::api_key::ApiKey;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn persist_api_key(pool: &PgPool, api_key: &ApiKey) -> Result<ApiKey, ApplicationError> {
+    Ok(sqlx::query_as!(
+        ApiKey,
+        "insert into api_keys(id, user_id, name, prefix, key_hash, scopes, expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7) returning *",
+        api_key.id,
+        api_key.user_id,
+        api_key.name,
+        api_key.prefix,
+        api_key.key_hash,
+        &api_key.scopes,
+        api_key.expires_at
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///keys of the user that are not revoked, newest first
+pub async fn get_api_keys_by_user_id(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<Vec<ApiKey>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        ApiKey,
+        "select * from api_keys where user_id = $1 and revoked_at is null
+        order by created_at desc",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///# Use Api Key
+///
+/// find a usable key by its digest and record that it was used
+///
+/// revoked and expired keys are not returned
+pub async fn use_api_key(
+    pool: &PgPool,
+    key_hash: &str,
+) -> Result<Option<ApiKey>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        ApiKey,
+        "update api_keys set last_used_at = now()
+        where key_hash = $1 and revoked_at is null and (expires_at is null or expires_at > now())
+        returning *",
+        key_hash
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+///id of a key that is neither revoked nor expired, without marking it used
+pub async fn find_active_api_key_id(
+    pool: &PgPool,
+    key_hash: &str,
+) -> Result<Option<Uuid>, ApplicationError> {
+    Ok(sqlx::query_scalar!(
+        "select id from api_keys
+        where key_hash = $1 and revoked_at is null and (expires_at is null or expires_at > now())",
+        key_hash
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn rename_api_key(
+    pool: &PgPool,
+    user_id: &Uuid,
+    id: &Uuid,
+    name: &str,
+) -> Result<Option<ApiKey>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        ApiKey,
+        "update api_keys set name = $3 where id = $2 and user_id = $1 and revoked_at is null
+        returning *",
+        user_id,
+        id,
+        name
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn revoke_api_key(
+    pool: &PgPool,
+    user_id: &Uuid,
+    id: &Uuid,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update api_keys set revoked_at = now() where id = $2 and user_id = $1 and revoked_at is null",
+        user_id,
+        id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1)
+}
@@ -0,0 +1,93 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::audit_event::AuditEvent;
+use crate::users::types::audit_event_query::AuditEventQuery;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn persist_audit_event(
+    pool: &PgPool,
+    event: &NewAuditEvent,
+) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "insert into audit_events(id, actor_id, event_type, outcome, ip_address, user_agent, metadata)
+        values ($1, $2, $3, $4, $5, $6, $7)",
+        Uuid::new_v4(),
+        event.actor_id,
+        event.event_type.to_string(),
+        event.outcome.to_string(),
+        event.ip_address,
+        event.user_agent,
+        event.metadata
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_audit_events_by_actor_id(
+    pool: &PgPool,
+    actor_id: &Uuid,
+) -> Result<Vec<AuditEvent>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        AuditEvent,
+        "select * from audit_events where actor_id = $1 order by created_at",
+        actor_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///# Search Audit Events
+///
+/// events matching every filter that is set, newest first, with the total count for paging
+pub async fn search_audit_events(
+    pool: &PgPool,
+    query: &AuditEventQuery,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<AuditEvent>, i64), ApplicationError> {
+    let events = sqlx::query_as!(
+        AuditEvent,
+        "select * from audit_events
+        where ($1::uuid is null or actor_id = $1)
+        and ($2::text is null or event_type = $2)
+        and ($3::text is null or outcome = $3)
+        and ($4::text is null or ip_address = $4)
+        and ($5::timestamptz is null or created_at >= $5)
+        and ($6::timestamptz is null or created_at < $6)
+        order by created_at desc, id
+        limit $7 offset $8",
+        query.actor_id,
+        query.event_type,
+        query.outcome,
+        query.ip_address,
+        query.from,
+        query.to,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        "select count(*) from audit_events
+        where ($1::uuid is null or actor_id = $1)
+        and ($2::text is null or event_type = $2)
+        and ($3::text is null or outcome = $3)
+        and ($4::text is null or ip_address = $4)
+        and ($5::timestamptz is null or created_at >= $5)
+        and ($6::timestamptz is null or created_at < $6)",
+        query.actor_id,
+        query.event_type,
+        query.outcome,
+        query.ip_address,
+        query.from,
+        query.to
+    )
+    .fetch_one(pool)
+    .await?
+    .unwrap_or_default();
+
+    Ok((events, total))
+}
@@ -3,6 +3,7 @@ use crate::users::types::role::Role;
 use crate::users::types::role_type::RoleType;
 use crate::users::types::user::User;
 use crate::users::types::user_response::UserResponse;
+use crate::users::types::user_source::UserSource;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -14,9 +15,13 @@ pub async fn save_new_user_and_allocate_a_role(
 
     let saved_user = sqlx::query_as!(
         User,
-        "insert into users (id, name, email, is_enabled, is_account_non_expired,
-                   is_account_non_locked, password, image_url, source)
-          VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING *",
+        r#"insert into users (id, name, email, is_enabled, is_account_non_expired,
+                   is_account_non_locked, password, image_url, source, email_verified)
+          VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified"#,
         &user.id,
         &user.name,
         &user.email,
@@ -25,20 +30,25 @@ pub async fn save_new_user_and_allocate_a_role(
         user.is_account_non_locked,
         user.password.as_deref(),
         user.image_url.as_deref(),
-        &user.source.to_string()
+        &user.source.to_string(),
+        user.email_verified
     )
     .fetch_one(&mut *tx)
     .await?;
 
-    let saved_role = sqlx::query_as!(
-        Role,
-        "insert into roles(id, user_id, role) values ($1, $2, $3) returning *",
+    let row = sqlx::query!(
+        "insert into roles(id, user_id, role) values ($1, $2, $3) returning id, user_id, role",
         Uuid::new_v4(),
         &saved_user.id,
         RoleType::USER.to_string()
     )
     .fetch_one(&mut *tx)
     .await?;
+    let saved_role = Role {
+        id: row.id,
+        user_id: row.user_id,
+        role: row.role.parse()?,
+    };
 
     tx.commit().await?;
 
@@ -47,7 +57,7 @@ pub async fn save_new_user_and_allocate_a_role(
             id: saved_user.id,
             name: saved_user.name,
             email: saved_user.email,
-            role: saved_role.role,
+            roles: vec![saved_role.role],
         }
     })
 }
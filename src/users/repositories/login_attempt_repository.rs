@@ -0,0 +1,116 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::ip_login_failure::IpLoginFailure;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///increment the failed attempts of the user, returns the attempts and lockouts so far
+pub async fn record_failed_login(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<(i32, i32), ApplicationError> {
+    let record = sqlx::query!(
+        "update users set failed_login_attempts = failed_login_attempts + 1
+        where id = $1 returning failed_login_attempts, lockout_count",
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((record.failed_login_attempts, record.lockout_count))
+}
+
+pub async fn lock_user(
+    pool: &PgPool,
+    user_id: &Uuid,
+    locked_until: OffsetDateTime,
+) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "update users set failed_login_attempts = 0, lockout_count = lockout_count + 1,
+        locked_until = $2, is_account_non_locked = false where id = $1",
+        user_id,
+        locked_until
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+///clear failed attempts, lockouts and any lock of the user, returns false when the user does not exist
+pub async fn reset_failed_logins(pool: &PgPool, user_id: &Uuid) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update users set failed_login_attempts = 0, lockout_count = 0,
+        locked_until = null, is_account_non_locked = true where id = $1",
+        user_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+///unlock users whose lock has run out, the lockout count is kept so backoff keeps growing
+pub async fn unlock_expired_users(pool: &PgPool) -> Result<u64, ApplicationError> {
+    Ok(sqlx::query!(
+        "update users set is_account_non_locked = true, locked_until = null
+        where locked_until is not null and locked_until <= now()"
+    )
+    .execute(pool)
+    .await?
+    .rows_affected())
+}
+
+pub async fn get_ip_login_failure(
+    pool: &PgPool,
+    ip: &str,
+) -> Result<Option<IpLoginFailure>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        IpLoginFailure,
+        "select * from ip_login_failures where ip = $1",
+        ip
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+///increment the failed attempts of the address, returns the attempts and lockouts so far
+pub async fn record_failed_ip_login(
+    pool: &PgPool,
+    ip: &str,
+) -> Result<(i32, i32), ApplicationError> {
+    let record = sqlx::query!(
+        "insert into ip_login_failures(ip, failed_attempts) values ($1, 1)
+        on conflict (ip) do update set failed_attempts = ip_login_failures.failed_attempts + 1
+        returning failed_attempts, lockout_count",
+        ip
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((record.failed_attempts, record.lockout_count))
+}
+
+pub async fn block_ip(
+    pool: &PgPool,
+    ip: &str,
+    blocked_until: OffsetDateTime,
+) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "update ip_login_failures set failed_attempts = 0, lockout_count = lockout_count + 1,
+        blocked_until = $2 where ip = $1",
+        ip,
+        blocked_until
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+///forget addresses that are not blocked and had no failure for a day
+pub async fn delete_stale_ip_login_failures(pool: &PgPool) -> Result<u64, ApplicationError> {
+    Ok(sqlx::query!(
+        "delete from ip_login_failures
+        where (blocked_until is null or blocked_until <= now()) and updated_at < now() - interval '1 day'"
+    )
+    .execute(pool)
+    .await?
+    .rows_affected())
+}
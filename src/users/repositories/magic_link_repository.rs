@@ -0,0 +1,103 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::magic_link::MagicLink;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///# Persist Magic Link
+///
+/// pending links of the email are marked used so only the latest one works, their attempts still count
+/// until they expire, expired links of anyone are removed
+pub async fn persist_magic_link(
+    pool: &PgPool,
+    id: &Uuid,
+    email: &str,
+    name: Option<&str>,
+    code_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<MagicLink, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("delete from magic_links where expires_at < now()")
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        "update magic_links set consumed_at = now() where email = $1 and consumed_at is null",
+        email
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let saved_link = sqlx::query_as!(
+        MagicLink,
+        "insert into magic_links(id, email, name, code_hash, expires_at)
+        values ($1, $2, $3, $4, $5) returning *",
+        id,
+        email,
+        name,
+        code_hash,
+        expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(saved_link)
+}
+
+///# Register Magic Link Attempt
+///
+/// count an attempt on the pending link of the email, returns None when there is none
+///
+/// the count returned adds up the attempts on every unexpired link of the email
+pub async fn register_magic_link_attempt(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<(MagicLink, i64)>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let Some(link) = sqlx::query_as!(
+        MagicLink,
+        "update magic_links set attempts = attempts + 1
+        where email = $1 and consumed_at is null and expires_at > now() returning *",
+        email
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let attempts = sqlx::query_scalar!(
+        r#"select coalesce(sum(attempts), 0) as "attempts!" from magic_links
+        where email = $1 and expires_at > now()"#,
+        email
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some((link, attempts)))
+}
+
+///# Consume Magic Link
+///
+/// use up a pending link, returns None for unknown, used or expired links and links with too many failed attempts
+pub async fn consume_magic_link(
+    pool: &PgPool,
+    id: &Uuid,
+    max_attempts: i32,
+) -> Result<Option<MagicLink>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        MagicLink,
+        "update magic_links set consumed_at = now()
+        where id = $1 and attempts <= $2 and consumed_at is null and expires_at > now() returning *",
+        id,
+        max_attempts
+    )
+    .fetch_optional(pool)
+    .await?)
+}
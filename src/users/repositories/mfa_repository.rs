@@ -0,0 +1,178 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::mfa_challenge::MfaChallenge;
+use crate::users::types::user_mfa::UserMfa;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub async fn get_user_mfa(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<Option<UserMfa>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        UserMfa,
+        "select * from user_mfa where user_id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+///store a new pending secret, replacing an enrolment that was never confirmed
+pub async fn persist_pending_mfa_secret(
+    pool: &PgPool,
+    user_id: &Uuid,
+    secret: &str,
+) -> Result<UserMfa, ApplicationError> {
+    sqlx::query_as!(
+        UserMfa,
+        "insert into user_mfa(user_id, secret) values ($1, $2)
+        on conflict (user_id) do update set secret = excluded.secret, last_used_step = null
+        where user_mfa.is_enabled = false
+        returning *",
+        user_id,
+        secret
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ApplicationError::new(
+        "MFA Error",
+        "Two-factor authentication is already enabled",
+    ))
+}
+
+///# Record Used Step
+///
+/// a code is accepted once, returns false when the step (or a later one) was already used
+pub async fn record_used_mfa_step(
+    pool: &PgPool,
+    user_id: &Uuid,
+    step: i64,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update user_mfa set last_used_step = $2
+        where user_id = $1 and (last_used_step is null or last_used_step < $2)",
+        user_id,
+        step
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1)
+}
+
+///# Enable MFA
+///
+/// confirm the enrolment and replace the recovery codes in one transaction
+pub async fn enable_mfa(
+    pool: &PgPool,
+    user_id: &Uuid,
+    recovery_code_hashes: &[String],
+) -> Result<(), ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "update user_mfa set is_enabled = true, confirmed_at = now() where user_id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("delete from mfa_recovery_codes where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for code_hash in recovery_code_hashes {
+        sqlx::query!(
+            "insert into mfa_recovery_codes(id, user_id, code_hash) values ($1, $2, $3)",
+            Uuid::new_v4(),
+            user_id,
+            code_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn delete_user_mfa(pool: &PgPool, user_id: &Uuid) -> Result<(), ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("delete from mfa_recovery_codes where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!("delete from user_mfa where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+///mark an unused recovery code as used, returns false when no such code exists
+pub async fn consume_recovery_code(
+    pool: &PgPool,
+    user_id: &Uuid,
+    code_hash: &str,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update mfa_recovery_codes set used_at = now()
+        where user_id = $1 and code_hash = $2 and used_at is null",
+        user_id,
+        code_hash
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1)
+}
+
+pub async fn persist_mfa_challenge(
+    pool: &PgPool,
+    user_id: &Uuid,
+    token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<MfaChallenge, ApplicationError> {
+    Ok(sqlx::query_as!(
+        MfaChallenge,
+        "insert into mfa_challenges(id, user_id, token_hash, expires_at)
+        values ($1, $2, $3, $4) returning *",
+        Uuid::new_v4(),
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///# Register Challenge Attempt
+///
+/// count an attempt on a pending challenge, returns None for unknown, used or expired challenges
+pub async fn register_mfa_challenge_attempt(
+    pool: &PgPool,
+    token_hash: &str,
+) -> Result<Option<MfaChallenge>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        MfaChallenge,
+        "update mfa_challenges set attempts = attempts + 1
+        where token_hash = $1 and consumed_at is null and expires_at > now() returning *",
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn consume_mfa_challenge(pool: &PgPool, id: &Uuid) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update mfa_challenges set consumed_at = now() where id = $1 and consumed_at is null",
+        id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1)
+}
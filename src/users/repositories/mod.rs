@@ -4,3 +4,23 @@ pub mod user_repository;
 pub mod token_repository;
 
 pub mod role_repository;
+
+pub mod verification_token_repository;
+
+pub mod password_reset_token_repository;
+
+pub mod oauth_state_repository;
+
+pub mod login_attempt_repository;
+
+pub mod mfa_repository;
+
+pub mod api_key_repository;
+
+pub mod oauth_client_repository;
+
+pub mod session_repository;
+
+pub mod audit_repository;
+
+pub mod magic_link_repository;
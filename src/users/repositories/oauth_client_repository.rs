@@ -0,0 +1,119 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::oauth_client::OAuthClient;
+use crate::users::types::role_type::RoleType;
+use crate::users::types::user::User;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+///# Save OAuth Client
+///
+/// create the service user, its APPLICATION role and the client in one transaction
+pub async fn save_oauth_client_with_service_user(
+    pool: &PgPool,
+    user: &User,
+    client: &OAuthClient,
+) -> Result<OAuthClient, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "insert into users (id, name, email, is_enabled, is_account_non_expired,
+                   is_account_non_locked, password, source, email_verified)
+          values ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        user.id,
+        user.name,
+        user.email,
+        user.is_enabled,
+        user.is_account_non_expired,
+        user.is_account_non_locked,
+        user.password.as_deref(),
+        user.source.to_string(),
+        user.email_verified
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "insert into roles(id, user_id, role) values ($1, $2, $3)",
+        Uuid::new_v4(),
+        user.id,
+        RoleType::APPLICATION.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let saved_client = sqlx::query_as!(
+        OAuthClient,
+        "insert into oauth_clients(id, client_id, client_secret_hash, name, scopes, user_id)
+        values ($1, $2, $3, $4, $5, $6) returning *",
+        client.id,
+        client.client_id,
+        client.client_secret_hash,
+        client.name,
+        &client.scopes,
+        user.id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(saved_client)
+}
+
+pub async fn get_active_oauth_client(
+    pool: &PgPool,
+    client_id: &str,
+) -> Result<Option<OAuthClient>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        OAuthClient,
+        "select * from oauth_clients where client_id = $1 and revoked_at is null",
+        client_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn get_oauth_clients(pool: &PgPool) -> Result<Vec<OAuthClient>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        OAuthClient,
+        "select * from oauth_clients order by created_at"
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///# Revoke OAuth Client
+///
+/// the client can no longer get tokens, its service user is disabled and its tokens revoked
+pub async fn revoke_oauth_client(
+    pool: &PgPool,
+    id: &Uuid,
+) -> Result<Option<OAuthClient>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let client = sqlx::query_as!(
+        OAuthClient,
+        "update oauth_clients set revoked_at = now() where id = $1 and revoked_at is null
+        returning *",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(client) = &client {
+        sqlx::query!(
+            "update users set is_enabled = false where id = $1",
+            client.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "update token set is_revoked = true where user_id = $1 and is_revoked = false",
+            client.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(client)
+}
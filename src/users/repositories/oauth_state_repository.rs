@@ -0,0 +1,46 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::oauth_state::OAuthState;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub async fn persist_oauth_state(
+    pool: &PgPool,
+    state_hash: &str,
+    code_verifier: &str,
+    nonce: &str,
+    expires_at: OffsetDateTime,
+) -> Result<OAuthState, ApplicationError> {
+    Ok(sqlx::query_as!(
+        OAuthState,
+        "insert into oauth_states(id, state_hash, code_verifier, nonce, expires_at)
+        values ($1, $2, $3, $4, $5) returning *",
+        Uuid::new_v4(),
+        state_hash,
+        code_verifier,
+        nonce,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///# Consume OAuth State
+///
+/// delete and return the state so it can only be used once, expired states are rejected
+pub async fn consume_oauth_state(
+    pool: &PgPool,
+    state_hash: &str,
+) -> Result<OAuthState, ApplicationError> {
+    sqlx::query_as!(
+        OAuthState,
+        "delete from oauth_states where state_hash = $1 and expires_at > now() returning *",
+        state_hash
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(ApplicationError::new(
+        "OAuth Error",
+        "Invalid or expired authorization state",
+    ))
+}
@@ -0,0 +1,116 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::password_reset_token::PasswordResetToken;
+use crate::users::types::user::User;
+use crate::users::types::user_source::UserSource;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///# Persist Password Reset Token
+///
+/// pending tokens of the user are removed so only the latest link works
+pub async fn persist_password_reset_token(
+    pool: &PgPool,
+    user_id: &Uuid,
+    token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<PasswordResetToken, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "delete from password_reset_tokens where user_id = $1 and consumed_at is null",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let saved_token = sqlx::query_as!(
+        PasswordResetToken,
+        "insert into password_reset_tokens(id, user_id, token_hash, expires_at)
+        values ($1, $2, $3, $4) returning *",
+        Uuid::new_v4(),
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(saved_token)
+}
+
+///# Reset Password With Token
+///
+/// consume the token, replace the password hash and revoke every issued token of the user
+///
+/// consumed and expired tokens are rejected
+pub async fn reset_password_with_token(
+    pool: &PgPool,
+    token_hash: &str,
+    password_hash: &str,
+) -> Result<PasswordResetToken, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let consumed_token = sqlx::query_as!(
+        PasswordResetToken,
+        "update password_reset_tokens set consumed_at = now()
+        where token_hash = $1 and consumed_at is null and expires_at > now() returning *",
+        token_hash
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApplicationError::new(
+        "Password Reset Error",
+        "Invalid or expired password reset token",
+    ))?;
+
+    sqlx::query!(
+        "update users set password = $1 where id = $2",
+        password_hash,
+        consumed_token.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "update token set is_revoked = true where user_id = $1",
+        consumed_token.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "update user_sessions set revoked_at = now() where user_id = $1 and revoked_at is null",
+        consumed_token.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(consumed_token)
+}
+
+///# Find User By Password Reset Token
+///
+/// the owner of a token that can still be consumed
+pub async fn find_user_by_password_reset_token(
+    pool: &PgPool,
+    token_hash: &str,
+) -> Result<Option<User>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"select users.id, users.name, users.email, users.is_enabled, users.is_account_non_expired,
+        users.is_account_non_locked, users.password, users.image_url, users.created_at,
+        users.updated_at, users.source as "source: UserSource", users.failed_login_attempts,
+        users.lockout_count, users.locked_until, users.account_expires_at, users.avatar_id,
+        users.deletion_scheduled_at, users.email_verified
+        from users join password_reset_tokens on password_reset_tokens.user_id = users.id
+        where token_hash = $1 and consumed_at is null and expires_at > now()"#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await?)
+}
@@ -1,14 +1,81 @@
 use crate::application::errors::application_error::ApplicationError;
-use crate::users::types::role::Role;
-use log::info;
+use crate::users::types::role_type::RoleType;
+use log::warn;
 use sqlx::PgPool;
 use uuid::Uuid;
-pub async fn get_token_by_user_id(pool: &PgPool, id: &Uuid) -> Result<Role, ApplicationError> {
-    info!("id {}", id);
 
-    Ok(
-        sqlx::query_as!(Role, "select * from roles where user_id = $1", id)
-            .fetch_one(pool)
-            .await?,
+///# Get Roles
+///
+/// every role held by the user, unknown role names are skipped instead of failing the request
+pub async fn get_roles_by_user_id(
+    pool: &PgPool,
+    id: &Uuid,
+) -> Result<Vec<RoleType>, ApplicationError> {
+    let roles = sqlx::query_scalar!(
+        "select role from roles where user_id = $1 order by role",
+        id
     )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(roles
+        .into_iter()
+        .filter_map(|role| match role.parse() {
+            Ok(role) => Some(role),
+            Err(_) => {
+                warn!("user {} has unknown role {}", id, role);
+                None
+            }
+        })
+        .collect())
+}
+
+pub async fn user_has_role(
+    pool: &PgPool,
+    id: &Uuid,
+    role: RoleType,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query_scalar!(
+        r#"select exists(select 1 from roles where user_id = $1 and role = $2) as "exists!""#,
+        id,
+        role.to_string()
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///returns false when the user already holds the role
+pub async fn grant_role(
+    pool: &PgPool,
+    id: &Uuid,
+    role: RoleType,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "insert into roles(id, user_id, role) values ($1, $2, $3)
+        on conflict (user_id, role) do nothing",
+        Uuid::new_v4(),
+        id,
+        role.to_string()
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1)
+}
+
+///returns false when the user did not hold the role
+pub async fn revoke_role(
+    pool: &PgPool,
+    id: &Uuid,
+    role: RoleType,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "delete from roles where user_id = $1 and role = $2",
+        id,
+        role.to_string()
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0)
 }
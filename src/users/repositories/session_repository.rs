@@ -0,0 +1,171 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::user_session::UserSession;
+use log::info;
+use sqlx::{PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///sessions that are neither revoked nor past their refresh token lifetime, most recently used first
+pub async fn get_active_sessions(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<Vec<UserSession>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        UserSession,
+        "select * from user_sessions
+        where user_id = $1 and revoked_at is null and expires_at > now()
+        order by last_seen_at desc",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///every session of the user including revoked and expired ones, oldest first
+pub async fn get_sessions_by_user_id(
+    pool: &PgPool,
+    user_id: &Uuid,
+) -> Result<Vec<UserSession>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        UserSession,
+        "select * from user_sessions where user_id = $1 order by created_at",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///# Touch Session
+///
+/// record that the session was used, written at most once a minute per session
+pub async fn touch_session(pool: &PgPool, id: &Uuid) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "update user_sessions set last_seen_at = now()
+        where id = $1 and last_seen_at < now() - interval '1 minute'",
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+///a rotated refresh token keeps the session alive for another refresh token lifetime
+pub async fn extend_session(
+    pool: &PgPool,
+    id: &Uuid,
+    expires_at: OffsetDateTime,
+) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "update user_sessions set last_seen_at = now(), expires_at = $2 where id = $1",
+        id,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+///# Revoke User Session
+///
+/// revoke a session of the user with every access and refresh token it issued
+///
+/// returns false when the user has no such active session
+pub async fn revoke_user_session(
+    pool: &PgPool,
+    user_id: &Uuid,
+    id: &Uuid,
+) -> Result<bool, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let revoked = sqlx::query!(
+        "update user_sessions set revoked_at = now()
+        where id = $1 and user_id = $2 and revoked_at is null",
+        id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        "update token set is_revoked = true where session_id = $1 and user_id = $2",
+        id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(revoked == 1)
+}
+
+///# Evict Oldest Sessions
+///
+/// part of starting a session, run in the transaction that persists it
+///
+/// keep the `keep` most recent active sessions of the user and revoke the rest with their tokens
+pub async fn evict_oldest_sessions(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: &Uuid,
+    keep: i64,
+) -> Result<u64, ApplicationError> {
+    //concurrent sign-ins of the user wait for each other so together they can't exceed the cap, `no key`
+    //since the new session row already holds a key share lock on the user
+    sqlx::query!(
+        "select id from users where id = $1 for no key update",
+        user_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let evicted = sqlx::query_scalar!(
+        "update user_sessions set revoked_at = now()
+        where id in (
+            select id from user_sessions
+            where user_id = $1 and revoked_at is null and expires_at > now()
+            order by created_at desc
+            offset $2
+        )
+        returning id",
+        user_id,
+        keep
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        "update token set is_revoked = true where session_id = any($1) and is_revoked = false",
+        &evicted
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    if !evicted.is_empty() {
+        info!(
+            "{} oldest sessions of user {} evicted",
+            evicted.len(),
+            user_id
+        );
+    }
+    Ok(evicted.len() as u64)
+}
+
+///delete at most `batch_size` sessions that expired or were revoked before `before`
+pub async fn delete_stale_sessions(
+    pool: &PgPool,
+    before: OffsetDateTime,
+    batch_size: i64,
+) -> Result<u64, ApplicationError> {
+    Ok(sqlx::query!(
+        "delete from user_sessions where id in (
+            select id from user_sessions
+            where expires_at < $1 or revoked_at < $1
+            limit $2
+        )",
+        before,
+        batch_size
+    )
+    .execute(pool)
+    .await?
+    .rows_affected())
+}
@@ -1,79 +1,278 @@
 use crate::application::errors::application_error::ApplicationError;
-use crate::users::repositories::user_repository::get_user_by_email;
+use crate::application::security::secure_token::hash_secure_token;
+use crate::users::repositories::session_repository::evict_oldest_sessions;
 use crate::users::services::jwt_service::UserTokenResponse;
 use crate::users::types::token::Token;
+use crate::users::types::user_session::UserSession;
 use sqlx::PgPool;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
+///# Persist Refresh And Access Tokens
+///
+/// both tokens start the given session, the session row is stored with them
+///
+/// with `max_sessions` the oldest sessions of the user beyond it are revoked in the same transaction
+///
+/// only the digests of the tokens are stored
 pub async fn persist_refresh_and_access_tokens(
     pool: &PgPool,
     tokens: &UserTokenResponse,
-    email: &str,
+    access_expires_at: OffsetDateTime,
+    session: &UserSession,
+    max_sessions: Option<i64>,
 ) -> Result<UserTokenResponse, ApplicationError> {
     let mut tx = pool.begin().await?;
-    let saved_user = get_user_by_email(pool, email).await?;
+
+    sqlx::query!(
+        "insert into user_sessions(id, user_id, user_agent, ip_address, created_at, last_seen_at,
+                   expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7)",
+        session.id,
+        session.user_id,
+        session.user_agent.as_deref(),
+        session.ip_address.as_deref(),
+        session.created_at,
+        session.last_seen_at,
+        session.expires_at
+    )
+    .execute(&mut *tx)
+    .await?;
 
     //persist access token
-    let access = sqlx::query_as!(
-        Token,
-        "insert into token(is_expired, is_revoked, id, user_id, token)
-        values ($1, $2, $3, $4, $5) returning *",
+    sqlx::query!(
+        "insert into token(is_expired, is_revoked, id, user_id, token_hash, session_id, expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7)",
         Some(false),
         Some(false),
         Uuid::new_v4(),
-        saved_user.id,
-        Some(&tokens.access),
+        session.user_id,
+        hash_secure_token(&tokens.access),
+        session.id,
+        access_expires_at,
     )
-    .fetch_one(&mut *tx)
+    .execute(&mut *tx)
     .await?;
 
-    //persist refresh token
-    let refresh = sqlx::query_as!(
-        Token,
-        "insert into token(is_expired, is_revoked, id, user_id, token)
-        values ($1, $2, $3, $4, $5) returning *",
+    //persist refresh token, it lives as long as the session
+    sqlx::query!(
+        "insert into token(is_expired, is_revoked, id, user_id, token_hash, session_id, expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7)",
         Some(false),
         Some(false),
         Uuid::new_v4(),
-        saved_user.id,
-        Some(&tokens.refresh),
+        session.user_id,
+        hash_secure_token(&tokens.refresh),
+        session.id,
+        session.expires_at,
     )
-    .fetch_one(&mut *tx)
+    .execute(&mut *tx)
     .await?;
 
+    if let Some(max_sessions) = max_sessions {
+        evict_oldest_sessions(&mut tx, &session.user_id, max_sessions).await?;
+    }
+
     tx.commit().await?;
 
     Ok(UserTokenResponse {
-        access: access.token.unwrap_or_default(),
-        refresh: refresh.token.unwrap_or_default(),
+        access: tokens.access.clone(),
+        refresh: tokens.refresh.clone(),
     })
 }
 
-pub async fn persist_access_tokens(
+///# Persist Access Token
+///
+/// a lone access token in its own session, used for grants without a refresh token
+pub async fn persist_access_token(
     pool: &PgPool,
-    access_token: &str,
-    email: &str,
-) -> Result<String, ApplicationError> {
-    let saved_user = get_user_by_email(pool, email).await?;
-    let access = sqlx::query_as!(
-        Token,
-        "insert into token(is_expired, is_revoked, id, user_id, token)
-        values ($1, $2, $3, $4, $5) returning *",
+    user_id: &Uuid,
+    access: &str,
+    expires_at: OffsetDateTime,
+) -> Result<(), ApplicationError> {
+    sqlx::query!(
+        "insert into token(is_expired, is_revoked, id, user_id, token_hash, session_id, expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7)",
         Some(false),
         Some(false),
         Uuid::new_v4(),
-        saved_user.id,
-        Some(access_token),
+        user_id,
+        hash_secure_token(access),
+        Uuid::new_v4(),
+        expires_at,
     )
-    .fetch_one(pool)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+///# Persist Rotated Refresh Token
+///
+/// mark the presented refresh token as rotated and persist its successor pair in the same family
+///
+/// returns None when the refresh token was already rotated, i.e. it is being reused
+pub async fn persist_rotated_refresh_token(
+    pool: &PgPool,
+    refresh: &Token,
+    tokens: &UserTokenResponse,
+    access_expires_at: OffsetDateTime,
+    refresh_expires_at: OffsetDateTime,
+) -> Result<Option<UserTokenResponse>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let rotated = sqlx::query!(
+        "update token set rotated_at = now() where id = $1 and rotated_at is null",
+        refresh.id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if rotated.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    //persist access token
+    sqlx::query!(
+        "insert into token(is_expired, is_revoked, id, user_id, token_hash, session_id, parent_id,
+                           expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7, $8)",
+        Some(false),
+        Some(false),
+        Uuid::new_v4(),
+        refresh.user_id,
+        hash_secure_token(&tokens.access),
+        refresh.session_id,
+        refresh.id,
+        access_expires_at,
+    )
+    .execute(&mut *tx)
     .await?;
-    Ok(access.token.unwrap_or_default())
+
+    //persist the successor refresh token
+    sqlx::query!(
+        "insert into token(is_expired, is_revoked, id, user_id, token_hash, session_id, parent_id,
+                           expires_at)
+        values ($1, $2, $3, $4, $5, $6, $7, $8)",
+        Some(false),
+        Some(false),
+        Uuid::new_v4(),
+        refresh.user_id,
+        hash_secure_token(&tokens.refresh),
+        refresh.session_id,
+        refresh.id,
+        refresh_expires_at,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(UserTokenResponse {
+        access: tokens.access.clone(),
+        refresh: tokens.refresh.clone(),
+    }))
 }
 
+///# Get Token By Token
+///
+/// look the token up by its digest, the raw token is never stored
 pub async fn get_token_by_token(token: &str, pool: &PgPool) -> Result<Token, ApplicationError> {
-    Ok(
-        sqlx::query_as!(Token, "select * from token where token.token = $1", token)
-            .fetch_one(pool)
-            .await?,
+    Ok(sqlx::query_as!(
+        Token,
+        "select * from token where token_hash = $1",
+        hash_secure_token(token)
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///revoke the session and every token it issued, access and refresh alike
+pub async fn revoke_session_tokens(
+    pool: &PgPool,
+    session_id: &Uuid,
+) -> Result<u64, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "update user_sessions set revoked_at = now() where id = $1 and revoked_at is null",
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let revoked = sqlx::query!(
+        "update token set is_revoked = true where session_id = $1 and is_revoked = false",
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(revoked)
+}
+
+///revoke every session and token of the user
+pub async fn revoke_user_tokens(pool: &PgPool, user_id: &Uuid) -> Result<u64, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "update user_sessions set revoked_at = now() where user_id = $1 and revoked_at is null",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let revoked = sqlx::query!(
+        "update token set is_revoked = true where user_id = $1 and is_revoked = false",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(revoked)
+}
+
+///# Mark Expired Tokens
+///
+/// flag tokens past their expiry, rows without expiry count as expired once created before `legacy_created_before`
+pub async fn mark_expired_tokens(
+    pool: &PgPool,
+    legacy_created_before: OffsetDateTime,
+) -> Result<u64, ApplicationError> {
+    Ok(sqlx::query!(
+        "update token set is_expired = true
+        where is_expired = false
+          and (expires_at < now() or (expires_at is null and created_at < $1))",
+        legacy_created_before
+    )
+    .execute(pool)
+    .await?
+    .rows_affected())
+}
+
+///delete at most `batch_size` tokens that expired before `expired_before`, or that were revoked
+///and last rotated or issued before it
+pub async fn delete_expired_tokens(
+    pool: &PgPool,
+    expired_before: OffsetDateTime,
+    legacy_created_before: OffsetDateTime,
+    batch_size: i64,
+) -> Result<u64, ApplicationError> {
+    Ok(sqlx::query!(
+        "delete from token where id in (
+            select id from token
+            where expires_at < $1
+               or (expires_at is null and created_at < $2)
+               or (is_revoked and coalesce(rotated_at, created_at) < $1)
+            limit $3
+        )",
+        expired_before,
+        legacy_created_before,
+        batch_size
     )
+    .execute(pool)
+    .await?
+    .rows_affected())
 }
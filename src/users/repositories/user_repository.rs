@@ -1,11 +1,432 @@
 use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::repositories::organization_repository::hand_over_memberships;
 use crate::users::types::user::User;
+use crate::users::types::user_source::UserSource;
 use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
 
 pub async fn get_user_by_email(pool: &PgPool, email: &str) -> Result<User, ApplicationError> {
-    Ok(
-        sqlx::query_as!(User, "select * from users where email = $1", email)
-            .fetch_one(pool)
-            .await?,
+    Ok(sqlx::query_as!(
+        User,
+        r#"select id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified
+        from users where email = $1"#,
+        email
     )
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn find_user_by_email(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<User>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"select id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified
+        from users where email = $1"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+///# Find User By Email Ignoring Case
+///
+/// emails are stored as entered, an exact match wins over one differing in case
+pub async fn find_user_by_email_ignoring_case(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<User>, ApplicationError> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"select id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified
+        from users where lower(email) = lower($1) order by email = $1 desc, created_at limit 1"#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+///# Update Password Hash
+///
+/// replace the hash of an unchanged password, a password changed in the meantime is left alone
+pub async fn update_password_hash(
+    pool: &PgPool,
+    id: &Uuid,
+    current_hash: &str,
+    password_hash: &str,
+) -> Result<bool, ApplicationError> {
+    let result = sqlx::query!(
+        "update users set password = $3 where id = $1 and password = $2",
+        id,
+        current_hash,
+        password_hash
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+///# Claim Unverified Account
+///
+/// the email was proven by other means, e.g. a google sign-in
+///
+/// whoever registered the email before may not own it, so the password they set is cleared and their
+/// sessions are revoked along with marking the email verified
+///
+/// returns false when the email was already verified
+pub async fn claim_unverified_account(pool: &PgPool, id: &Uuid) -> Result<bool, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let claimed = sqlx::query!(
+        "update users set email_verified = true, password = null
+        where id = $1 and email_verified = false",
+        id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        == 1;
+
+    if claimed {
+        sqlx::query!(
+            "update user_sessions set revoked_at = now() where user_id = $1 and revoked_at is null",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "update token set is_revoked = true where user_id = $1 and is_revoked = false",
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(claimed)
+}
+
+///set or clear the expiry date, an account expired by its flag stays expired
+pub async fn set_account_expiry(
+    pool: &PgPool,
+    id: &Uuid,
+    expires_at: Option<OffsetDateTime>,
+) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update users set account_expires_at = $2 where id = $1",
+        id,
+        expires_at
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+pub async fn get_user_by_id(pool: &PgPool, id: &Uuid) -> Result<User, ApplicationError> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"select id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified
+        from users where id = $1"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///# Update User Profile
+///
+/// change the name and/or image url, `None` leaves a field as it is
+///
+/// replacing the image url, even with none, detaches an uploaded avatar
+///
+/// returns the updated user with the avatar it had before, read under the same row lock
+pub async fn update_user_profile(
+    pool: &PgPool,
+    id: &Uuid,
+    name: Option<&str>,
+    image_url: Option<Option<&str>>,
+) -> Result<(User, Option<Uuid>), ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let previous = sqlx::query_scalar!("select avatar_id from users where id = $1 for update", id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let user = sqlx::query_as!(
+        User,
+        r#"update users set name = coalesce($2, name),
+        image_url = case when $3 then $4 else image_url end,
+        avatar_id = case when $3 then null else avatar_id end
+        where id = $1
+        returning id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified"#,
+        id,
+        name,
+        image_url.is_some(),
+        image_url.flatten()
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((user, previous))
+}
+
+///# Change User Password
+///
+/// store the new hash and revoke every other session with its tokens in one transaction
+///
+/// the session the change was made from stays logged in
+pub async fn change_user_password(
+    pool: &PgPool,
+    id: &Uuid,
+    password_hash: &str,
+    current_session: Option<Uuid>,
+) -> Result<u64, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "update users set password = $2 where id = $1",
+        id,
+        password_hash
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let revoked = sqlx::query!(
+        "update user_sessions set revoked_at = now()
+        where user_id = $1 and revoked_at is null and id is distinct from $2",
+        id,
+        current_session
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        "update token set is_revoked = true
+        where user_id = $1 and is_revoked = false and session_id is distinct from $2",
+        id,
+        current_session
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(revoked)
+}
+
+///point the user at a new avatar, or at none, and return the avatar it replaced
+pub async fn set_user_avatar(
+    pool: &PgPool,
+    id: &Uuid,
+    avatar_id: Option<Uuid>,
+    image_url: Option<&str>,
+) -> Result<Option<Uuid>, ApplicationError> {
+    Ok(sqlx::query_scalar!(
+        "update users set avatar_id = $2, image_url = $3
+        from (select avatar_id as previous from users where id = $1 for update) old
+        where users.id = $1
+        returning old.previous",
+        id,
+        avatar_id,
+        image_url
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+///# Schedule Account Deletion
+///
+/// mark the account for deletion and log it out everywhere in one transaction
+///
+/// false when a deletion is already scheduled
+pub async fn schedule_account_deletion(
+    pool: &PgPool,
+    id: &Uuid,
+    deletion_scheduled_at: OffsetDateTime,
+) -> Result<bool, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let scheduled = sqlx::query!(
+        "update users set deletion_scheduled_at = $2 where id = $1 and deletion_scheduled_at is null",
+        id,
+        deletion_scheduled_at
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if !scheduled {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        "update user_sessions set revoked_at = now() where user_id = $1 and revoked_at is null",
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "update token set is_revoked = true where user_id = $1 and is_revoked = false",
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+///false when no deletion was scheduled
+pub async fn cancel_account_deletion(pool: &PgPool, id: &Uuid) -> Result<bool, ApplicationError> {
+    Ok(sqlx::query!(
+        "update users set deletion_scheduled_at = null where id = $1 and deletion_scheduled_at is not null",
+        id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+///accounts whose grace period is over, oldest schedule first
+pub async fn get_due_account_deletions(
+    pool: &PgPool,
+    skip: &[Uuid],
+    limit: i64,
+) -> Result<Vec<Uuid>, ApplicationError> {
+    Ok(sqlx::query_scalar!(
+        "select id from users where deletion_scheduled_at <= now() and id <> all($1)
+        order by deletion_scheduled_at limit $2",
+        skip,
+        limit
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+///# Delete Scheduled User
+///
+/// hard delete the account, everything it owns goes with it through the cascading foreign keys
+///
+/// organizations it is the last member of go with it, ones it is the last owner of get a new owner
+///
+/// a deletion cancelled in the meantime is left alone and `None` returned
+pub async fn delete_scheduled_user(
+    pool: &PgPool,
+    id: &Uuid,
+) -> Result<Option<User>, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let due = sqlx::query_scalar!(
+        "select id from users where id = $1 and deletion_scheduled_at <= now() for update",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if due.is_none() {
+        return Ok(None);
+    }
+
+    hand_over_memberships(&mut tx, id).await?;
+    let user = sqlx::query_as!(
+        User,
+        r#"delete from users where id = $1
+        returning id, name, email, is_enabled, is_account_non_expired, is_account_non_locked,
+        password, image_url, created_at, updated_at, source as "source: UserSource",
+        failed_login_attempts, lockout_count, locked_until, account_expires_at, avatar_id,
+        deletion_scheduled_at, email_verified"#,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    //links are keyed by email as they can precede the account, nothing cascades to them
+    sqlx::query!("delete from magic_links where email = $1", user.email)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test]
+    async fn claiming_an_unverified_account_drops_the_squatters_access(pool: PgPool) {
+        let id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password) values ($1, 'Squatter', 'ada@x.io', 'hash')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "insert into user_sessions(id, user_id, expires_at) values ($1, $2, now() + interval '1 day')",
+            session_id,
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "insert into token(id, user_id, session_id, token_hash, is_expired, is_revoked)
+            values ($1, $2, $3, 'digest', false, false)",
+            Uuid::new_v4(),
+            id,
+            session_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(claim_unverified_account(&pool, &id).await.unwrap());
+
+        let user = get_user_by_id(&pool, &id).await.unwrap();
+        assert!(user.email_verified);
+        assert_eq!(user.password, None);
+        let open_sessions = sqlx::query_scalar!(
+            "select count(*) from user_sessions where user_id = $1 and revoked_at is null",
+            id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(open_sessions, Some(0));
+        let live_tokens = sqlx::query_scalar!(
+            "select count(*) from token where user_id = $1 and is_revoked = false",
+            id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(live_tokens, Some(0));
+
+        //a verified account is never claimed
+        assert!(!claim_unverified_account(&pool, &id).await.unwrap());
+    }
 }
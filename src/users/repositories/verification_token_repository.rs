@@ -0,0 +1,76 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::verification_token::VerificationToken;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///# Persist Verification Token
+///
+/// pending tokens of the user are removed so only the latest link works
+pub async fn persist_verification_token(
+    pool: &PgPool,
+    user_id: &Uuid,
+    token_hash: &str,
+    expires_at: OffsetDateTime,
+) -> Result<VerificationToken, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "delete from verification_tokens where user_id = $1 and consumed_at is null",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let saved_token = sqlx::query_as!(
+        VerificationToken,
+        "insert into verification_tokens(id, user_id, token_hash, expires_at)
+        values ($1, $2, $3, $4) returning *",
+        Uuid::new_v4(),
+        user_id,
+        token_hash,
+        expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(saved_token)
+}
+
+///# Consume Verification Token
+///
+/// mark the token as used and the email of its user as verified in one transaction
+///
+/// consumed and expired tokens are rejected
+pub async fn consume_verification_token(
+    pool: &PgPool,
+    token_hash: &str,
+) -> Result<VerificationToken, ApplicationError> {
+    let mut tx = pool.begin().await?;
+
+    let consumed_token = sqlx::query_as!(
+        VerificationToken,
+        "update verification_tokens set consumed_at = now()
+        where token_hash = $1 and consumed_at is null and expires_at > now() returning *",
+        token_hash
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApplicationError::new(
+        "Verification Error",
+        "Invalid or expired verification token",
+    ))?;
+
+    sqlx::query!(
+        "update users set email_verified = true where id = $1",
+        consumed_token.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(consumed_token)
+}
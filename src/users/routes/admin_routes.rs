@@ -0,0 +1,26 @@
+use crate::users::services::admin_service::{
+    grant_user_role, revoke_user_role, update_account_expiry,
+};
+use crate::users::services::audit_service::list_audit_events;
+use crate::users::services::login_attempt_service::unlock_account;
+use crate::users::services::oauth_client_service::{
+    delete_oauth_client, list_oauth_clients, register_oauth_client,
+};
+use axum::Router;
+use axum::routing::{delete, get, post, put};
+
+pub fn admin() -> Router {
+    Router::new()
+        .route("/users/{id}/unlock", post(unlock_account))
+        .route("/users/{id}/expiry", put(update_account_expiry))
+        .route(
+            "/users/{id}/roles/{role}",
+            put(grant_user_role).delete(revoke_user_role),
+        )
+        .route(
+            "/oauth-clients",
+            get(list_oauth_clients).post(register_oauth_client),
+        )
+        .route("/oauth-clients/{id}", delete(delete_oauth_client))
+        .route("/audit-events", get(list_audit_events))
+}
@@ -0,0 +1,12 @@
+use crate::users::services::api_key_service::{
+    create_api_key, current_api_key, delete_api_key, list_api_keys, update_api_key_name,
+};
+use axum::Router;
+use axum::routing::{get, patch};
+
+pub fn api_keys() -> Router {
+    Router::new()
+        .route("/", get(list_api_keys).post(create_api_key))
+        .route("/current", get(current_api_key))
+        .route("/{id}", patch(update_api_key_name).delete(delete_api_key))
+}
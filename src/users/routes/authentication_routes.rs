@@ -1,15 +1,31 @@
-use crate::users::services::authentication_service::{login, refresh_token, signup};
-use crate::users::types::user::User;
+use crate::users::services::authentication_service::{
+    login, logout, logout_all, refresh_token, signup,
+};
+use crate::users::services::google_authentication_service::{google_callback, google_start};
+use crate::users::services::magic_link_service::{request_magic_link, sign_in_with_magic_link};
+use crate::users::services::mfa_service::{confirm_mfa, disable_mfa, enroll_mfa, verify_mfa};
+use crate::users::services::password_reset_service::{forgot_password, reset_password};
+use crate::users::services::verification_service::{resend_verification, verify_email};
 use axum::Router;
-use axum::middleware::from_extractor;
-use axum::routing::post;
+use axum::routing::{get, post};
 
 pub fn authentication() -> Router {
     Router::new()
         .route("/signup", post(signup))
         .route("/sign-in", post(login))
-        .route(
-            "/refresh",
-            post(refresh_token).layer(from_extractor::<User>()),
-        )
+        .route("/refresh", post(refresh_token))
+        .route("/logout", post(logout))
+        .route("/logout-all", post(logout_all))
+        .route("/verify-email", post(verify_email))
+        .route("/resend-verification", post(resend_verification))
+        .route("/forgot-password", post(forgot_password))
+        .route("/reset-password", post(reset_password))
+        .route("/magic-link", post(request_magic_link))
+        .route("/magic-link/sign-in", post(sign_in_with_magic_link))
+        .route("/google/start", get(google_start))
+        .route("/google/callback", get(google_callback))
+        .route("/mfa/enroll", post(enroll_mfa))
+        .route("/mfa/confirm", post(confirm_mfa))
+        .route("/mfa/disable", post(disable_mfa))
+        .route("/mfa/verify", post(verify_mfa))
 }
@@ -0,0 +1,7 @@
+use crate::users::services::avatar_service::get_avatar;
+use axum::Router;
+use axum::routing::get;
+
+pub fn avatars() -> Router {
+    Router::new().route("/{user_id}/{avatar_id}/{size}", get(get_avatar))
+}
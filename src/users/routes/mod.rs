@@ -1 +1,8 @@
+pub mod admin_routes;
+pub mod api_key_routes;
 pub mod authentication_routes;
+pub mod avatar_routes;
+pub mod oauth_routes;
+pub mod profile_routes;
+pub mod session_routes;
+pub mod well_known_routes;
@@ -0,0 +1,7 @@
+use crate::users::services::oauth_client_service::token;
+use axum::Router;
+use axum::routing::post;
+
+pub fn oauth() -> Router {
+    Router::new().route("/token", post(token))
+}
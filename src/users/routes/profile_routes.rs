@@ -0,0 +1,27 @@
+use crate::users::services::account_deletion_service::{cancel_deletion, request_account_deletion};
+use crate::users::services::account_export_service::export_account;
+use crate::users::services::avatar_service::{delete_avatar, upload_avatar};
+use crate::users::services::profile_service::{change_password, get_profile, update_profile};
+use axum::Router;
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post, put};
+
+pub fn profile() -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(get_profile)
+                .patch(update_profile)
+                .delete(request_account_deletion),
+        )
+        .route("/cancel-deletion", post(cancel_deletion))
+        .route("/export", get(export_account))
+        .route("/password", post(change_password))
+        //the upload enforces AVATAR_MAX_SIZE itself while streaming
+        .route(
+            "/avatar",
+            put(upload_avatar)
+                .delete(delete_avatar)
+                .layer(DefaultBodyLimit::disable()),
+        )
+}
@@ -0,0 +1,9 @@
+use crate::users::services::session_service::{list_sessions, revoke_session};
+use axum::Router;
+use axum::routing::{delete, get};
+
+pub fn sessions() -> Router {
+    Router::new()
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/{id}", delete(revoke_session))
+}
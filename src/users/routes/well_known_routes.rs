@@ -0,0 +1,7 @@
+use crate::users::services::jwt_service::jwks;
+use axum::Router;
+use axum::routing::get;
+
+pub fn well_known() -> Router {
+    Router::new().route("/jwks.json", get(jwks))
+}
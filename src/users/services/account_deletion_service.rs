@@ -0,0 +1,341 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::storage::file_storage::FileStorage;
+use crate::users::repositories::user_repository::{
+    cancel_account_deletion, delete_scheduled_user, get_due_account_deletions,
+    schedule_account_deletion,
+};
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::avatar_service::delete_avatar_files;
+use crate::users::services::profile_service::verify_current_password;
+use crate::users::types::account_deletion_response::AccountDeletionResponse;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::delete_account_request::DeleteAccountRequest;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+const DELETION_BATCH_SIZE: i64 = 100;
+
+///# Request Account Deletion
+///
+/// confirm with the password, or a code from /auth/magic-link without one, and schedule the hard delete after ACCOUNT_DELETION_GRACE_PERIOD (ms)
+///
+/// every session is logged out, signing in again during the grace period allows cancelling
+pub async fn request_account_deletion(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    request: Json<DeleteAccountRequest>,
+) -> Result<Json<AccountDeletionResponse>, (StatusCode, Json<ApplicationError>)> {
+    let audit = NewAuditEvent::new(
+        AuditEventType::ACCOUNT_DELETION_REQUESTED,
+        AuditOutcome::FAILURE,
+    )
+    .actor(user.id)
+    .context(&context);
+    if let Err(error) = verify_current_password(
+        &state.pool,
+        &user,
+        &request.0.password,
+        request.0.code.as_deref(),
+    )
+    .await
+    {
+        record_audit_event(
+            &state.pool,
+            audit.metadata(json!({"reason": "invalid password"})),
+        )
+        .await;
+        return Err(error);
+    }
+
+    let grace_period: Result<i64, ApplicationError> = env::var("ACCOUNT_DELETION_GRACE_PERIOD")
+        .unwrap_or(String::from("2592000000"))
+        .parse()
+        .map_err(ApplicationError::from);
+    let deletion_scheduled_at = match grace_period {
+        Ok(grace_period) => OffsetDateTime::now_utc() + Duration::milliseconds(grace_period),
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    match schedule_account_deletion(&state.pool, &user.id, deletion_scheduled_at).await {
+        Ok(true) => {
+            info!(
+                "deletion of user {} scheduled at {}",
+                user.id, deletion_scheduled_at
+            );
+            let metadata = json!({"deletion_scheduled_at": deletion_scheduled_at.unix_timestamp()});
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent {
+                    outcome: AuditOutcome::SUCCESS,
+                    ..audit
+                }
+                .metadata(metadata),
+            )
+            .await;
+            Ok(Json(AccountDeletionResponse {
+                message: String::from("Account deletion scheduled"),
+                deletion_scheduled_at,
+            }))
+        }
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            Json(ApplicationError::new(
+                "Account Deletion Error",
+                "Account deletion is already scheduled",
+            )),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Cancel Account Deletion
+///
+/// keep the account, only possible until the grace period is over
+pub async fn cancel_deletion(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match cancel_account_deletion(&state.pool, &user.id).await {
+        Ok(true) => {
+            info!("deletion of user {} cancelled", user.id);
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(
+                    AuditEventType::ACCOUNT_DELETION_CANCELLED,
+                    AuditOutcome::SUCCESS,
+                )
+                .actor(user.id)
+                .context(&context),
+            )
+            .await;
+            Ok(Json(MessageResponse::new("Account deletion cancelled")))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApplicationError::new(
+                "Not Found",
+                "No account deletion is scheduled",
+            )),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Delete Due Accounts
+///
+/// hard delete every account whose grace period is over, along with its stored files
+///
+/// an account that fails to delete is logged and skipped, the next run tries it again
+pub async fn delete_due_accounts(
+    pool: &PgPool,
+    storage: &Arc<dyn FileStorage>,
+) -> Result<u64, ApplicationError> {
+    let mut deleted = 0;
+    let mut failed: Vec<Uuid> = Vec::new();
+    loop {
+        let due = get_due_account_deletions(pool, &failed, DELETION_BATCH_SIZE).await?;
+        for id in &due {
+            let user = match delete_scheduled_user(pool, id).await {
+                Ok(Some(user)) => user,
+                Ok(None) => continue,
+                Err(error) => {
+                    error!("account {} could not be deleted: {:?}", id, error);
+                    failed.push(*id);
+                    continue;
+                }
+            };
+            if let Some(avatar_id) = user.avatar_id {
+                delete_avatar_files(storage, &user.id, &avatar_id).await;
+            }
+            //only the id is kept, it is what earlier events of the account refer to
+            record_audit_event(
+                pool,
+                NewAuditEvent::new(AuditEventType::ACCOUNT_DELETED, AuditOutcome::SUCCESS)
+                    .actor(user.id),
+            )
+            .await;
+            deleted += 1;
+        }
+        if (due.len() as i64) < DELETION_BATCH_SIZE {
+            break;
+        }
+    }
+    if deleted > 0 {
+        info!("{} accounts deleted after their grace period", deleted);
+    }
+    Ok(deleted)
+}
+
+///# Spawn Account Deletion Task
+///
+/// look for accounts due for deletion every ACCOUNT_DELETION_INTERVAL (ms)
+pub fn spawn_account_deletion_task(
+    pool: PgPool,
+    storage: Arc<dyn FileStorage>,
+) -> Result<(), ApplicationError> {
+    let interval: u64 = env::var("ACCOUNT_DELETION_INTERVAL")
+        .unwrap_or(String::from("3600000"))
+        .parse()?;
+    //tokio panics on a zero period
+    if interval == 0 {
+        return Err(ApplicationError::new(
+            "Configuration Error",
+            "ACCOUNT_DELETION_INTERVAL must be greater than 0",
+        ));
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_millis(interval));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = delete_due_accounts(&pool, &storage).await {
+                error!("{:?}", error);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizations::repositories::organization_repository::{
+        get_membership, persist_organization,
+    };
+
+    async fn insert_user(pool: &PgPool, email: &str, deletion_due: bool) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, deletion_scheduled_at)
+            values ($1, 'Ada', $2, case when $3 then now() - interval '1 day' end)",
+            id,
+            email,
+            deletion_due
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    #[sqlx::test]
+    async fn organizations_keep_an_owner_or_go_with_their_last_member(pool: PgPool) {
+        let owner = insert_user(&pool, "owner@x.io", true).await;
+        let admin = insert_user(&pool, "admin@x.io", false).await;
+        let member = insert_user(&pool, "member@x.io", false).await;
+        let shared = persist_organization(&pool, "Acme", &owner).await.unwrap();
+        let solo = persist_organization(&pool, "Solo", &owner).await.unwrap();
+        for (id, role) in [(member, "MEMBER"), (admin, "ADMIN")] {
+            sqlx::query!(
+                "insert into memberships(organization_id, user_id, role) values ($1, $2, $3)",
+                shared.id,
+                id,
+                role
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        for email in ["owner@x.io", "admin@x.io"] {
+            sqlx::query!(
+                "insert into magic_links(id, email, code_hash, expires_at)
+                values ($1, $2, 'digest', now() + interval '15 minutes')",
+                Uuid::new_v4(),
+                email
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let (state, _) = AppState::for_tests(pool);
+
+        assert_eq!(
+            delete_due_accounts(&state.pool, &state.storage)
+                .await
+                .unwrap(),
+            1
+        );
+        let links = sqlx::query_scalar!("select email from magic_links")
+            .fetch_all(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(links, vec![String::from("admin@x.io")]);
+        let (_, membership) = get_membership(&state.pool, &shared.id, &admin)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(membership.role, "OWNER");
+        let (_, membership) = get_membership(&state.pool, &shared.id, &member)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(membership.role, "MEMBER");
+        let solo_left =
+            sqlx::query_scalar!("select count(*) from organizations where id = $1", solo.id)
+                .fetch_one(&state.pool)
+                .await
+                .unwrap();
+        assert_eq!(solo_left, Some(0));
+    }
+
+    #[sqlx::test]
+    async fn a_failing_account_does_not_block_the_others(pool: PgPool) {
+        for email in ["stuck@x.io", "gone@x.io"] {
+            sqlx::query!(
+                "insert into users(id, name, email, deletion_scheduled_at)
+                values ($1, 'Ada', $2, now() - interval '1 day')",
+                Uuid::new_v4(),
+                email
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        sqlx::raw_sql(
+            "create function refuse_delete() returns trigger language plpgsql as
+            $$ begin raise exception 'refused'; end $$;
+            create trigger refuse_delete before delete on users
+            for each row when (old.email = 'stuck@x.io') execute function refuse_delete();",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let (state, _) = AppState::for_tests(pool);
+
+        assert_eq!(
+            delete_due_accounts(&state.pool, &state.storage)
+                .await
+                .unwrap(),
+            1
+        );
+        let remaining = sqlx::query_scalar!("select email from users")
+            .fetch_all(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![String::from("stuck@x.io")]);
+    }
+}
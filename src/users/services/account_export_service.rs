@@ -0,0 +1,124 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::organizations::repositories::organization_repository::get_organizations_by_user_id;
+use crate::organizations::types::organization_response::OrganizationResponse;
+use crate::users::repositories::api_key_repository::get_api_keys_by_user_id;
+use crate::users::repositories::audit_repository::get_audit_events_by_actor_id;
+use crate::users::repositories::session_repository::get_sessions_by_user_id;
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::mfa_service::is_mfa_enabled;
+use crate::users::services::profile_service::profile_response;
+use crate::users::types::account_export::AccountExport;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::session_response::SessionResponse;
+use crate::users::types::user::User;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+async fn build_account_export(
+    pool: &PgPool,
+    user: &User,
+) -> Result<AccountExport, ApplicationError> {
+    Ok(AccountExport {
+        exported_at: OffsetDateTime::now_utc(),
+        profile: profile_response(pool, user).await?,
+        email_verified: user.email_verified,
+        mfa_enabled: is_mfa_enabled(pool, user).await?,
+        failed_login_attempts: user.failed_login_attempts,
+        locked_until: user.locked_until,
+        account_expires_at: user.account_expires_at,
+        sessions: get_sessions_by_user_id(pool, &user.id)
+            .await?
+            .into_iter()
+            .map(|session| SessionResponse::new(session, false))
+            .collect(),
+        api_keys: get_api_keys_by_user_id(pool, &user.id)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        organizations: get_organizations_by_user_id(pool, &user.id)
+            .await?
+            .into_iter()
+            .map(|(organization, role)| OrganizationResponse::new(organization, role))
+            .collect(),
+        audit_events: get_audit_events_by_actor_id(pool, &user.id).await?,
+    })
+}
+
+///# Export Account
+///
+/// download everything held about the signed in user as one json document
+pub async fn export_account(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+) -> Result<Response, (StatusCode, Json<ApplicationError>)> {
+    match build_account_export(&state.pool, &user).await {
+        Ok(export) => {
+            info!("account export for user {}", user.id);
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(AuditEventType::ACCOUNT_EXPORTED, AuditOutcome::SUCCESS)
+                    .actor(user.id)
+                    .context(&context),
+            )
+            .await;
+            let disposition = format!("attachment; filename=\"account-export-{}.json\"", user.id);
+            Ok((
+                [
+                    (header::CONTENT_DISPOSITION, disposition),
+                    (header::CACHE_CONTROL, String::from("no-store")),
+                ],
+                Json(export),
+            )
+                .into_response())
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizations::repositories::organization_repository::persist_organization;
+    use crate::organizations::types::membership_role::MembershipRole;
+    use crate::users::repositories::role_repository::grant_role;
+    use crate::users::repositories::user_repository::get_user_by_id;
+    use crate::users::types::role_type::RoleType;
+    use uuid::Uuid;
+
+    #[sqlx::test]
+    async fn export_lists_roles_and_memberships(pool: PgPool) {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email) values ($1, 'Ada', 'ada@x.io')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        grant_role(&pool, &id, RoleType::APPLICATION).await.unwrap();
+        let organization = persist_organization(&pool, "Acme", &id).await.unwrap();
+        let user = get_user_by_id(&pool, &id).await.unwrap();
+
+        let export = build_account_export(&pool, &user).await.unwrap();
+
+        assert_eq!(export.profile.user.roles, vec![RoleType::APPLICATION]);
+        assert_eq!(export.organizations.len(), 1);
+        assert_eq!(export.organizations[0].id, organization.id);
+        assert_eq!(export.organizations[0].role, MembershipRole::OWNER);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::services::login_attempt_service::check_account_lock;
+use crate::users::types::user::User;
+use time::OffsetDateTime;
+
+///# Check Account Status
+///
+/// the one policy deciding whether an account may be used, applied to password logins and bearer tokens alike
+///
+/// the account has to be enabled, verified, not expired (by flag or expiry date) and not locked
+pub fn check_account_status(user: &User) -> Result<(), ApplicationError> {
+    if !user.is_enabled.unwrap_or_default() {
+        return Err(ApplicationError::new(
+            "Account Disabled",
+            "This account has been disabled.",
+        ));
+    }
+
+    if !user.email_verified {
+        return Err(ApplicationError::new(
+            "Account Not Verified",
+            "Please verify your email address before signing in.",
+        ));
+    }
+
+    let expired = !user.is_account_non_expired.unwrap_or(true)
+        || user
+            .account_expires_at
+            .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc());
+    if expired {
+        return Err(ApplicationError::new(
+            "Account Expired",
+            "This account has expired.",
+        ));
+    }
+
+    check_account_lock(user)
+}
+
+///errors of the account status policy, the only authentication errors explained to the caller
+pub fn is_account_status_error(error: &ApplicationError) -> bool {
+    matches!(
+        error.error.as_str(),
+        "Account Disabled" | "Account Not Verified" | "Account Expired" | "Account Locked"
+    )
+}
@@ -0,0 +1,193 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::repositories::role_repository::{grant_role, revoke_role};
+use crate::users::repositories::user_repository::{get_user_by_id, set_account_expiry};
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::types::account_expiry_request::AccountExpiryRequest;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::require_role::{Admin, RequireRole};
+use crate::users::types::role_type::RoleType;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_response::UserResponse;
+use axum::extract::Path;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+use uuid::Uuid;
+
+///# Update Account Expiry
+///
+/// admin only, set the date an account expires on e.g. the end of a trial or contract
+///
+/// the account stops working at that date without further action
+pub async fn update_account_expiry(
+    RequireRole { user, .. }: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    Path(id): Path<Uuid>,
+    request: Json<AccountExpiryRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    let expires_at = request.0.expires_at;
+    match set_account_expiry(&state.pool, &id, expires_at).await {
+        Ok(true) => {
+            info!("expiry of user {} updated by admin {}", id, user.id);
+            record_admin_event(
+                &state,
+                AuditEventType::ACCOUNT_EXPIRY_UPDATED,
+                &user,
+                &context,
+                json!({
+                    "user_id": id,
+                    "expires_at": expires_at.and_then(|at| at.format(&Rfc3339).ok()),
+                }),
+            )
+            .await;
+            Ok(Json(MessageResponse::new("Account expiry updated")))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApplicationError::new("Not Found", "User not found")),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///record an admin action, the admin is the actor and the target account goes in the metadata
+pub async fn record_admin_event(
+    state: &AppState,
+    event_type: AuditEventType,
+    admin: &User,
+    context: &SessionContext,
+    metadata: Value,
+) {
+    record_audit_event(
+        &state.pool,
+        NewAuditEvent::new(event_type, AuditOutcome::SUCCESS)
+            .actor(admin.id)
+            .context(context)
+            .metadata(metadata),
+    )
+    .await;
+}
+
+fn parse_role(role: &str) -> Result<RoleType, (StatusCode, Json<ApplicationError>)> {
+    role.parse()
+        .map_err(|error| (StatusCode::BAD_REQUEST, Json(error)))
+}
+
+async fn find_user(
+    state: &AppState,
+    id: &Uuid,
+) -> Result<User, (StatusCode, Json<ApplicationError>)> {
+    get_user_by_id(&state.pool, id).await.map_err(|_| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(ApplicationError::new("Not Found", "User not found")),
+        )
+    })
+}
+
+async fn user_response(
+    state: &AppState,
+    user: &User,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ApplicationError>)> {
+    match user.to_response(&state.pool).await {
+        Ok(response) => Ok(Json(response)),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Grant Role
+///
+/// admin only, add a role to a user, granting a role the user already holds is a no-op
+pub async fn grant_user_role(
+    RequireRole { user, .. }: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    Path((id, role)): Path<(Uuid, String)>,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ApplicationError>)> {
+    let role = parse_role(&role)?;
+    let target = find_user(&state, &id).await?;
+
+    match grant_role(&state.pool, &id, role.clone()).await {
+        Ok(granted) => {
+            if granted {
+                info!("role {} granted to user {} by admin {}", role, id, user.id);
+                record_admin_event(
+                    &state,
+                    AuditEventType::ROLE_GRANTED,
+                    &user,
+                    &context,
+                    json!({"user_id": id, "role": role.to_string()}),
+                )
+                .await;
+            }
+            user_response(&state, &target).await
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Revoke Role
+///
+/// admin only, remove a role from a user
+///
+/// admins cannot drop their own ADMIN role so the last admin is not locked out by accident
+pub async fn revoke_user_role(
+    RequireRole { user, .. }: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    Path((id, role)): Path<(Uuid, String)>,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ApplicationError>)> {
+    let role = parse_role(&role)?;
+    if id == user.id && role == RoleType::ADMIN {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "Authorization Error",
+                "Admins cannot revoke their own admin role",
+            )),
+        ));
+    }
+    let target = find_user(&state, &id).await?;
+
+    match revoke_role(&state.pool, &id, role.clone()).await {
+        Ok(revoked) => {
+            if revoked {
+                info!(
+                    "role {} revoked from user {} by admin {}",
+                    role, id, user.id
+                );
+                record_admin_event(
+                    &state,
+                    AuditEventType::ROLE_REVOKED,
+                    &user,
+                    &context,
+                    json!({"user_id": id, "role": role.to_string()}),
+                )
+                .await;
+            }
+            user_response(&state, &target).await
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
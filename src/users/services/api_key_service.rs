@@ -0,0 +1,401 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::secure_token::{generate_secure_token, hash_secure_token};
+use crate::users::repositories::api_key_repository::{
+    get_api_keys_by_user_id, persist_api_key, rename_api_key, revoke_api_key, use_api_key,
+};
+use crate::users::repositories::role_repository::user_has_role;
+use crate::users::repositories::user_repository::get_user_by_id;
+use crate::users::services::account_status_service::check_account_status;
+use crate::users::types::api_key::ApiKey;
+use crate::users::types::api_key_principal::ApiKeyPrincipal;
+use crate::users::types::api_key_principal_response::ApiKeyPrincipalResponse;
+use crate::users::types::api_key_request::ApiKeyRequest;
+use crate::users::types::api_key_response::ApiKeyResponse;
+use crate::users::types::created_api_key_response::CreatedApiKeyResponse;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::rename_api_key_request::RenameApiKeyRequest;
+use crate::users::types::require_role::{Application, RequireRole};
+use crate::users::types::role_type::RoleType;
+use axum::extract::Path;
+use axum::http::HeaderMap;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const API_KEY_PREFIX: &str = "vik_";
+//the part of the key kept in clear to tell keys apart
+const VISIBLE_PREFIX_LENGTH: usize = 12;
+const MAX_NAME_LENGTH: usize = 100;
+
+///# Extract Api Key
+///
+/// the key from the `X-Api-Key` header or from `Authorization: ApiKey ...`
+pub fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
+    let key = match headers.get("X-Api-Key") {
+        Some(value) => value.to_str().ok()?,
+        None => headers
+            .get("Authorization")?
+            .to_str()
+            .ok()?
+            .strip_prefix("ApiKey ")?,
+    };
+    Some(key.trim()).filter(|key| !key.is_empty())
+}
+
+///# Authenticate Api Key
+///
+/// resolve a key to its owner, the owner must still hold the APPLICATION role and pass the account status policy
+pub async fn authenticate_api_key(
+    key: &str,
+    pool: &PgPool,
+) -> Result<ApiKeyPrincipal, ApplicationError> {
+    let invalid = || ApplicationError::new("Authentication Error", "invalid api key");
+    let api_key = use_api_key(pool, &hash_secure_token(key))
+        .await?
+        .ok_or_else(invalid)?;
+    let user = get_user_by_id(pool, &api_key.user_id).await?;
+    check_account_status(&user)?;
+    if !user_has_role(pool, &user.id, RoleType::APPLICATION).await? {
+        return Err(invalid());
+    }
+    Ok(ApiKeyPrincipal { user, api_key })
+}
+
+pub fn validate_name(name: &str) -> Result<String, (StatusCode, Json<ApplicationError>)> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "Validation Error",
+                format!("Name must be 1 to {} characters", MAX_NAME_LENGTH),
+            )),
+        ));
+    }
+    Ok(name.to_owned())
+}
+
+///scopes are short tokens such as `videos:write`, duplicates are dropped
+pub fn validate_scopes(
+    scopes: Vec<String>,
+) -> Result<Vec<String>, (StatusCode, Json<ApplicationError>)> {
+    let mut validated: Vec<String> = Vec::new();
+    for scope in scopes {
+        let scope = scope.trim().to_owned();
+        let valid = !scope.is_empty()
+            && scope
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '.' | '_' | '-'));
+        if !valid {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApplicationError::new(
+                    "Validation Error",
+                    format!("Invalid scope {}", scope),
+                )),
+            ));
+        }
+        if !validated.contains(&scope) {
+            validated.push(scope);
+        }
+    }
+    Ok(validated)
+}
+
+fn not_found() -> (StatusCode, Json<ApplicationError>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApplicationError::new("Not Found", "Api key not found")),
+    )
+}
+
+fn internal_error(error: ApplicationError) -> (StatusCode, Json<ApplicationError>) {
+    error!("{:?}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error))
+}
+
+///# Create Api Key
+///
+/// users holding the APPLICATION role can create keys for their scripts
+///
+/// only the digest is stored, the key is returned once
+pub async fn create_api_key(
+    RequireRole { user, .. }: RequireRole<Application>,
+    state: Extension<Arc<AppState>>,
+    request: Json<ApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKeyResponse>), (StatusCode, Json<ApplicationError>)> {
+    let request = request.0;
+    let name = validate_name(&request.name)?;
+    let scopes = validate_scopes(request.scopes)?;
+    if let Some(expires_at) = request.expires_at
+        && expires_at <= OffsetDateTime::now_utc()
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "Validation Error",
+                "Expiry must be in the future",
+            )),
+        ));
+    }
+
+    let key = format!("{}{}", API_KEY_PREFIX, generate_secure_token());
+    let api_key = ApiKey {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        name,
+        prefix: key[..VISIBLE_PREFIX_LENGTH].to_owned(),
+        key_hash: hash_secure_token(&key),
+        scopes,
+        expires_at: request.expires_at,
+        last_used_at: None,
+        revoked_at: None,
+        created_at: None,
+    };
+
+    match persist_api_key(&state.pool, &api_key).await {
+        Ok(api_key) => {
+            info!("api key {} created by user {}", api_key.id, user.id);
+            Ok((
+                StatusCode::CREATED,
+                Json(CreatedApiKeyResponse {
+                    key,
+                    api_key: api_key.into(),
+                }),
+            ))
+        }
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+pub async fn list_api_keys(
+    RequireRole { user, .. }: RequireRole<Application>,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<Vec<ApiKeyResponse>>, (StatusCode, Json<ApplicationError>)> {
+    match get_api_keys_by_user_id(&state.pool, &user.id).await {
+        Ok(api_keys) => Ok(Json(api_keys.into_iter().map(Into::into).collect())),
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+pub async fn update_api_key_name(
+    RequireRole { user, .. }: RequireRole<Application>,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    request: Json<RenameApiKeyRequest>,
+) -> Result<Json<ApiKeyResponse>, (StatusCode, Json<ApplicationError>)> {
+    let name = validate_name(&request.0.name)?;
+    match rename_api_key(&state.pool, &user.id, &id, &name).await {
+        Ok(Some(api_key)) => Ok(Json(api_key.into())),
+        Ok(None) => Err(not_found()),
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+///# Delete Api Key
+///
+/// revoke a key, it stops working immediately
+pub async fn delete_api_key(
+    RequireRole { user, .. }: RequireRole<Application>,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match revoke_api_key(&state.pool, &user.id, &id).await {
+        Ok(true) => {
+            info!("api key {} revoked by user {}", id, user.id);
+            Ok(Json(MessageResponse::new("Api key revoked")))
+        }
+        Ok(false) => Err(not_found()),
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+///# Current Api Key
+///
+/// lets scripts check which principal and scopes their key resolves to
+pub async fn current_api_key(
+    principal: ApiKeyPrincipal,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<ApiKeyPrincipalResponse>, (StatusCode, Json<ApplicationError>)> {
+    match principal.user.to_response(&state.pool).await {
+        Ok(user) => Ok(Json(ApiKeyPrincipalResponse {
+            user,
+            api_key: principal.api_key.into(),
+        })),
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::repositories::role_repository::grant_role;
+    use crate::users::services::jwt_service::generate_persisted_user_token;
+    use crate::users::types::require_scope::{MembersRead, RequireScope, RequiredScope};
+    use crate::users::types::session_context::SessionContext;
+    use axum::extract::FromRequestParts;
+    use axum::http::request::Parts;
+    use axum::http::{Request, header};
+
+    //a verified user holding the APPLICATION role, returns its id and an access token
+    async fn application_user(pool: &PgPool) -> (Uuid, String) {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', 'hash', true)",
+            id
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        grant_role(pool, &id, RoleType::APPLICATION).await.unwrap();
+        let tokens = generate_persisted_user_token("ada@x.io", &SessionContext::default(), pool)
+            .await
+            .unwrap();
+        (id, tokens.access)
+    }
+
+    fn request_parts(state: &Arc<AppState>, name: &str, value: &str) -> Parts {
+        let (mut parts, _) = Request::builder()
+            .header(name, value)
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.extensions.insert(state.clone());
+        parts
+    }
+
+    async fn create_key(
+        state: &Arc<AppState>,
+        access: &str,
+        scopes: &[&str],
+    ) -> CreatedApiKeyResponse {
+        let mut parts = request_parts(state, "Authorization", &format!("Bearer {}", access));
+        let role = RequireRole::<Application>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        let request = ApiKeyRequest {
+            name: String::from("ci"),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            expires_at: None,
+        };
+        let (status, Json(created)) = create_api_key(role, Extension(state.clone()), Json(request))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        created
+    }
+
+    async fn authenticate(
+        state: &Arc<AppState>,
+        name: &str,
+        value: &str,
+    ) -> Result<ApiKeyPrincipal, (StatusCode, Json<ApplicationError>)> {
+        ApiKeyPrincipal::from_request_parts(&mut request_parts(state, name, value), &()).await
+    }
+
+    #[sqlx::test]
+    async fn only_the_digest_and_a_visible_prefix_are_stored(pool: PgPool) {
+        let (_, access) = application_user(&pool).await;
+        let (state, _) = AppState::for_tests(pool);
+
+        let created = create_key(&state, &access, &[]).await;
+
+        let stored = sqlx::query!(
+            "select prefix, key_hash from api_keys where id = $1",
+            created.api_key.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+        assert!(created.key.starts_with(API_KEY_PREFIX));
+        assert_eq!(stored.prefix, created.key[..VISIBLE_PREFIX_LENGTH]);
+        assert_eq!(created.api_key.prefix, stored.prefix);
+        assert_eq!(stored.key_hash, hash_secure_token(&created.key));
+        assert!(!stored.key_hash.contains(&created.key));
+    }
+
+    #[sqlx::test]
+    async fn keys_authenticate_through_either_header_and_record_their_use(pool: PgPool) {
+        let (user_id, access) = application_user(&pool).await;
+        let (state, _) = AppState::for_tests(pool);
+        let created = create_key(&state, &access, &[]).await;
+        assert!(created.api_key.last_used_at.is_none());
+
+        for (name, value) in [
+            ("X-Api-Key", created.key.clone()),
+            (
+                header::AUTHORIZATION.as_str(),
+                format!("ApiKey {}", created.key),
+            ),
+        ] {
+            let principal = authenticate(&state, name, &value).await.ok().unwrap();
+            assert_eq!(principal.user.id, user_id);
+            assert_eq!(principal.api_key.id, created.api_key.id);
+            assert!(principal.api_key.last_used_at.is_some());
+        }
+        let last_used_at = sqlx::query_scalar!(
+            "select last_used_at from api_keys where id = $1",
+            created.api_key.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+        assert!(last_used_at.is_some());
+    }
+
+    #[sqlx::test]
+    async fn expired_and_revoked_keys_are_rejected(pool: PgPool) {
+        let (_, access) = application_user(&pool).await;
+        let (state, _) = AppState::for_tests(pool);
+        let expired = create_key(&state, &access, &[]).await;
+        let revoked = create_key(&state, &access, &[]).await;
+        sqlx::query!(
+            "update api_keys set expires_at = now() - interval '1 minute' where id = $1",
+            expired.api_key.id
+        )
+        .execute(&state.pool)
+        .await
+        .unwrap();
+        let mut parts = request_parts(&state, "Authorization", &format!("Bearer {}", access));
+        let role = RequireRole::<Application>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert!(
+            delete_api_key(role, Extension(state.clone()), Path(revoked.api_key.id))
+                .await
+                .is_ok()
+        );
+
+        for key in [&expired.key, &revoked.key] {
+            let (status, Json(error)) = authenticate(&state, "X-Api-Key", key).await.err().unwrap();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(error.description, "invalid api key");
+        }
+    }
+
+    #[sqlx::test]
+    async fn a_key_without_the_required_scope_is_forbidden(pool: PgPool) {
+        let (_, access) = application_user(&pool).await;
+        let (state, _) = AppState::for_tests(pool);
+        let unscoped = create_key(&state, &access, &["videos:read"]).await;
+        let scoped = create_key(&state, &access, &["videos:read", MembersRead::SCOPE]).await;
+        let require_scope = |key: String| {
+            let mut parts = request_parts(&state, "X-Api-Key", &key);
+            async move { RequireScope::<MembersRead>::from_request_parts(&mut parts, &()).await }
+        };
+
+        let (status, _) = require_scope(unscoped.key).await.err().unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(require_scope(scoped.key).await.is_ok());
+    }
+}
@@ -0,0 +1,78 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::repositories::audit_repository::{persist_audit_event, search_audit_events};
+use crate::users::types::audit_event_page::AuditEventPage;
+use crate::users::types::audit_event_query::AuditEventQuery;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::require_role::{Admin, RequireRole};
+use axum::extract::Query;
+use axum::{Extension, Json};
+use log::error;
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+///# Record Audit Event
+///
+/// append the event to the audit trail, a failed write is logged and never fails the request it describes
+pub async fn record_audit_event(pool: &PgPool, event: NewAuditEvent) {
+    if let Err(error) = persist_audit_event(pool, &event).await {
+        error!("audit event {} not recorded: {:?}", event.event_type, error);
+    }
+}
+
+///# List Audit Events
+///
+/// admin only, page through the audit trail filtered by actor, event type, outcome, address and time
+///
+/// pages start at 1, `per_page` defaults to 50 and is capped at 200
+pub async fn list_audit_events(
+    _: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    Query(query): Query<AuditEventQuery>,
+) -> Result<Json<AuditEventPage>, (StatusCode, Json<ApplicationError>)> {
+    let bad_request = |error: ApplicationError| (StatusCode::BAD_REQUEST, Json(error));
+    //unknown names are refused rather than silently matching nothing
+    if let Some(event_type) = &query.event_type {
+        event_type.parse::<AuditEventType>().map_err(bad_request)?;
+    }
+    if let Some(outcome) = &query.outcome {
+        outcome.parse::<AuditOutcome>().map_err(bad_request)?;
+    }
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page < 1 || !(1..=MAX_PAGE_SIZE).contains(&per_page) {
+        return Err(bad_request(ApplicationError::new(
+            "Validation Error",
+            format!(
+                "page must be at least 1 and per_page between 1 and {}",
+                MAX_PAGE_SIZE
+            ),
+        )));
+    }
+
+    match search_audit_events(
+        &state.pool,
+        &query,
+        per_page,
+        (page - 1).saturating_mul(per_page),
+    )
+    .await
+    {
+        Ok((events, total)) => Ok(Json(AuditEventPage {
+            events,
+            page,
+            per_page,
+            total,
+        })),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
@@ -1,34 +1,80 @@
 use crate::application::configuration::application_state::AppState;
 use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::client_ip::ClientIp;
+use crate::application::security::password_hasher::{
+    PasswordVerification, hash_password, verify_password,
+};
 use crate::users::repositories::authentication_repository::save_new_user_and_allocate_a_role;
-use crate::users::repositories::user_repository::get_user_by_email;
+use crate::users::repositories::token_repository::{
+    get_token_by_token, revoke_session_tokens, revoke_user_tokens,
+};
+use crate::users::repositories::user_repository::{
+    find_user_by_email, get_user_by_email, update_password_hash,
+};
+use crate::users::services::account_status_service::check_account_status;
+use crate::users::services::audit_service::record_audit_event;
 use crate::users::services::jwt_service::{
-    TokenType, extract_subject, generate_persisted_access_token, generate_persisted_user_token,
-    verify_token,
+    TokenType, extract_bearer_token, extract_scope, extract_subject, generate_persisted_user_token,
+    rotate_refresh_token, verify_token,
+};
+use crate::users::services::login_attempt_service::{
+    check_account_lock, check_ip_block, ip_blocked_error, register_failed_ip_login,
+    register_failed_login, register_successful_login,
 };
+use crate::users::services::mfa_service::{is_mfa_enabled, start_mfa_challenge};
+use crate::users::services::password_policy_service::validate_password;
+use crate::users::services::verification_service::send_verification_email;
+use crate::users::types::access_token_response::RefreshTokenResponse;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::authentication_outcome::AuthenticationOutcome;
 use crate::users::types::authentication_result::AuthenticationResult;
 use crate::users::types::login_request::LoginRequest;
 use crate::users::types::login_response::LoginResponse;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
 use crate::users::types::user::User;
 use crate::users::types::user_request::UserRequest;
 use crate::users::types::user_response::UserResponse;
 use crate::users::types::user_source::UserSource;
+use axum::http::{HeaderMap, header};
+use axum::response::{IntoResponse, Response};
 use axum::{Extension, Json};
-use bcrypt::{DEFAULT_COST, hash, verify};
 use log::{error, info};
 use reqwest::StatusCode;
+use serde_json::json;
 use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
-use crate::users::types::access_token_response::RefreshTokenResponse;
 
 ///# Signup New User
 ///
 /// create a new user
 ///
 /// assign a USER role to a new user
+///
+/// mail a verification link, the account is enabled but `email_verified` stays false and sign-ins
+/// are refused until the link is opened
 pub async fn signup(
     state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    user_request: Json<UserRequest>,
+) -> Result<Json<UserResponse>, (StatusCode, Json<ApplicationError>)> {
+    let result = create_user(&state, user_request).await;
+    let event = match &result {
+        Ok(response) => {
+            NewAuditEvent::new(AuditEventType::SIGNUP, AuditOutcome::SUCCESS).actor(response.id)
+        }
+        Err((_, error)) => NewAuditEvent::new(AuditEventType::SIGNUP, AuditOutcome::FAILURE)
+            .metadata(json!({"reason": error.error})),
+    };
+    record_audit_event(&state.pool, event.context(&context)).await;
+    result
+}
+
+async fn create_user(
+    state: &Arc<AppState>,
     user_request: Json<UserRequest>,
 ) -> Result<Json<UserResponse>, (StatusCode, Json<ApplicationError>)> {
     if !user_request.0.password.eq(&user_request.0.confirm_password) {
@@ -41,13 +87,18 @@ pub async fn signup(
         ));
     }
 
-    match hash(&user_request.0.password, DEFAULT_COST) {
+    validate_password(
+        &user_request.0.password,
+        &[&user_request.0.name, &user_request.0.email],
+    )?;
+
+    match hash_password(&user_request.0.password).await {
         Ok(hash) => {
             let user = User {
                 id: Uuid::new_v4(),
                 name: user_request.0.name,
                 email: user_request.0.email,
-                is_enabled: Some(false),
+                is_enabled: Some(true),
                 is_account_non_expired: Some(true),
                 is_account_non_locked: Some(true),
                 password: Some(hash),
@@ -55,10 +106,29 @@ pub async fn signup(
                 created_at: None,
                 updated_at: None,
                 source: UserSource::SYSTEM,
+                failed_login_attempts: 0,
+                lockout_count: 0,
+                locked_until: None,
+                account_expires_at: None,
+                avatar_id: None,
+                deletion_scheduled_at: None,
+                email_verified: false,
             };
 
             match save_new_user_and_allocate_a_role(&state.pool, &user).await {
-                Ok(response) => Ok(Json(response)),
+                Ok(response) => {
+                    //mailed in the background like a resend so a slow mail server doesn't stall
+                    //the signup, the user can request a new link if this one fails
+                    let (state, user) = (state.clone(), response.clone());
+                    tokio::spawn(async move {
+                        if let Err(error) =
+                            send_verification_email(&state, &user.id, &user.name, &user.email).await
+                        {
+                            error!("{:?}", error);
+                        }
+                    });
+                    Ok(Json(response))
+                }
                 Err(error) => {
                     log::error!("{:?}", error);
                     Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
@@ -66,14 +136,38 @@ pub async fn signup(
             }
         }
 
-        Err(error) => Err((StatusCode::BAD_REQUEST, Json(ApplicationError::from(error)))),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+//re-hash a password stored with outdated parameters, the login goes on when this fails
+async fn upgrade_password_hash(pool: &PgPool, user: &User, password: &str) {
+    let result = match hash_password(password).await {
+        Ok(password_hash) => {
+            update_password_hash(
+                pool,
+                &user.id,
+                user.password.as_deref().unwrap_or_default(),
+                &password_hash,
+            )
+            .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(true) => info!("password hash of user {} upgraded", user.id),
+        Ok(false) => {}
+        Err(error) => error!("{:?}", error),
     }
 }
 
 ///# Login User
 ///Fetch user from db
 ///
-///verify password
+///verify password, failed attempts are tracked per account and per address
 ///
 /// generate access and refresh token
 ///
@@ -81,24 +175,94 @@ pub async fn signup(
 ///
 /// fetch user info
 ///
-/// return response
+/// return response, or an mfa challenge when two-factor authentication is enabled
 pub async fn login(
     state: Extension<Arc<AppState>>,
+    client_ip: ClientIp,
+    context: SessionContext,
     login_request: Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, (StatusCode, Json<ApplicationError>)> {
+) -> Result<Response, (StatusCode, Json<ApplicationError>)> {
+    //addresses with too many failures are refused before touching the account
+    match check_ip_block(&state.pool, &client_ip).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => {
+            let error = ip_blocked_error(retry_after);
+            record_failed_login(&state.pool, &login_request.0.email, &error, &context).await;
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(error),
+            )
+                .into_response());
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    }
+
     //get the user
-    match authenticate_user(None, Some(login_request.0), &state.pool).await {
-        Ok(result) => Ok(Json(result.session)),
-        Err(error) => Err((StatusCode::UNAUTHORIZED, Json(error))),
+    let email = login_request.0.email.clone();
+    match authenticate_user(None, Some(login_request.0), &context, &state.pool).await {
+        Ok(outcome) => {
+            match outcome {
+                AuthenticationOutcome::Authenticated(result) => {
+                    record_audit_event(
+                        &state.pool,
+                        NewAuditEvent::new(AuditEventType::LOGIN, AuditOutcome::SUCCESS)
+                            .actor(result.user.id)
+                            .context(&context),
+                    )
+                    .await;
+                    Ok(Json(result.session).into_response())
+                }
+                AuthenticationOutcome::MfaRequired { user_id, challenge } => {
+                    //LOGIN is only recorded once the second factor is verified
+                    record_audit_event(
+                        &state.pool,
+                        NewAuditEvent::new(AuditEventType::MFA_CHALLENGE, AuditOutcome::SUCCESS)
+                            .actor(user_id)
+                            .context(&context)
+                            .metadata(json!({"method": "password"})),
+                    )
+                    .await;
+                    Ok(Json(challenge).into_response())
+                }
+            }
+        }
+        Err(error) => {
+            if let Err(error) = register_failed_ip_login(&state.pool, &client_ip).await {
+                error!("{:?}", error);
+            }
+            record_failed_login(&state.pool, &email, &error, &context).await;
+            Err((StatusCode::UNAUTHORIZED, Json(error)))
+        }
     }
 }
 
+///the attempted email is only used to find the account, it is never recorded
+async fn record_failed_login(
+    pool: &PgPool,
+    email: &str,
+    error: &ApplicationError,
+    context: &SessionContext,
+) {
+    let mut event = NewAuditEvent::new(AuditEventType::LOGIN, AuditOutcome::FAILURE)
+        .context(context)
+        .metadata(json!({"reason": error.error}));
+    if let Ok(Some(user)) = find_user_by_email(pool, email).await {
+        event = event.actor(user.id);
+    }
+    record_audit_event(pool, event).await;
+}
+
 pub async fn generate_user_session(
     email: &str,
+    context: &SessionContext,
     pool: &PgPool,
 ) -> Result<AuthenticationResult, ApplicationError> {
     let user = get_user_by_email(pool, email).await?;
-    let tokens = generate_persisted_user_token(email, pool).await?;
+    let tokens = generate_persisted_user_token(email, context, pool).await?;
     let session = LoginResponse {
         access_token: tokens.access,
         refresh_token: tokens.refresh,
@@ -107,6 +271,49 @@ pub async fn generate_user_session(
     Ok(AuthenticationResult { session, user })
 }
 
+///# Authenticate Token
+///
+/// verify an access token and return its user, the account status policy applies as for password logins
+///
+/// no new tokens are issued
+///
+/// service client tokens are refused, they only work on routes asking for a scope
+pub async fn authenticate_token(token: &str, pool: &PgPool) -> Result<User, ApplicationError> {
+    let token = verify_token(token, TokenType::ACCESS, pool).await?;
+    if extract_scope(&token)?.is_some() {
+        return Err(scope_mismatch());
+    }
+    let user = get_user_by_email(pool, &extract_subject(&token)?).await?;
+    if user.source == UserSource::CLIENT {
+        return Err(scope_mismatch());
+    }
+    check_account_status(&user)?;
+    Ok(user)
+}
+
+///# Authenticate Client Token
+///
+/// verify an access token issued to a service client, returns its service user and granted scopes
+pub async fn authenticate_client_token(
+    token: &str,
+    pool: &PgPool,
+) -> Result<(User, Vec<String>), ApplicationError> {
+    let token = verify_token(token, TokenType::ACCESS, pool).await?;
+    let Some(scope) = extract_scope(&token)? else {
+        return Err(scope_mismatch());
+    };
+    let user = get_user_by_email(pool, &extract_subject(&token)?).await?;
+    if user.source != UserSource::CLIENT {
+        return Err(scope_mismatch());
+    }
+    check_account_status(&user)?;
+    Ok((user, scope.split_whitespace().map(str::to_owned).collect()))
+}
+
+fn scope_mismatch() -> ApplicationError {
+    ApplicationError::new("JWT Token error", "JWT Token scope mismatch")
+}
+
 ///# Authenticate User
 ///
 /// If a Token is provided, it will be verified and the session will be returned
@@ -116,12 +323,15 @@ pub async fn generate_user_session(
 pub async fn authenticate_user(
     token: Option<&str>,
     details: Option<LoginRequest>,
+    context: &SessionContext,
     pool: &PgPool,
-) -> Result<AuthenticationResult, ApplicationError> {
+) -> Result<AuthenticationOutcome, ApplicationError> {
     //if the token is present, then just return session using token
-    if token.is_some() {
-        let token = verify_token(token.unwrap_or_default(), TokenType::ACCESS, pool).await?;
-        Ok(generate_user_session(&extract_subject(&token)?, pool).await?)
+    if let Some(token) = token {
+        let user = authenticate_token(token, pool).await?;
+        Ok(AuthenticationOutcome::Authenticated(Box::new(
+            generate_user_session(&user.email, context, pool).await?,
+        )))
     }
     //verify password and username and return session
     else {
@@ -131,20 +341,34 @@ pub async fn authenticate_user(
         });
         match get_user_by_email(pool, &details.email).await {
             Ok(user) => {
-                match verify(
-                    details.password,
-                    user.password.clone().unwrap_or_default().as_str(),
-                ) {
+                //locked accounts are refused before the password is checked
+                check_account_lock(&user)?;
+                match verify_password(&details.password, user.password.as_deref()).await {
                     Err(e) => {
                         log::error!("{:?}", e);
-                        Err(ApplicationError::from(e))
+                        Err(e)
                     }
-                    Ok(result) => {
-                        match result {
-                            true => {
+                    Ok(verification) => {
+                        match verification {
+                            PasswordVerification::Valid | PasswordVerification::Outdated => {
+                                check_account_status(&user)?;
+                                if verification == PasswordVerification::Outdated {
+                                    upgrade_password_hash(pool, &user, &details.password).await;
+                                }
+                                //the session is held back until the second factor is verified, the failure
+                                //counters too so wrong codes between correct passwords still add up
+                                if is_mfa_enabled(pool, &user).await? {
+                                    return Ok(AuthenticationOutcome::MfaRequired {
+                                        user_id: user.id,
+                                        challenge: start_mfa_challenge(pool, &user).await?,
+                                    });
+                                }
                                 //generate and save access and refresh token
-                                match generate_persisted_user_token(&details.email, pool).await {
+                                match generate_persisted_user_token(&details.email, context, pool)
+                                    .await
+                                {
                                     Ok(tokens) => {
+                                        register_successful_login(pool, &user).await?;
                                         //fetch user details from db and pass them down to user response
                                         match user.to_response(pool).await {
                                             Ok(response) => {
@@ -153,7 +377,9 @@ pub async fn authenticate_user(
                                                     refresh_token: tokens.refresh,
                                                     user: response,
                                                 };
-                                                Ok(AuthenticationResult { session, user })
+                                                Ok(AuthenticationOutcome::Authenticated(Box::new(
+                                                    AuthenticationResult { session, user },
+                                                )))
                                             }
                                             Err(error) => {
                                                 error!("{:?}", error);
@@ -171,10 +397,13 @@ pub async fn authenticate_user(
                                     }
                                 }
                             }
-                            _ => Err(ApplicationError::new(
-                                "Authentication Error",
-                                "Invalid Credentials.",
-                            )),
+                            PasswordVerification::Invalid => {
+                                register_failed_login(pool, &user).await?;
+                                Err(ApplicationError::new(
+                                    "Authentication Error",
+                                    "Invalid Credentials.",
+                                ))
+                            }
                         }
                     }
                 }
@@ -193,20 +422,315 @@ pub async fn authenticate_user(
 
 ///# Refresh Token
 ///
-/// Validate refresh token if it's return new access_token
+/// Validate refresh token if it's valid return new access_token and refresh_token
+///
+/// the presented refresh token is rotated and can't be used again
 pub async fn refresh_token(
     state: Extension<Arc<AppState>>,
+    context: SessionContext,
     token: Json<String>,
 ) -> Result<Json<RefreshTokenResponse>, (StatusCode, Json<ApplicationError>)> {
-    info!("{}", token.0);
-    match generate_persisted_access_token(&token, &state.pool).await {
+    match rotate_refresh_token(&token, &context, &state.pool).await {
         Err(error) => {
             log::error!("{:?}", error);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(error),
-            ))
+            //the token may not even decode, so the event has no actor
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(AuditEventType::TOKEN_REFRESHED, AuditOutcome::FAILURE)
+                    .context(&context)
+                    .metadata(json!({"reason": error.description})),
+            )
+            .await;
+            Err((StatusCode::UNAUTHORIZED, Json(error)))
+        }
+        Ok(result) => Ok(Json(RefreshTokenResponse {
+            access_token: result.access,
+            refresh_token: result.refresh,
+        })),
+    }
+}
+
+///# Logout
+///
+/// revoke the presented access token and every token of its session, including the refresh token
+pub async fn logout(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    headers: HeaderMap,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    let unauthorized = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ApplicationError::new(
+                "Authentication Error",
+                "invalid token",
+            )),
+        )
+    };
+    let Some(token) = extract_bearer_token(&headers) else {
+        return Err(unauthorized());
+    };
+    //an unknown token or one of another user is refused, only a failing database is a 500
+    let token = match get_token_by_token(token, &state.pool).await {
+        Ok(token) if token.user_id == user.id => token,
+        Err(error) if error.is_internal() => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
         }
-        Ok(result) => Ok(Json(RefreshTokenResponse{ access_token: result})),
+        _ => return Err(unauthorized()),
+    };
+
+    match revoke_session_tokens(&state.pool, &token.session_id).await {
+        Ok(revoked) => {
+            info!("user {} logged out, {} tokens revoked", user.id, revoked);
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(AuditEventType::LOGOUT, AuditOutcome::SUCCESS)
+                    .actor(user.id)
+                    .context(&context),
+            )
+            .await;
+            Ok(Json(MessageResponse::new("Logged out successfully")))
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Logout All
+///
+/// revoke every access and refresh token of the user on all devices
+pub async fn logout_all(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match revoke_user_tokens(&state.pool, &user.id).await {
+        Ok(revoked) => {
+            info!(
+                "user {} logged out everywhere, {} tokens revoked",
+                user.id, revoked
+            );
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(AuditEventType::LOGOUT_ALL, AuditOutcome::SUCCESS)
+                    .actor(user.id)
+                    .context(&context)
+                    .metadata(json!({"revoked_tokens": revoked})),
+            )
+            .await;
+            Ok(Json(MessageResponse::new("Logged out of all sessions")))
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::password_hasher::PasswordHasher;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::repositories::user_repository::get_user_by_id;
+    use crate::users::services::jwt_service::UserTokenResponse;
+    use axum::extract::FromRequestParts;
+
+    //a verified user with a fresh session, returns the user and its first token pair
+    async fn sign_in(pool: &PgPool, email: &str) -> (User, UserTokenResponse) {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', $2, 'hash', true)",
+            Uuid::new_v4(),
+            email
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        let tokens = generate_persisted_user_token(email, &SessionContext::default(), pool)
+            .await
+            .unwrap();
+        (get_user_by_email(pool, email).await.unwrap(), tokens)
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[sqlx::test]
+    async fn logout_revokes_the_whole_session_family(pool: PgPool) {
+        let (user, tokens) = sign_in(&pool, "ada@x.io").await;
+        let other_device =
+            generate_persisted_user_token("ada@x.io", &SessionContext::default(), &pool)
+                .await
+                .unwrap();
+        let rotated = rotate_refresh_token(&tokens.refresh, &SessionContext::default(), &pool)
+            .await
+            .unwrap();
+        let (state, _) = AppState::for_tests(pool);
+
+        assert!(
+            logout(
+                user,
+                Extension(state.clone()),
+                SessionContext::default(),
+                bearer(&rotated.access)
+            )
+            .await
+            .is_ok()
+        );
+
+        for (token, token_type) in [
+            (&tokens.access, TokenType::ACCESS),
+            (&tokens.refresh, TokenType::REFRESH),
+            (&rotated.access, TokenType::ACCESS),
+            (&rotated.refresh, TokenType::REFRESH),
+        ] {
+            let error = verify_token(token, token_type, &state.pool)
+                .await
+                .unwrap_err();
+            assert_eq!(error.description, "JWT Token is revoked");
+        }
+        //other devices stay signed in
+        assert!(
+            verify_token(&other_device.access, TokenType::ACCESS, &state.pool)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[sqlx::test]
+    async fn a_second_logout_fails_cleanly(pool: PgPool) {
+        let (user, tokens) = sign_in(&pool, "ada@x.io").await;
+        let (_, other_user) = sign_in(&pool, "eve@x.io").await;
+        let (state, _) = AppState::for_tests(pool);
+        let logout_with = |headers: HeaderMap| {
+            logout(
+                user.clone(),
+                Extension(state.clone()),
+                SessionContext::default(),
+                headers,
+            )
+        };
+        assert!(logout_with(bearer(&tokens.access)).await.is_ok());
+
+        //the revoked token no longer resolves to a user
+        let (mut parts, _) = axum::http::Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", tokens.access))
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.extensions.insert(state.clone());
+        let (status, _) = User::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        //without a token, with an unknown one or with another user's the handler refuses too
+        for headers in [
+            HeaderMap::new(),
+            bearer("unknown"),
+            bearer(&other_user.access),
+        ] {
+            let (status, _) = logout_with(headers).await.err().unwrap();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        assert!(
+            verify_token(&other_user.access, TokenType::ACCESS, &state.pool)
+                .await
+                .is_ok()
+        );
+    }
+
+    async fn insert_bcrypt_user(pool: &PgPool) -> Uuid {
+        PasswordHasher::install_for_tests();
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', $2, true)",
+            id,
+            bcrypt::hash("Quartz-Lamp-58", 4).unwrap()
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn password_login(pool: &PgPool) -> Result<AuthenticationOutcome, ApplicationError> {
+        let request = LoginRequest {
+            email: String::from("ada@x.io"),
+            password: String::from("Quartz-Lamp-58"),
+        };
+        authenticate_user(None, Some(request), &SessionContext::default(), pool).await
+    }
+
+    #[sqlx::test]
+    async fn an_outdated_hash_is_rewritten_on_the_next_login(pool: PgPool) {
+        let id = insert_bcrypt_user(&pool).await;
+        let bcrypt = get_user_by_id(&pool, &id).await.unwrap().password.unwrap();
+        assert_eq!(
+            verify_password("Quartz-Lamp-58", Some(&bcrypt))
+                .await
+                .unwrap(),
+            PasswordVerification::Outdated
+        );
+
+        assert!(matches!(
+            password_login(&pool).await,
+            Ok(AuthenticationOutcome::Authenticated(_))
+        ));
+
+        let upgraded = get_user_by_id(&pool, &id).await.unwrap().password.unwrap();
+        assert!(upgraded.starts_with("$argon2id$"));
+        assert_eq!(
+            verify_password("Quartz-Lamp-58", Some(&upgraded))
+                .await
+                .unwrap(),
+            PasswordVerification::Valid
+        );
+        //a current hash is left alone
+        assert!(password_login(&pool).await.is_ok());
+        assert_eq!(
+            get_user_by_id(&pool, &id).await.unwrap().password,
+            Some(upgraded)
+        );
+    }
+
+    #[sqlx::test]
+    async fn a_failed_upgrade_does_not_block_the_login(pool: PgPool) {
+        let id = insert_bcrypt_user(&pool).await;
+        let bcrypt = get_user_by_id(&pool, &id).await.unwrap().password;
+        //every write of the password fails from now on
+        sqlx::raw_sql(
+            "create function refuse_password_update() returns trigger as $$
+            begin raise exception 'password updates are refused'; end $$ language plpgsql;
+            create trigger refuse_password_update before update of password on users
+            for each row execute function refuse_password_update();",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            password_login(&pool).await,
+            Ok(AuthenticationOutcome::Authenticated(_))
+        ));
+        assert_eq!(get_user_by_id(&pool, &id).await.unwrap().password, bcrypt);
     }
 }
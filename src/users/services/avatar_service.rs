@@ -0,0 +1,348 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::storage::file_storage::FileStorage;
+use crate::users::repositories::user_repository::set_user_avatar;
+use crate::users::services::profile_service::profile_response;
+use crate::users::types::profile_response::ProfileResponse;
+use crate::users::types::user::User;
+use axum::extract::{Multipart, Path};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use log::{error, info};
+use reqwest::StatusCode;
+use std::env;
+use std::io::Cursor;
+use std::sync::Arc;
+use uuid::Uuid;
+
+///square sizes every avatar is stored in, the largest one is used as image url
+pub const AVATAR_SIZES: [u32; 3] = [64, 128, 256];
+
+///storage key of one size of an avatar
+pub fn avatar_key(user_id: &Uuid, avatar_id: &Uuid, size: u32) -> String {
+    format!("avatars/{}/{}/{}.png", user_id, avatar_id, size)
+}
+
+fn avatar_url(user_id: &Uuid, avatar_id: &Uuid) -> String {
+    let base = env::var("AVATAR_URL").unwrap_or(String::from("http://localhost:8080/avatars"));
+    format!(
+        "{}/{}/{}/{}",
+        base.trim_end_matches('/'),
+        user_id,
+        avatar_id,
+        AVATAR_SIZES[AVATAR_SIZES.len() - 1]
+    )
+}
+
+fn avatar_error(status: StatusCode, description: &str) -> (StatusCode, Json<ApplicationError>) {
+    (
+        status,
+        Json(ApplicationError::new("Avatar Error", description)),
+    )
+}
+
+///# Resize Avatar
+///
+/// the format is taken from the magic bytes, never from the file name or content type
+///
+/// every size is cropped to a square and re-encoded as png, which drops metadata and anything appended to the file
+fn resize_avatar(
+    bytes: &[u8],
+    max_dimension: u32,
+) -> Result<Vec<(u32, Vec<u8>)>, ApplicationError> {
+    let format = image::guess_format(bytes)
+        .ok()
+        .filter(|format| {
+            matches!(
+                format,
+                ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+            )
+        })
+        .ok_or_else(|| {
+            ApplicationError::new(
+                "Avatar Error",
+                "Avatar must be a png, jpeg, gif or webp image",
+            )
+        })?;
+
+    //refuse huge dimensions before the pixels are allocated
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(max_dimension);
+    limits.max_image_height = Some(max_dimension);
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader.decode()?;
+
+    let mut resized = Vec::new();
+    for size in AVATAR_SIZES {
+        let mut encoded = Cursor::new(Vec::new());
+        image
+            .resize_to_fill(size, size, FilterType::Lanczos3)
+            .write_to(&mut encoded, ImageFormat::Png)?;
+        resized.push((size, encoded.into_inner()));
+    }
+    Ok(resized)
+}
+
+///# Delete Avatar Files
+///
+/// remove every stored size of an avatar, failures are logged and leave the profile untouched
+pub async fn delete_avatar_files(storage: &Arc<dyn FileStorage>, user_id: &Uuid, avatar_id: &Uuid) {
+    for size in AVATAR_SIZES {
+        if let Err(error) = storage.delete(&avatar_key(user_id, avatar_id, size)).await {
+            error!("{:?}", error);
+        }
+    }
+}
+
+//read the `file` field chunk by chunk so an oversized upload is refused without buffering it
+async fn read_avatar_field(
+    multipart: &mut Multipart,
+    max_size: usize,
+) -> Result<Vec<u8>, (StatusCode, Json<ApplicationError>)> {
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => {
+                return Err(avatar_error(
+                    StatusCode::BAD_REQUEST,
+                    "Multipart field `file` is missing",
+                ));
+            }
+            Err(error) => return Err(avatar_error(error.status(), &error.body_text())),
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+        loop {
+            match field.chunk().await {
+                Ok(Some(chunk)) => {
+                    if bytes.len() + chunk.len() > max_size {
+                        return Err(avatar_error(
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            &format!("Avatar must be at most {} bytes", max_size),
+                        ));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                Ok(None) => return Ok(bytes),
+                Err(error) => return Err(avatar_error(error.status(), &error.body_text())),
+            }
+        }
+    }
+}
+
+///# Upload Avatar
+///
+/// multipart upload with the image in the `file` field, limited by AVATAR_MAX_SIZE (bytes)
+/// and AVATAR_MAX_DIMENSION (pixels)
+///
+/// the image url of the user points at the new avatar, a replaced avatar is deleted
+pub async fn upload_avatar(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<ApplicationError>)> {
+    let limits: Result<(usize, u32), ApplicationError> = (|| {
+        Ok((
+            env::var("AVATAR_MAX_SIZE")
+                .unwrap_or(String::from("5242880"))
+                .parse()?,
+            env::var("AVATAR_MAX_DIMENSION")
+                .unwrap_or(String::from("4096"))
+                .parse()?,
+        ))
+    })();
+    let (max_size, max_dimension) = match limits {
+        Ok(limits) => limits,
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    let bytes = read_avatar_field(&mut multipart, max_size).await?;
+
+    //decoding and resizing is cpu bound, keep it off the async workers
+    let resized = match tokio::task::spawn_blocking(move || resize_avatar(&bytes, max_dimension))
+        .await
+        .map_err(ApplicationError::from)
+    {
+        Ok(Ok(resized)) => resized,
+        Ok(Err(error)) => {
+            info!("avatar of user {} refused: {}", user.id, error.description);
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, Json(error)));
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    let avatar_id = Uuid::new_v4();
+    let result = async {
+        for (size, bytes) in resized {
+            state
+                .storage
+                .put(&avatar_key(&user.id, &avatar_id, size), bytes)
+                .await?;
+        }
+        set_user_avatar(
+            &state.pool,
+            &user.id,
+            Some(avatar_id),
+            Some(&avatar_url(&user.id, &avatar_id)),
+        )
+        .await
+    }
+    .await;
+
+    match result {
+        Ok(previous) => {
+            if let Some(previous) = previous {
+                delete_avatar_files(&state.storage, &user.id, &previous).await;
+            }
+            info!("avatar {} uploaded for user {}", avatar_id, user.id);
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            delete_avatar_files(&state.storage, &user.id, &avatar_id).await;
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    }
+
+    match profile_response(
+        &state.pool,
+        &User {
+            image_url: Some(avatar_url(&user.id, &avatar_id)),
+            avatar_id: Some(avatar_id),
+            ..user
+        },
+    )
+    .await
+    {
+        Ok(response) => Ok(Json(response)),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Delete Avatar
+///
+/// clear the image url of the user and delete an uploaded avatar
+pub async fn delete_avatar(
+    user: User,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<ApplicationError>)> {
+    match set_user_avatar(&state.pool, &user.id, None, None).await {
+        Ok(previous) => {
+            if let Some(previous) = previous {
+                delete_avatar_files(&state.storage, &user.id, &previous).await;
+                info!("avatar {} of user {} deleted", previous, user.id);
+            }
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    }
+
+    match profile_response(
+        &state.pool,
+        &User {
+            image_url: None,
+            avatar_id: None,
+            ..user
+        },
+    )
+    .await
+    {
+        Ok(response) => Ok(Json(response)),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Get Avatar
+///
+/// serve one stored size, avatars are public and never change under the same id
+pub async fn get_avatar(
+    state: Extension<Arc<AppState>>,
+    Path((user_id, avatar_id, size)): Path<(Uuid, Uuid, u32)>,
+) -> Result<Response, (StatusCode, Json<ApplicationError>)> {
+    let not_found = || avatar_error(StatusCode::NOT_FOUND, "Avatar not found");
+    if !AVATAR_SIZES.contains(&size) {
+        return Err(not_found());
+    }
+
+    match state
+        .storage
+        .get(&avatar_key(&user_id, &avatar_id, size))
+        .await
+    {
+        Ok(Some(bytes)) => Ok((
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+                (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            ],
+            bytes,
+        )
+            .into_response()),
+        Ok(None) => Err(not_found()),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut encoded = Cursor::new(Vec::new());
+        RgbImage::from_pixel(width, height, Rgb([200, 40, 90]))
+            .write_to(&mut encoded, ImageFormat::Png)
+            .unwrap();
+        encoded.into_inner()
+    }
+
+    #[test]
+    fn resizes_an_image_to_every_size() {
+        let resized = resize_avatar(&png(300, 200), 4096).unwrap();
+
+        let sizes: Vec<u32> = resized.iter().map(|(size, _)| *size).collect();
+        assert_eq!(sizes, AVATAR_SIZES);
+        for (size, bytes) in resized {
+            let image = image::load_from_memory_with_format(&bytes, ImageFormat::Png).unwrap();
+            assert_eq!((image.width(), image.height()), (size, size));
+        }
+    }
+
+    #[test]
+    fn refuses_a_file_that_is_no_image_whatever_its_name() {
+        //the name `avatar.png` never reaches resize_avatar, only the bytes do
+        let error = resize_avatar(b"<?php echo 'not an image'; ?>", 4096).unwrap_err();
+        assert_eq!(error.error, "Avatar Error");
+    }
+
+    #[test]
+    fn refuses_an_image_larger_than_the_maximum_dimension() {
+        //the limits are checked against the header, before any pixel is decoded
+        let error = resize_avatar(&png(65, 10), 64).unwrap_err();
+        assert_eq!(error.error, "Image Error");
+        assert!(resize_avatar(&png(64, 64), 64).is_ok());
+    }
+}
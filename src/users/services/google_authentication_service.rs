@@ -0,0 +1,425 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::secure_token::{
+    generate_secure_token, hash_secure_token, secure_tokens_equal,
+};
+use crate::users::repositories::authentication_repository::save_new_user_and_allocate_a_role;
+use crate::users::repositories::oauth_state_repository::{
+    consume_oauth_state, persist_oauth_state,
+};
+use crate::users::repositories::user_repository::{claim_unverified_account, find_user_by_email};
+use crate::users::services::account_status_service::check_account_status;
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::authentication_service::generate_user_session;
+use crate::users::services::google_oidc_service::GoogleOidcClient;
+use crate::users::services::mfa_service::{is_mfa_enabled, start_mfa_challenge};
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::google_callback_query::GoogleCallbackQuery;
+use crate::users::types::google_id_token_claims::GoogleIdTokenClaims;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_source::UserSource;
+use axum::extract::Query;
+use axum::http::header::{COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderName};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Extension, Json};
+use log::{error, info, warn};
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+//the state is also kept in a cookie of the browser that started the sign-in, a callback carrying
+//a state started elsewhere is refused, so nobody can sign a victim into the attacker's account
+const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+fn oauth_state_cookie(value: &str, max_age: i64) -> [(HeaderName, String); 1] {
+    [(
+        SET_COOKIE,
+        format!(
+            "{OAUTH_STATE_COOKIE}={value}; Path=/auth/google; Max-Age={max_age}; HttpOnly; Secure; SameSite=Lax"
+        ),
+    )]
+}
+
+fn oauth_state_from_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(OAUTH_STATE_COOKIE)?
+                .strip_prefix('=')
+        })
+}
+
+fn google_client(
+    state: &AppState,
+) -> Result<&GoogleOidcClient, (StatusCode, Json<ApplicationError>)> {
+    state.google_oidc.as_ref().ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ApplicationError::new(
+            "OAuth Error",
+            "Google sign-in is not configured",
+        )),
+    ))
+}
+
+///# Google Start
+///
+/// persist state, nonce and PKCE verifier then redirect to the Google consent screen
+///
+/// the state is bound to the browser with a cookie for the callback
+pub async fn google_start(
+    state: Extension<Arc<AppState>>,
+) -> Result<([(HeaderName, String); 1], Redirect), (StatusCode, Json<ApplicationError>)> {
+    let client = google_client(&state)?;
+
+    let oauth_state = generate_secure_token();
+    let nonce = generate_secure_token();
+    let code_verifier = generate_secure_token();
+
+    if let Err(error) = persist_oauth_state(
+        &state.pool,
+        &hash_secure_token(&oauth_state),
+        &code_verifier,
+        &nonce,
+        OffsetDateTime::now_utc() + Duration::minutes(10),
+    )
+    .await
+    {
+        error!("{:?}", error);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+    }
+
+    match client.authorization_url(&oauth_state, &nonce, &code_verifier) {
+        Ok(url) => Ok((oauth_state_cookie(&oauth_state, 600), Redirect::to(&url))),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Google Callback
+///
+/// validate the state against the cookie of the browser and the database, exchange the code, verify the id token
+///
+/// find or create the GOOGLE user and return the same session, or mfa challenge, as a password login
+pub async fn google_callback(
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    headers: HeaderMap,
+    query: Query<GoogleCallbackQuery>,
+) -> Result<([(HeaderName, String); 1], Response), (StatusCode, Json<ApplicationError>)> {
+    let client = google_client(&state)?;
+    let query = query.0;
+
+    if let Some(error) = query.error {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ApplicationError::new("OAuth Error", error)),
+        ));
+    }
+    let (Some(code), Some(oauth_state)) = (query.code, query.state) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "OAuth Error",
+                "Missing code or state",
+            )),
+        ));
+    };
+
+    if !oauth_state_from_cookie(&headers)
+        .is_some_and(|cookie| secure_tokens_equal(cookie, &oauth_state))
+    {
+        warn!("google callback without the state cookie of its sign-in");
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ApplicationError::new(
+                "OAuth Error",
+                "Sign-in was not started in this browser",
+            )),
+        ));
+    }
+
+    let user = match authenticate_google_user(client, &state.pool, &code, &oauth_state).await {
+        Ok(user) => user,
+        Err(error) => {
+            error!("{:?}", error);
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent::new(AuditEventType::LOGIN, AuditOutcome::FAILURE)
+                    .context(&context)
+                    .metadata(json!({"method": "google", "reason": error.error})),
+            )
+            .await;
+            return Err((StatusCode::UNAUTHORIZED, Json(error)));
+        }
+    };
+    let response = sign_in_google_user(&state.pool, &context, &user).await?;
+    Ok((oauth_state_cookie("", 0), response))
+}
+
+//the user whose verified google email the id token carries
+async fn authenticate_google_user(
+    client: &GoogleOidcClient,
+    pool: &PgPool,
+    code: &str,
+    oauth_state: &str,
+) -> Result<User, ApplicationError> {
+    let saved_state = consume_oauth_state(pool, &hash_secure_token(oauth_state)).await?;
+    let tokens = client
+        .exchange_code(code, &saved_state.code_verifier)
+        .await?;
+    let claims = client
+        .verify_id_token(&tokens.id_token, &saved_state.nonce)
+        .await?;
+
+    if !claims.email_verified {
+        return Err(ApplicationError::new(
+            "OAuth Error",
+            "Google account email is not verified",
+        ));
+    }
+
+    find_or_create_google_user(pool, &claims).await
+}
+
+///# Sign In Google User
+///
+/// the account status is checked as with any other sign-in, accounts with two-factor
+/// authentication get an mfa challenge instead of the session
+async fn sign_in_google_user(
+    pool: &PgPool,
+    context: &SessionContext,
+    user: &User,
+) -> Result<Response, (StatusCode, Json<ApplicationError>)> {
+    let audit = NewAuditEvent::new(AuditEventType::LOGIN, AuditOutcome::SUCCESS)
+        .actor(user.id)
+        .context(context);
+    if let Err(error) = check_account_status(user) {
+        record_audit_event(
+            pool,
+            NewAuditEvent {
+                outcome: AuditOutcome::FAILURE,
+                ..audit
+            }
+            .metadata(json!({"method": "google", "reason": error.error})),
+        )
+        .await;
+        return Err((StatusCode::UNAUTHORIZED, Json(error)));
+    }
+    let result = match is_mfa_enabled(pool, user).await {
+        //LOGIN is only recorded once the second factor is verified
+        Ok(true) => start_mfa_challenge(pool, user).await.map(|challenge| {
+            (
+                NewAuditEvent {
+                    event_type: AuditEventType::MFA_CHALLENGE,
+                    ..audit
+                },
+                Json(challenge).into_response(),
+            )
+        }),
+        Ok(false) => generate_user_session(&user.email, context, pool)
+            .await
+            .map(|result| (audit, Json(result.session).into_response())),
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok((event, response)) => {
+            record_audit_event(pool, event.metadata(json!({"method": "google"}))).await;
+            Ok(response)
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Find Or Create Google User
+///
+/// an existing account with the same email is reused, google has verified the email
+///
+/// an unverified account may have been registered by someone else, its password and sessions are dropped
+///
+/// new accounts are created without a password, a reset sets one
+async fn find_or_create_google_user(
+    pool: &PgPool,
+    claims: &GoogleIdTokenClaims,
+) -> Result<User, ApplicationError> {
+    if let Some(user) = find_user_by_email(pool, &claims.email).await? {
+        if user.email_verified || !claim_unverified_account(pool, &user.id).await? {
+            return Ok(user);
+        }
+        warn!(
+            "unverified user {} claimed by a google sign-in, password and sessions dropped",
+            user.id
+        );
+        return Ok(User {
+            password: None,
+            email_verified: true,
+            ..user
+        });
+    }
+
+    let user = User {
+        id: Uuid::new_v4(),
+        name: claims.name.clone().unwrap_or(claims.email.clone()),
+        email: claims.email.clone(),
+        is_enabled: Some(true),
+        is_account_non_expired: Some(true),
+        is_account_non_locked: Some(true),
+        password: None,
+        image_url: claims.picture.clone(),
+        created_at: None,
+        updated_at: None,
+        source: UserSource::GOOGLE,
+        failed_login_attempts: 0,
+        lockout_count: 0,
+        locked_until: None,
+        account_expires_at: None,
+        avatar_id: None,
+        deletion_scheduled_at: None,
+        email_verified: true,
+    };
+    save_new_user_and_allocate_a_role(pool, &user).await?;
+    info!("created GOOGLE user {}", user.id);
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::users::repositories::audit_repository::get_audit_events_by_actor_id;
+    use crate::users::repositories::user_repository::get_user_by_id;
+    use axum::http::HeaderValue;
+    use serde_json::Value;
+
+    async fn body(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn claims(email: &str) -> GoogleIdTokenClaims {
+        GoogleIdTokenClaims {
+            sub: Uuid::new_v4().to_string(),
+            email: email.to_string(),
+            email_verified: true,
+            name: None,
+            picture: None,
+            nonce: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn locked_and_two_factor_accounts_get_no_session(pool: PgPool) {
+        JwtKeySet::install_for_tests();
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, email_verified, is_account_non_locked, locked_until)
+            values ($1, 'Ada', 'ada@x.io', true, false, now() + interval '1 hour')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let context = SessionContext::default();
+
+        let user = get_user_by_id(&pool, &id).await.unwrap();
+        let (status, error) = sign_in_google_user(&pool, &context, &user)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(error.error, "Account Locked");
+
+        sqlx::query!(
+            "update users set is_account_non_locked = true, locked_until = null where id = $1",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "insert into user_mfa(user_id, secret, is_enabled) values ($1, 'secret', true)",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let user = get_user_by_id(&pool, &id).await.unwrap();
+        let challenge = body(sign_in_google_user(&pool, &context, &user).await.unwrap()).await;
+        assert_eq!(challenge["mfa_required"], true);
+        assert!(challenge.get("access_token").is_none());
+
+        let events: Vec<(String, String)> = get_audit_events_by_actor_id(&pool, &id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| (event.event_type, event.outcome))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                ("LOGIN".to_string(), "FAILURE".to_string()),
+                ("MFA_CHALLENGE".to_string(), "SUCCESS".to_string())
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn an_unverified_account_is_claimed(pool: PgPool) {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password) values ($1, 'Ada', 'ada@x.io', 'hash')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let claims = claims("ada@x.io");
+
+        let user = find_or_create_google_user(&pool, &claims).await.unwrap();
+        assert!(user.password.is_none() && user.email_verified);
+        let stored = get_user_by_id(&pool, &id).await.unwrap();
+        assert!(stored.password.is_none() && stored.email_verified);
+        //the first sign-in of the claimed account passes the status check
+        assert!(check_account_status(&user).is_ok());
+    }
+
+    #[sqlx::test]
+    async fn new_accounts_are_created_without_a_password(pool: PgPool) {
+        let claims = claims("ada@x.io");
+
+        let user = find_or_create_google_user(&pool, &claims).await.unwrap();
+        let stored = get_user_by_id(&pool, &user.id).await.unwrap();
+        assert!(stored.password.is_none() && stored.email_verified);
+        assert_eq!(stored.source, UserSource::GOOGLE);
+    }
+
+    #[test]
+    fn state_is_read_from_its_cookie_only() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_static("theme=dark; oauth_state_old=x; oauth_state=abc"),
+        );
+        assert_eq!(oauth_state_from_cookie(&headers), Some("abc"));
+
+        headers.insert(COOKIE, HeaderValue::from_static("theme=dark"));
+        assert_eq!(oauth_state_from_cookie(&headers), None);
+    }
+}
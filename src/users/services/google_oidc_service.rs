@@ -0,0 +1,366 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::types::google_id_token_claims::GoogleIdTokenClaims;
+use crate::users::types::google_token_response::GoogleTokenResponse;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use log::{error, info};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use url::Url;
+
+///# Google OIDC Config
+///
+/// every provider endpoint is configurable so a local identity provider can stand in for Google
+#[derive(Clone, Debug)]
+pub struct GoogleOidcConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub issuers: Vec<String>,
+    pub jwks_cache_ttl: Duration,
+}
+
+struct CachedJwks {
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+pub struct GoogleOidcClient {
+    config: GoogleOidcConfig,
+    http: reqwest::Client,
+    jwks: RwLock<Option<CachedJwks>>,
+}
+
+///PKCE S256 challenge of the code verifier
+pub fn pkce_code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+impl GoogleOidcClient {
+    pub fn new(config: GoogleOidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    ///# Authorization Url
+    ///
+    /// url of the provider consent screen for the authorization code flow with PKCE
+    pub fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, ApplicationError> {
+        Ok(Url::parse_with_params(
+            &self.config.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("scope", "openid email profile"),
+                ("state", state),
+                ("nonce", nonce),
+                (
+                    "code_challenge",
+                    pkce_code_challenge(code_verifier).as_str(),
+                ),
+                ("code_challenge_method", "S256"),
+            ],
+        )?
+        .to_string())
+    }
+
+    ///# Exchange Code
+    ///
+    /// trade the authorization code and PKCE verifier for the provider tokens
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<GoogleTokenResponse, ApplicationError> {
+        let response = self
+            .http
+            .post(&self.config.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            error!("token endpoint responded with {}", response.status());
+            return Err(ApplicationError::new(
+                "OAuth Error",
+                "Authorization code exchange failed",
+            ));
+        }
+        Ok(response.json::<GoogleTokenResponse>().await?)
+    }
+
+    ///# Verify Id Token
+    ///
+    /// check signature against the provider JWKS, audience, issuer, expiry and nonce
+    pub async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<GoogleIdTokenClaims, ApplicationError> {
+        let kid = decode_header(id_token)?.kid.ok_or(ApplicationError::new(
+            "OAuth Error",
+            "Id token has no key id",
+        ))?;
+        let key = self.decoding_key(&kid).await?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&self.config.issuers);
+
+        let claims = decode::<GoogleIdTokenClaims>(id_token, &key, &validation)?.claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(ApplicationError::new(
+                "OAuth Error",
+                "Id token nonce mismatch",
+            ));
+        }
+        Ok(claims)
+    }
+
+    ///decoding key for the kid, the JWKS is refetched when stale or the kid is unknown
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, ApplicationError> {
+        if let Some(cached) = self.jwks.read().await.as_ref()
+            && cached.fetched_at.elapsed() < self.config.jwks_cache_ttl
+            && let Some(jwk) = cached.jwks.find(kid)
+        {
+            return Ok(DecodingKey::from_jwk(jwk)?);
+        }
+
+        let mut cache = self.jwks.write().await;
+        let jwks = self
+            .http
+            .get(&self.config.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await?;
+        info!(
+            "fetched {} signing keys from {}",
+            jwks.keys.len(),
+            self.config.jwks_uri
+        );
+
+        let key = jwks.find(kid).map(DecodingKey::from_jwk);
+        *cache = Some(CachedJwks {
+            jwks,
+            fetched_at: Instant::now(),
+        });
+        Ok(key.ok_or(ApplicationError::new(
+            "OAuth Error",
+            "Id token signed with an unknown key",
+        ))??)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::{JwtKey, JwtKeySet};
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    const RSA_PEM: &[u8] = include_bytes!("../../application/security/test_rsa_key.pem");
+    const CLIENT_ID: &str = "client-id";
+    const ISSUER: &str = "https://accounts.google.com";
+    const NONCE: &str = "nonce";
+
+    #[derive(Serialize)]
+    struct IdTokenClaims {
+        iss: String,
+        aud: String,
+        exp: u64,
+        sub: String,
+        email: String,
+        email_verified: bool,
+        nonce: String,
+    }
+
+    fn id_token_claims() -> IdTokenClaims {
+        IdTokenClaims {
+            iss: ISSUER.to_owned(),
+            aud: CLIENT_ID.to_owned(),
+            exp: jsonwebtoken::get_current_timestamp() + 60,
+            sub: String::from("google-user"),
+            email: String::from("ada@x.io"),
+            email_verified: true,
+            nonce: NONCE.to_owned(),
+        }
+    }
+
+    fn signing_keys(kid: &str) -> JwtKeySet {
+        JwtKeySet::new(vec![JwtKey::from_pem(kid, RSA_PEM).unwrap()], Some(kid)).unwrap()
+    }
+
+    //the token endpoint hands back the code as the id token, so every test picks its own token
+    async fn token_endpoint(
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<GoogleTokenResponse>, StatusCode> {
+        match (form.get("code"), form.get("code_verifier")) {
+            (Some(code), Some(_)) if code != "refused" => Ok(Json(GoogleTokenResponse {
+                id_token: code.clone(),
+            })),
+            _ => Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    async fn jwks_endpoint(State(jwks): State<Arc<JwkSet>>) -> Json<JwkSet> {
+        Json(jwks.as_ref().clone())
+    }
+
+    //a local identity provider publishing the key `idp`
+    async fn mock_idp() -> GoogleOidcClient {
+        let router = Router::new()
+            .route("/token", post(token_endpoint))
+            .route("/jwks", get(jwks_endpoint))
+            .with_state(Arc::new(signing_keys("idp").jwks()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        GoogleOidcClient::new(GoogleOidcConfig {
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: String::from("client-secret"),
+            redirect_uri: String::from("http://localhost/auth/google/callback"),
+            authorization_endpoint: format!("http://{}/authorize", address),
+            token_endpoint: format!("http://{}/token", address),
+            jwks_uri: format!("http://{}/jwks", address),
+            issuers: vec![ISSUER.to_owned()],
+            jwks_cache_ttl: Duration::from_secs(60),
+        })
+    }
+
+    async fn sign_in(
+        client: &GoogleOidcClient,
+        id_token: &str,
+    ) -> Result<GoogleIdTokenClaims, ApplicationError> {
+        let tokens = client.exchange_code(id_token, "verifier").await?;
+        client.verify_id_token(&tokens.id_token, NONCE).await
+    }
+
+    #[tokio::test]
+    async fn a_valid_id_token_is_accepted() {
+        let client = mock_idp().await;
+        let token = signing_keys("idp").encode(&id_token_claims()).unwrap();
+
+        let claims = sign_in(&client, &token).await.unwrap();
+        assert_eq!(claims.email, "ada@x.io");
+        assert!(claims.email_verified);
+    }
+
+    #[tokio::test]
+    async fn a_refused_code_exchange_fails() {
+        let client = mock_idp().await;
+        let error = sign_in(&client, "refused").await.unwrap_err();
+        assert_eq!(error.description, "Authorization code exchange failed");
+    }
+
+    #[tokio::test]
+    async fn a_tampered_signature_is_rejected() {
+        let client = mock_idp().await;
+        let token = signing_keys("idp").encode(&id_token_claims()).unwrap();
+        let forged = signing_keys("idp")
+            .encode(&IdTokenClaims {
+                email: String::from("mallory@x.io"),
+                ..id_token_claims()
+            })
+            .unwrap();
+        //the claims of one token under the signature of the other
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged_parts: Vec<&str> = forged.split('.').collect();
+        let tampered = format!("{}.{}.{}", parts[0], forged_parts[1], parts[2]);
+
+        let error = sign_in(&client, &tampered).await.unwrap_err();
+        assert_eq!(error.description, "InvalidSignature");
+    }
+
+    #[tokio::test]
+    async fn the_wrong_audience_or_issuer_is_rejected() {
+        let client = mock_idp().await;
+        let keys = signing_keys("idp");
+        let wrong_audience = keys
+            .encode(&IdTokenClaims {
+                aud: String::from("another-client"),
+                ..id_token_claims()
+            })
+            .unwrap();
+        let wrong_issuer = keys
+            .encode(&IdTokenClaims {
+                iss: String::from("https://evil.example"),
+                ..id_token_claims()
+            })
+            .unwrap();
+
+        let error = sign_in(&client, &wrong_audience).await.unwrap_err();
+        assert_eq!(error.description, "InvalidAudience");
+        let error = sign_in(&client, &wrong_issuer).await.unwrap_err();
+        assert_eq!(error.description, "InvalidIssuer");
+    }
+
+    #[tokio::test]
+    async fn an_expired_token_is_rejected() {
+        let client = mock_idp().await;
+        let expired = signing_keys("idp")
+            .encode(&IdTokenClaims {
+                exp: jsonwebtoken::get_current_timestamp() - 3600,
+                ..id_token_claims()
+            })
+            .unwrap();
+
+        let error = sign_in(&client, &expired).await.unwrap_err();
+        assert_eq!(error.description, "ExpiredSignature");
+    }
+
+    #[tokio::test]
+    async fn a_nonce_mismatch_is_rejected() {
+        let client = mock_idp().await;
+        let replayed = signing_keys("idp")
+            .encode(&IdTokenClaims {
+                nonce: String::from("another-sign-in"),
+                ..id_token_claims()
+            })
+            .unwrap();
+
+        let error = sign_in(&client, &replayed).await.unwrap_err();
+        assert_eq!(error.description, "Id token nonce mismatch");
+    }
+
+    #[tokio::test]
+    async fn an_unknown_kid_is_rejected() {
+        let client = mock_idp().await;
+        let token = signing_keys("rotated-away")
+            .encode(&id_token_claims())
+            .unwrap();
+
+        let error = sign_in(&client, &token).await.unwrap_err();
+        assert_eq!(error.description, "Id token signed with an unknown key");
+    }
+}
@@ -1,51 +1,199 @@
 use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::jwt_key_set::jwt_key_set;
+use crate::application::security::session_policy::session_policy;
+use crate::users::repositories::session_repository::{extend_session, touch_session};
 use crate::users::repositories::token_repository::{
-    get_token_by_token, persist_access_tokens, persist_refresh_and_access_tokens,
+    get_token_by_token, persist_access_token, persist_refresh_and_access_tokens,
+    persist_rotated_refresh_token, revoke_session_tokens,
 };
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use crate::users::repositories::user_repository::get_user_by_email;
+use crate::users::services::account_status_service::check_account_status;
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_session::UserSession;
+use axum::Json;
+use axum::http::{HeaderMap, header};
+use jsonwebtoken::jwk::JwkSet;
+use log::{error, warn};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::PgPool;
 use std::cmp::PartialEq;
-use std::env;
-use chrono::{Duration, Utc};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+///# Extract Bearer Token
+///
+/// token of an `Authorization: Bearer <token>` header
+pub fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .filter(|token| !token.is_empty())
+}
 
 pub fn extract_subject(token: &str) -> Result<String, ApplicationError> {
-    let decoding_key = DecodingKey::from_secret(env::var("JWT_SECRET")?.as_ref());
-    let token_data = decode::<Claim>(token, &decoding_key, &Validation::new(Algorithm::HS256))?;
-    Ok(token_data.claims.sub)
+    Ok(jwt_key_set()?.decode::<Claim>(token)?.claims.sub)
 }
 
+///space separated scopes of a service client token, `None` on user tokens
+pub fn extract_scope(token: &str) -> Result<Option<String>, ApplicationError> {
+    Ok(jwt_key_set()?.decode::<Claim>(token)?.claims.scope)
+}
+
+///sign the claims with the current signing key, its kid goes in the header
 pub fn generate_token(clams: Claim) -> Result<String, ApplicationError> {
-    Ok(encode(
-        &Header::default(),
-        &clams,
-        &EncodingKey::from_secret(env::var("JWT_SECRET")?.as_ref()),
-    )?)
+    jwt_key_set()?.encode(&clams)
 }
 
+///# JWKS
+///
+/// public keys of every key that still validates tokens, so other services can verify them without a secret
+pub async fn jwks() -> Result<
+    ([(header::HeaderName, &'static str); 1], Json<JwkSet>),
+    (StatusCode, Json<ApplicationError>),
+> {
+    match jwt_key_set() {
+        Ok(key_set) => Ok((
+            [(header::CACHE_CONTROL, "public, max-age=300")],
+            Json(key_set.jwks()),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Generate Persisted User Token
+///
+/// start a new session for the device described by the context and issue its first token pair
 pub async fn generate_persisted_user_token(
     email: &str,
+    context: &SessionContext,
     pool: &PgPool,
 ) -> Result<UserTokenResponse, ApplicationError> {
-    
+    let user = get_user_by_email(pool, email).await?;
+    let access_claim = get_token_claim(email, TokenType::ACCESS)?;
+    let refresh_claim = get_token_claim(email, TokenType::REFRESH)?;
+    let access_expires_at = claim_expiry(&access_claim)?;
+    let now = OffsetDateTime::now_utc();
+    let session = UserSession {
+        id: Uuid::new_v4(),
+        user_id: user.id,
+        user_agent: context.user_agent.clone(),
+        ip_address: context.ip_address.clone(),
+        created_at: now,
+        last_seen_at: now,
+        expires_at: claim_expiry(&refresh_claim)?,
+        revoked_at: None,
+    };
+
     //persist tokens to the database
-    Ok(
-        persist_refresh_and_access_tokens(pool, &UserTokenResponse {
-        access: generate_token(get_token_claim(email, TokenType::ACCESS)?)?,
-        refresh: generate_token(get_token_claim(email, TokenType::REFRESH)?)?,
-    }, email).await?
+    let tokens = persist_refresh_and_access_tokens(
+        pool,
+        &UserTokenResponse {
+            access: generate_token(access_claim)?,
+            refresh: generate_token(refresh_claim)?,
+        },
+        access_expires_at,
+        &session,
+        session_policy()?.max_sessions_per_user,
     )
+    .await?;
+    Ok(tokens)
 }
 
-///# Save Access Token
-pub async fn generate_persisted_access_token(
+///# Generate Client Token
+///
+/// access token for a service client in the same format as user tokens, the granted scopes go in `scope`
+///
+/// no refresh token is issued, the client simply asks again, the token is returned with its expiry
+pub async fn generate_persisted_client_token(
+    user: &User,
+    scopes: &[String],
+    pool: &PgPool,
+) -> Result<(String, OffsetDateTime), ApplicationError> {
+    let mut claim = get_token_claim(&user.email, TokenType::ACCESS)?;
+    claim.scope = Some(scopes.join(" "));
+    let expires_at = claim_expiry(&claim)?;
+    let access = generate_token(claim)?;
+    persist_access_token(pool, &user.id, &access, expires_at).await?;
+    Ok((access, expires_at))
+}
+
+///# Rotate Refresh Token
+///
+/// exchange a valid refresh token for a new access and refresh token, the old refresh token stops working
+///
+/// presenting an already rotated refresh token revokes its whole family since it was most likely stolen
+pub async fn rotate_refresh_token(
     token: &str,
+    context: &SessionContext,
     pg_pool: &PgPool,
-) -> Result<String, ApplicationError> {
+) -> Result<UserTokenResponse, ApplicationError> {
     let result = verify_token(token, TokenType::REFRESH, pg_pool).await?;
     let username = extract_subject(&result)?;
-    
-    Ok(persist_access_tokens(pg_pool, &generate_token(get_token_claim(&username, TokenType::ACCESS)?)?, &username).await?)
+    let refresh = get_token_by_token(&result, pg_pool).await?;
+    //a disabled, expired or locked account can't renew its session
+    check_account_status(&get_user_by_email(pg_pool, &username).await?)?;
+
+    let access_claim = get_token_claim(&username, TokenType::ACCESS)?;
+    let refresh_claim = get_token_claim(&username, TokenType::REFRESH)?;
+    let access_expires_at = claim_expiry(&access_claim)?;
+    let refresh_expires_at = claim_expiry(&refresh_claim)?;
+    let tokens = UserTokenResponse {
+        access: generate_token(access_claim)?,
+        refresh: generate_token(refresh_claim)?,
+    };
+    match persist_rotated_refresh_token(
+        pg_pool,
+        &refresh,
+        &tokens,
+        access_expires_at,
+        refresh_expires_at,
+    )
+    .await?
+    {
+        Some(tokens) => {
+            extend_session(pg_pool, &refresh.session_id, refresh_expires_at).await?;
+            record_audit_event(
+                pg_pool,
+                NewAuditEvent::new(AuditEventType::TOKEN_REFRESHED, AuditOutcome::SUCCESS)
+                    .actor(refresh.user_id)
+                    .context(context)
+                    .metadata(json!({"session_id": refresh.session_id})),
+            )
+            .await;
+            Ok(tokens)
+        }
+        None => {
+            let revoked = revoke_session_tokens(pg_pool, &refresh.session_id).await?;
+            warn!(
+                "refresh token reuse detected for user {}, {} tokens of family {} revoked",
+                refresh.user_id, revoked, refresh.session_id
+            );
+            record_audit_event(
+                pg_pool,
+                NewAuditEvent::new(AuditEventType::REFRESH_TOKEN_REUSED, AuditOutcome::FAILURE)
+                    .actor(refresh.user_id)
+                    .context(context)
+                    .metadata(json!({"session_id": refresh.session_id, "revoked_tokens": revoked})),
+            )
+            .await;
+            Err(ApplicationError::new(
+                "JWT Token error",
+                "JWT Token reuse detected",
+            ))
+        }
+    }
 }
 
 ///# Verify JWT Token
@@ -60,21 +208,11 @@ pub async fn verify_token(
     token_type: TokenType,
     pool: &PgPool,
 ) -> Result<String, ApplicationError> {
-    //prepare validation
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.validate_exp = true;
-
-    //validate token
-    let token_data = decode::<Claim>(
-        token,
-        &DecodingKey::from_secret(env::var("JWT_SECRET")?.as_bytes()),
-        &validation,
-    )?;
-
-    let claim = token_data.claims;
+    //validate signature and exp against the key named in the header
+    let claim = jwt_key_set()?.decode::<Claim>(token)?.claims;
 
     //fetch token
-    let token = get_token_by_token(token, pool).await?;
+    let saved_token = get_token_by_token(token, pool).await?;
 
     //check the type
     if claim.token_type != token_type {
@@ -85,7 +223,7 @@ pub async fn verify_token(
     }
 
     //expiration
-    if token.is_expired.unwrap_or_default() {
+    if saved_token.is_expired.unwrap_or_default() {
         return Err(ApplicationError::new(
             "JWT Token error",
             "JWT Token is expired",
@@ -93,44 +231,65 @@ pub async fn verify_token(
     }
 
     //revoked
-    if token.is_revoked.unwrap_or_default() {
+    if saved_token.is_revoked.unwrap_or_default() {
         return Err(ApplicationError::new(
             "JWT Token error",
             "JWT Token is revoked",
         ));
     }
 
+    touch_session(pool, &saved_token.session_id).await?;
+
     //if everything is fine, then the token is valid return the token
-    Ok(token.token.unwrap_or_default())
+    Ok(token.to_owned())
 }
 
+///lifetime of a token type from the session policy
+pub fn token_lifetime(token_type: &TokenType) -> Result<Duration, ApplicationError> {
+    let policy = session_policy()?;
+    Ok(match token_type {
+        TokenType::ACCESS => policy.access_lifetime,
+        _ => policy.refresh_lifetime,
+    })
+}
 
+///the exp claim as a timestamp, stored with the token row
+pub fn claim_expiry(claim: &Claim) -> Result<OffsetDateTime, ApplicationError> {
+    Ok(OffsetDateTime::from_unix_timestamp(claim.exp as i64)?)
+}
 
 pub fn get_token_claim(username: &str, token_type: TokenType) -> Result<Claim, ApplicationError> {
     Ok(Claim {
         sub: username.to_owned(),
-        exp: (Utc::now() + Duration::milliseconds(
-            env::var(
-                if token_type == TokenType::ACCESS {"JWT_ACCESS_EXPIRATION"}
-                   else {"JWT_REFRESH_EXPIRATION"}
-            )?
-                .parse()?))
-            .timestamp() as usize,
-        token_type: if token_type == TokenType::ACCESS {TokenType::ACCESS.into()} else { TokenType::REFRESH.into() },
+        jti: Uuid::new_v4().to_string(),
+        exp: (OffsetDateTime::now_utc() + token_lifetime(&token_type)?).unix_timestamp() as usize,
+        token_type,
+        scope: None,
     })
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claim {
     pub(crate) exp: usize,
+    //unique per token so two tokens issued in the same second never collide
+    #[serde(default)]
+    pub(crate) jti: String,
     pub(crate) sub: String,
     pub(crate) token_type: TokenType,
+    //space separated scopes granted to a service client, absent on user tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) scope: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
 pub enum TokenType {
     ACCESS,
     REFRESH,
+    //signed into magic links, never persisted with the session tokens
+    MAGIC_LINK,
+    //signed into organization invitations
+    INVITATION,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -144,6 +303,131 @@ impl std::fmt::Display for TokenType {
         match self {
             Self::ACCESS => write!(f, "ACCESS"),
             Self::REFRESH => write!(f, "REFRESH"),
+            Self::MAGIC_LINK => write!(f, "MAGIC_LINK"),
+            Self::INVITATION => write!(f, "INVITATION"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::session_policy::SessionPolicy;
+
+    //a verified user with a fresh session, returns its first token pair
+    async fn sign_in(pool: &PgPool) -> UserTokenResponse {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', 'hash', true)",
+            Uuid::new_v4()
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        generate_persisted_user_token("ada@x.io", &SessionContext::default(), pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn refreshing_rotates_the_token_and_marks_its_parent(pool: PgPool) {
+        let tokens = sign_in(&pool).await;
+
+        let rotated = rotate_refresh_token(&tokens.refresh, &SessionContext::default(), &pool)
+            .await
+            .unwrap();
+
+        let parent = get_token_by_token(&tokens.refresh, &pool).await.unwrap();
+        assert!(parent.rotated_at.is_some());
+        for token in [&rotated.access, &rotated.refresh] {
+            let child = get_token_by_token(token, &pool).await.unwrap();
+            assert_eq!(child.parent_id, Some(parent.id));
+            assert_eq!(child.session_id, parent.session_id);
+        }
+        assert!(
+            verify_token(&rotated.access, TokenType::ACCESS, &pool)
+                .await
+                .is_ok()
+        );
+        assert!(
+            verify_token(&rotated.refresh, TokenType::REFRESH, &pool)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[sqlx::test]
+    async fn reusing_a_rotated_token_revokes_its_whole_family(pool: PgPool) {
+        let tokens = sign_in(&pool).await;
+        let context = SessionContext::default();
+        let rotated = rotate_refresh_token(&tokens.refresh, &context, &pool)
+            .await
+            .unwrap();
+
+        let reused = rotate_refresh_token(&tokens.refresh, &context, &pool).await;
+        assert_eq!(
+            reused.err().unwrap().description,
+            "JWT Token reuse detected"
+        );
+
+        //the successor issued to whoever rotated first dies with the family
+        for (token, token_type) in [
+            (&tokens.access, TokenType::ACCESS),
+            (&rotated.access, TokenType::ACCESS),
+            (&rotated.refresh, TokenType::REFRESH),
+        ] {
+            let error = verify_token(token, token_type, &pool).await.unwrap_err();
+            assert_eq!(error.description, "JWT Token is revoked");
+        }
+        assert!(
+            rotate_refresh_token(&rotated.refresh, &context, &pool)
+                .await
+                .is_err()
+        );
+        let session = get_token_by_token(&tokens.refresh, &pool)
+            .await
+            .unwrap()
+            .session_id;
+        let revoked_at = sqlx::query_scalar!(
+            "select revoked_at from user_sessions where id = $1",
+            session
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(revoked_at.is_some());
+    }
+
+    #[sqlx::test]
+    async fn concurrent_refreshes_rotate_the_token_only_once(pool: PgPool) {
+        let tokens = sign_in(&pool).await;
+        let context = SessionContext::default();
+
+        let (first, second) = tokio::join!(
+            rotate_refresh_token(&tokens.refresh, &context, &pool),
+            rotate_refresh_token(&tokens.refresh, &context, &pool)
+        );
+
+        //the loser runs into the `rotated_at is null` guard and is treated as a reuse
+        let (winner, loser) = match (first, second) {
+            (Ok(winner), Err(loser)) | (Err(loser), Ok(winner)) => (winner, loser),
+            (first, second) => panic!("expected one rotation, got {:?} and {:?}", first, second),
+        };
+        assert_eq!(loser.description, "JWT Token reuse detected");
+        let parent = get_token_by_token(&tokens.refresh, &pool).await.unwrap();
+        let children =
+            sqlx::query_scalar!("select count(*) from token where parent_id = $1", parent.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(children, Some(2));
+        assert!(
+            verify_token(&winner.refresh, TokenType::REFRESH, &pool)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::client_ip::ClientIp;
+use crate::users::repositories::login_attempt_repository::{
+    block_ip, delete_stale_ip_login_failures, get_ip_login_failure, lock_user,
+    record_failed_ip_login, record_failed_login, reset_failed_logins, unlock_expired_users,
+};
+use crate::users::services::admin_service::record_admin_event;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::require_role::{Admin, RequireRole};
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use axum::extract::Path;
+use axum::{Extension, Json};
+use log::{error, info, warn};
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+///# Lockout Policy
+///
+/// after `max_attempts` failures the lock lasts `base_duration`, doubling with every further lock up to `max_duration`
+struct LockoutPolicy {
+    max_attempts: i32,
+    base_duration: Duration,
+    max_duration: Duration,
+}
+
+impl LockoutPolicy {
+    fn from_env(
+        max_attempts_env: &str,
+        default_max_attempts: &str,
+    ) -> Result<Self, ApplicationError> {
+        Ok(Self {
+            max_attempts: env::var(max_attempts_env)
+                .unwrap_or(String::from(default_max_attempts))
+                .parse()?,
+            base_duration: Duration::milliseconds(
+                env::var("LOGIN_LOCKOUT_DURATION")
+                    .unwrap_or(String::from("60000"))
+                    .parse()?,
+            ),
+            max_duration: Duration::milliseconds(
+                env::var("LOGIN_LOCKOUT_MAX_DURATION")
+                    .unwrap_or(String::from("86400000"))
+                    .parse()?,
+            ),
+        })
+    }
+
+    fn account() -> Result<Self, ApplicationError> {
+        Self::from_env("LOGIN_MAX_FAILED_ATTEMPTS", "5")
+    }
+
+    fn ip() -> Result<Self, ApplicationError> {
+        Self::from_env("LOGIN_IP_MAX_FAILED_ATTEMPTS", "20")
+    }
+
+    ///lock duration for the next lock given the number of previous locks
+    fn lock_duration(&self, lockout_count: i32) -> Duration {
+        let factor = 2i32.saturating_pow(lockout_count.clamp(0, 30) as u32);
+        (self.base_duration * factor).min(self.max_duration)
+    }
+}
+
+fn seconds_until(until: OffsetDateTime) -> i64 {
+    let milliseconds = (until - OffsetDateTime::now_utc()).whole_milliseconds() as i64;
+    ((milliseconds + 999) / 1000).max(1)
+}
+
+fn account_locked_error(locked_until: Option<OffsetDateTime>) -> ApplicationError {
+    match locked_until {
+        Some(until) => ApplicationError::new(
+            "Account Locked",
+            format!(
+                "Too many failed sign-in attempts, try again in {} seconds.",
+                seconds_until(until)
+            ),
+        ),
+        None => ApplicationError::new("Account Locked", "This account has been locked."),
+    }
+}
+
+///# Check Account Lock
+///
+/// a lock whose time has passed no longer applies, even before the unlock task clears it
+pub fn check_account_lock(user: &User) -> Result<(), ApplicationError> {
+    let locked = !user.is_account_non_locked.unwrap_or(true)
+        && user
+            .locked_until
+            .is_none_or(|until| until > OffsetDateTime::now_utc());
+    match locked {
+        true => Err(account_locked_error(user.locked_until)),
+        false => Ok(()),
+    }
+}
+
+///# Register Failed Login
+///
+/// count a wrong password and lock the account once the limit is reached
+pub async fn register_failed_login(pool: &PgPool, user: &User) -> Result<(), ApplicationError> {
+    let policy = LockoutPolicy::account()?;
+    let (attempts, lockout_count) = record_failed_login(pool, &user.id).await?;
+    if attempts >= policy.max_attempts {
+        let locked_until = OffsetDateTime::now_utc() + policy.lock_duration(lockout_count);
+        lock_user(pool, &user.id, locked_until).await?;
+        warn!(
+            "user {} locked after {} failed sign-in attempts",
+            user.id, attempts
+        );
+        return Err(account_locked_error(Some(locked_until)));
+    }
+    Ok(())
+}
+
+///clear the failure counters once the sign-in is complete, after the second factor if enabled
+pub async fn register_successful_login(pool: &PgPool, user: &User) -> Result<(), ApplicationError> {
+    if user.failed_login_attempts > 0 || user.lockout_count > 0 {
+        reset_failed_logins(pool, &user.id).await?;
+    }
+    Ok(())
+}
+
+///# Check Ip Block
+///
+/// seconds until an address that failed too often may sign in again, None when it is not blocked
+pub async fn check_ip_block(pool: &PgPool, ip: &ClientIp) -> Result<Option<i64>, ApplicationError> {
+    Ok(get_ip_login_failure(pool, &ip.to_string())
+        .await?
+        .and_then(|failure| failure.blocked_until)
+        .filter(|until| *until > OffsetDateTime::now_utc())
+        .map(seconds_until))
+}
+
+pub fn ip_blocked_error(retry_after: i64) -> ApplicationError {
+    ApplicationError::new(
+        "Too Many Attempts",
+        format!(
+            "Too many failed sign-in attempts from this address, try again in {} seconds.",
+            retry_after
+        ),
+    )
+}
+
+///# Register Failed Ip Login
+///
+/// count a failed sign-in from the address and block it once the limit is reached
+///
+/// a successful sign-in doesn't clear the count, otherwise signing in to an account of one's own now
+/// and then would allow guessing the passwords of others forever, the count decays once the address
+/// stays quiet for a day
+pub async fn register_failed_ip_login(
+    pool: &PgPool,
+    ip: &ClientIp,
+) -> Result<(), ApplicationError> {
+    let policy = LockoutPolicy::ip()?;
+    let (attempts, lockout_count) = record_failed_ip_login(pool, &ip.to_string()).await?;
+    if attempts >= policy.max_attempts {
+        let blocked_until = OffsetDateTime::now_utc() + policy.lock_duration(lockout_count);
+        block_ip(pool, &ip.to_string(), blocked_until).await?;
+        warn!(
+            "address {} blocked after {} failed sign-in attempts",
+            ip, attempts
+        );
+    }
+    Ok(())
+}
+
+///# Spawn Account Unlock Task
+///
+/// periodically unlock accounts whose lock has expired and forget stale address failures
+pub fn spawn_account_unlock_task(pool: PgPool) -> Result<(), ApplicationError> {
+    let interval: u64 = env::var("ACCOUNT_UNLOCK_INTERVAL")
+        .unwrap_or(String::from("60000"))
+        .parse()?;
+    //tokio panics on a zero period
+    if interval == 0 {
+        return Err(ApplicationError::new(
+            "Configuration Error",
+            "ACCOUNT_UNLOCK_INTERVAL must be greater than 0",
+        ));
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_millis(interval));
+        loop {
+            ticker.tick().await;
+            match unlock_expired_users(&pool).await {
+                Ok(0) => {}
+                Ok(unlocked) => info!("unlocked {} accounts", unlocked),
+                Err(error) => error!("{:?}", error),
+            }
+            if let Err(error) = delete_stale_ip_login_failures(&pool).await {
+                error!("{:?}", error);
+            }
+        }
+    });
+    Ok(())
+}
+
+///# Unlock Account
+///
+/// admin only, clear the lock and failure counters of an account
+pub async fn unlock_account(
+    RequireRole { user, .. }: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match reset_failed_logins(&state.pool, &id).await {
+        Ok(true) => {
+            info!("user {} unlocked by admin {}", id, user.id);
+            record_admin_event(
+                &state,
+                AuditEventType::ACCOUNT_UNLOCKED,
+                &user,
+                &context,
+                json!({"user_id": id}),
+            )
+            .await;
+            Ok(Json(MessageResponse::new("Account unlocked")))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApplicationError::new("Not Found", "User not found")),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::password_hasher::PasswordHasher;
+    use crate::users::services::authentication_service::login;
+    use crate::users::types::login_request::LoginRequest;
+    use crate::users::types::session_context::SessionContext;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn lock_duration_doubles_up_to_the_maximum() {
+        let policy = LockoutPolicy {
+            max_attempts: 5,
+            base_duration: Duration::minutes(1),
+            max_duration: Duration::minutes(5),
+        };
+        assert_eq!(policy.lock_duration(0), Duration::minutes(1));
+        assert_eq!(policy.lock_duration(2), Duration::minutes(4));
+        assert_eq!(policy.lock_duration(3), Duration::minutes(5));
+        assert_eq!(policy.lock_duration(i32::MAX), Duration::minutes(5));
+    }
+
+    #[sqlx::test]
+    async fn signing_in_to_an_own_account_doesnt_reset_the_address(pool: PgPool) {
+        PasswordHasher::install_for_tests();
+        JwtKeySet::install_for_tests();
+        let password_hash = bcrypt::hash("Quartz-Lamp-58", 4).unwrap();
+        let own = Uuid::new_v4();
+        for (id, email) in [(own, "eve@x.io"), (Uuid::new_v4(), "ada@x.io")] {
+            sqlx::query!(
+                "insert into users(id, name, email, password, email_verified)
+                values ($1, 'Ada', $2, $3, true)",
+                id,
+                email,
+                password_hash
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        //the own account has two factors so a correct password ends in a challenge, not a session
+        sqlx::query!(
+            "insert into user_mfa(user_id, secret, is_enabled) values ($1, 'secret', true)",
+            own
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let (state, _) = AppState::for_tests(pool);
+        let attempt = |email: &str, password: &str| {
+            login(
+                Extension(state.clone()),
+                ClientIp(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7))),
+                SessionContext::default(),
+                Json(LoginRequest {
+                    email: email.to_owned(),
+                    password: password.to_owned(),
+                }),
+            )
+        };
+
+        let max_attempts = LockoutPolicy::ip().unwrap().max_attempts;
+        for _ in 1..max_attempts {
+            assert!(attempt("ada@x.io", "guess").await.is_err());
+        }
+        let own_sign_in = attempt("eve@x.io", "Quartz-Lamp-58").await.unwrap();
+        assert_eq!(own_sign_in.status(), StatusCode::OK);
+        assert!(attempt("ada@x.io", "guess").await.is_err());
+
+        let blocked = attempt("eve@x.io", "Quartz-Lamp-58").await.unwrap();
+        assert_eq!(blocked.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,444 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::security::jwt_key_set::jwt_key_set;
+use crate::application::security::secure_token::hash_secure_token;
+use crate::users::repositories::authentication_repository::save_new_user_and_allocate_a_role;
+use crate::users::repositories::magic_link_repository::{
+    consume_magic_link, persist_magic_link, register_magic_link_attempt,
+};
+use crate::users::repositories::user_repository::{claim_unverified_account, find_user_by_email};
+use crate::users::services::account_status_service::check_account_status;
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::authentication_service::generate_user_session;
+use crate::users::services::jwt_service::{Claim, TokenType, generate_token};
+use crate::users::services::mfa_service::{is_mfa_enabled, start_mfa_challenge};
+use crate::users::services::profile_service::validate_profile_name;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::magic_link::MagicLink;
+use crate::users::types::magic_link_request::MagicLinkRequest;
+use crate::users::types::magic_link_sign_in_request::MagicLinkSignInRequest;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_source::UserSource;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use log::{error, info, warn};
+use rand::Rng;
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+//the code is bound to its link, the same digits of another link hash differently
+fn hash_magic_link_code(id: &Uuid, code: &str) -> String {
+    hash_secure_token(&format!("{}:{}", id, code))
+}
+
+fn max_attempts() -> Result<i32, ApplicationError> {
+    Ok(env::var("MAGIC_LINK_MAX_ATTEMPTS")
+        .unwrap_or(String::from("5"))
+        .parse()?)
+}
+
+///# Send Magic Link
+///
+/// persist a single use link and mail it together with a 6 digit code, both expire after
+/// MAGIC_LINK_EXPIRATION (ms)
+///
+/// unknown emails only get a link when MAGIC_LINK_SIGNUP allows creating the account
+async fn send_magic_link(
+    state: &AppState,
+    request: &MagicLinkRequest,
+) -> Result<(), ApplicationError> {
+    let expiration: i64 = env::var("MAGIC_LINK_EXPIRATION")
+        .unwrap_or(String::from("600000"))
+        .parse()?;
+    let signup: bool = env::var("MAGIC_LINK_SIGNUP")
+        .unwrap_or(String::from("true"))
+        .parse()
+        .map_err(|_| ApplicationError::new("Magic Link Error", "Invalid MAGIC_LINK_SIGNUP"))?;
+    let url =
+        env::var("MAGIC_LINK_URL").unwrap_or(String::from("http://localhost:3000/magic-link"));
+
+    let greeting = match find_user_by_email(&state.pool, &request.email).await? {
+        Some(user) => user.name,
+        None if signup => request.name.clone().unwrap_or(request.email.clone()),
+        None => return Ok(()),
+    };
+
+    let id = Uuid::new_v4();
+    let code = format!("{:06}", rand::rng().random_range(0..1_000_000));
+    let expires_at = OffsetDateTime::now_utc() + Duration::milliseconds(expiration);
+    persist_magic_link(
+        &state.pool,
+        &id,
+        &request.email,
+        request.name.as_deref(),
+        &hash_magic_link_code(&id, &code),
+        expires_at,
+    )
+    .await?;
+
+    let token = generate_token(Claim {
+        exp: expires_at.unix_timestamp() as usize,
+        jti: id.to_string(),
+        sub: request.email.clone(),
+        token_type: TokenType::MAGIC_LINK,
+        scope: None,
+    })?;
+
+    state
+        .mailer
+        .send(MailMessage {
+            to: request.email.clone(),
+            subject: String::from("Your sign-in link"),
+            body: format!(
+                "Hi {greeting},\n\nOpen the link below to sign in, or enter the code {code} on the sign-in page.\n\
+                The link and the code can only be used once and expire in {} minutes.\n\n{url}?token={token}\n\n\
+                If you did not request this, you can ignore this email.\n",
+                expiration / 60_000
+            ),
+        })
+        .await
+}
+
+///# Request Magic Link
+///
+/// mail a sign-in link and code, the response is the same whether the email exists or not
+///
+/// the lookup and the mail happen in the background so the response time doesn't tell either
+pub async fn request_magic_link(
+    Extension(state): Extension<Arc<AppState>>,
+    request: Json<MagicLinkRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    let request = MagicLinkRequest {
+        email: request.0.email.trim().to_owned(),
+        name: match &request.0.name {
+            Some(name) => Some(validate_profile_name(name)?),
+            None => None,
+        },
+    };
+    tokio::spawn(async move {
+        if let Err(error) = send_magic_link(&state, &request).await {
+            error!("{:?}", error);
+        }
+    });
+    Ok(Json(MessageResponse::new(
+        "If sign-in by email is possible for this account, a link has been sent",
+    )))
+}
+
+//the link is signed, so only its signature, type and expiry are checked before it is used up
+async fn consume_link_token(pool: &PgPool, token: &str) -> Result<MagicLink, ApplicationError> {
+    let invalid = || ApplicationError::new("Magic Link Error", "Invalid or expired magic link");
+    let claims = jwt_key_set()?
+        .decode::<Claim>(token)
+        .map_err(|_| invalid())?
+        .claims;
+    if claims.token_type != TokenType::MAGIC_LINK {
+        return Err(invalid());
+    }
+    let id = Uuid::parse_str(&claims.jti).map_err(|_| invalid())?;
+    consume_magic_link(pool, &id, max_attempts()?)
+        .await?
+        .filter(|link| link.email == claims.sub)
+        .ok_or_else(invalid)
+}
+
+//every code entered counts against the email, requesting a new link doesn't start the count over
+async fn consume_link_code(
+    pool: &PgPool,
+    email: &str,
+    code: &str,
+) -> Result<MagicLink, ApplicationError> {
+    let invalid = || ApplicationError::new("Magic Link Error", "Invalid or expired code");
+    let max_attempts = max_attempts()?;
+    let link = match register_magic_link_attempt(pool, email).await? {
+        Some((link, attempts)) if attempts <= i64::from(max_attempts) => link,
+        Some(_) => {
+            return Err(ApplicationError::new(
+                "Magic Link Error",
+                "Too many attempts, please try again later",
+            ));
+        }
+        None => return Err(invalid()),
+    };
+    if hash_magic_link_code(&link.id, code.trim()) != link.code_hash {
+        return Err(invalid());
+    }
+    consume_magic_link(pool, &link.id, max_attempts)
+        .await?
+        .ok_or_else(invalid)
+}
+
+///# Confirm With Magic Link Code
+///
+/// use up the pending code of the email to confirm a sensitive change of a passwordless account
+pub async fn confirm_with_magic_link_code(
+    pool: &PgPool,
+    email: &str,
+    code: &str,
+) -> Result<(), ApplicationError> {
+    consume_link_code(pool, email, code).await.map(|_| ())
+}
+
+///# Find Or Create Magic Link User
+///
+/// the link proves the email, a missing account is created without a password
+///
+/// an unverified account may have been registered by someone else, its password and sessions are dropped
+async fn find_or_create_magic_link_user(
+    pool: &PgPool,
+    link: &MagicLink,
+) -> Result<User, ApplicationError> {
+    if let Some(user) = find_user_by_email(pool, &link.email).await? {
+        if user.email_verified || !claim_unverified_account(pool, &user.id).await? {
+            return Ok(user);
+        }
+        warn!(
+            "unverified user {} claimed by a magic link sign-in, password and sessions dropped",
+            user.id
+        );
+        return Ok(User {
+            password: None,
+            email_verified: true,
+            ..user
+        });
+    }
+
+    let user = User {
+        id: Uuid::new_v4(),
+        name: link.name.clone().unwrap_or(link.email.clone()),
+        email: link.email.clone(),
+        is_enabled: Some(true),
+        is_account_non_expired: Some(true),
+        is_account_non_locked: Some(true),
+        password: None,
+        image_url: None,
+        created_at: None,
+        updated_at: None,
+        source: UserSource::SYSTEM,
+        failed_login_attempts: 0,
+        lockout_count: 0,
+        locked_until: None,
+        account_expires_at: None,
+        avatar_id: None,
+        deletion_scheduled_at: None,
+        email_verified: true,
+    };
+    save_new_user_and_allocate_a_role(pool, &user).await?;
+    info!("created passwordless user {}", user.id);
+    Ok(user)
+}
+
+///# Sign In With Magic Link
+///
+/// trade the token of the link, or the email and code, for the session
+///
+/// accounts with two-factor authentication get an mfa challenge as with a password sign-in
+pub async fn sign_in_with_magic_link(
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    request: Json<MagicLinkSignInRequest>,
+) -> Result<Response, (StatusCode, Json<ApplicationError>)> {
+    let link = match (&request.0.token, &request.0.email, &request.0.code) {
+        (Some(token), _, _) => consume_link_token(&state.pool, token).await,
+        (None, Some(email), Some(code)) => consume_link_code(&state.pool, email.trim(), code).await,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApplicationError::new(
+                    "Magic Link Error",
+                    "Provide the token of the link, or the email and code",
+                )),
+            ));
+        }
+    };
+
+    let audit = NewAuditEvent::new(AuditEventType::LOGIN, AuditOutcome::FAILURE).context(&context);
+    let user = match link {
+        Ok(link) => find_or_create_magic_link_user(&state.pool, &link).await,
+        Err(error) => Err(error),
+    };
+    let user = match user {
+        Ok(user) => match check_account_status(&user) {
+            Ok(()) => user,
+            Err(error) => {
+                record_audit_event(
+                    &state.pool,
+                    audit
+                        .actor(user.id)
+                        .metadata(json!({"method": "magic_link", "reason": error.error})),
+                )
+                .await;
+                return Err((StatusCode::UNAUTHORIZED, Json(error)));
+            }
+        },
+        Err(error) => {
+            error!("{:?}", error);
+            let status = match error.error.as_str() {
+                "Magic Link Error" => StatusCode::UNAUTHORIZED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            record_audit_event(
+                &state.pool,
+                audit.metadata(json!({"method": "magic_link", "reason": error.error})),
+            )
+            .await;
+            return Err((status, Json(error)));
+        }
+    };
+
+    let audit = NewAuditEvent {
+        outcome: AuditOutcome::SUCCESS,
+        ..audit
+    }
+    .actor(user.id);
+    let result = match is_mfa_enabled(&state.pool, &user).await {
+        //LOGIN is only recorded once the second factor is verified
+        Ok(true) => start_mfa_challenge(&state.pool, &user)
+            .await
+            .map(|challenge| {
+                (
+                    NewAuditEvent {
+                        event_type: AuditEventType::MFA_CHALLENGE,
+                        ..audit
+                    }
+                    .metadata(json!({"method": "magic_link"})),
+                    Json(challenge).into_response(),
+                )
+            }),
+        Ok(false) => generate_user_session(&user.email, &context, &state.pool)
+            .await
+            .map(|result| {
+                (
+                    audit.metadata(json!({"method": "magic_link"})),
+                    Json(result.session).into_response(),
+                )
+            }),
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok((event, response)) => {
+            record_audit_event(&state.pool, event).await;
+            Ok(response)
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::repositories::user_repository::get_user_by_id;
+    use crate::users::services::profile_service::verify_current_password;
+
+    async fn pending_link(pool: &PgPool, email: &str, code: &str) -> MagicLink {
+        let id = Uuid::new_v4();
+        persist_magic_link(
+            pool,
+            &id,
+            email,
+            None,
+            &hash_magic_link_code(&id, code),
+            OffsetDateTime::now_utc() + Duration::minutes(10),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn attempts_count_against_the_email_across_links(pool: PgPool) {
+        pending_link(&pool, "ada@x.io", "111111").await;
+        for _ in 0..3 {
+            assert!(
+                consume_link_code(&pool, "ada@x.io", "000000")
+                    .await
+                    .is_err()
+            );
+        }
+        pending_link(&pool, "ada@x.io", "222222").await;
+        assert!(
+            consume_link_code(&pool, "ada@x.io", "111111")
+                .await
+                .is_err()
+        );
+        assert!(
+            consume_link_code(&pool, "ada@x.io", "000000")
+                .await
+                .is_err()
+        );
+
+        let error = consume_link_code(&pool, "ada@x.io", "222222")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.description,
+            "Too many attempts, please try again later"
+        );
+    }
+
+    #[sqlx::test]
+    async fn an_unverified_account_is_claimed(pool: PgPool) {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password) values ($1, 'Ada', 'ada@x.io', 'hash')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let link = pending_link(&pool, "ada@x.io", "123456").await;
+
+        let user = find_or_create_magic_link_user(&pool, &link).await.unwrap();
+        assert!(user.password.is_none() && user.email_verified);
+        let stored = get_user_by_id(&pool, &id).await.unwrap();
+        assert!(stored.password.is_none() && stored.email_verified);
+    }
+
+    #[sqlx::test]
+    async fn a_passwordless_account_confirms_with_a_fresh_code(pool: PgPool) {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email) values ($1, 'Ada', 'ada@x.io')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let user = get_user_by_id(&pool, &id).await.unwrap();
+
+        assert!(
+            verify_current_password(&pool, &user, "", None)
+                .await
+                .is_err()
+        );
+        pending_link(&pool, "ada@x.io", "123456").await;
+        assert!(
+            verify_current_password(&pool, &user, "", Some("654321"))
+                .await
+                .is_err()
+        );
+        assert!(
+            verify_current_password(&pool, &user, "", Some("123456"))
+                .await
+                .is_ok()
+        );
+        //the code is used up
+        assert!(
+            verify_current_password(&pool, &user, "", Some("123456"))
+                .await
+                .is_err()
+        );
+    }
+}
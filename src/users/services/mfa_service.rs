@@ -0,0 +1,517 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::secure_token::{
+    generate_secure_token, hash_secure_token, secure_tokens_equal,
+};
+use crate::users::repositories::mfa_repository::{
+    consume_mfa_challenge, consume_recovery_code, delete_user_mfa, enable_mfa, get_user_mfa,
+    persist_mfa_challenge, persist_pending_mfa_secret, record_used_mfa_step,
+    register_mfa_challenge_attempt,
+};
+use crate::users::repositories::user_repository::get_user_by_id;
+use crate::users::services::account_status_service::{
+    check_account_status, is_account_status_error,
+};
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::authentication_service::generate_user_session;
+use crate::users::services::login_attempt_service::{
+    register_failed_login, register_successful_login,
+};
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::login_response::LoginResponse;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::mfa_challenge_response::MfaChallengeResponse;
+use crate::users::types::mfa_code_request::MfaCodeRequest;
+use crate::users::types::mfa_enrollment_response::MfaEnrollmentResponse;
+use crate::users::types::mfa_verify_request::MfaVerifyRequest;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::recovery_codes_response::RecoveryCodesResponse;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_mfa::UserMfa;
+use axum::{Extension, Json};
+use log::{error, info, warn};
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+fn build_totp(secret: &str, account_name: &str) -> Result<TOTP, ApplicationError> {
+    Ok(TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        1,
+        TOTP_STEP,
+        Secret::Encoded(secret.to_owned()).to_bytes()?,
+        Some(env::var("MFA_ISSUER").unwrap_or(String::from("Video Intelligence"))),
+        account_name.to_owned(),
+    )?)
+}
+
+///# Matching Totp Step
+///
+/// the RFC 6238 time step the code belongs to, one step of clock drift is tolerated either way
+///
+/// the time is passed in, handlers read it from the clock in the app state so codes can be checked
+/// against a fixed clock
+pub fn matching_totp_step(totp: &TOTP, code: &str, unix_time: u64) -> Option<i64> {
+    let current = unix_time / TOTP_STEP;
+    (current.saturating_sub(1)..=current + 1)
+        .find(|step| secure_tokens_equal(&totp.generate(step * TOTP_STEP), code))
+        .map(|step| step as i64)
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 5];
+    rand::rng().fill_bytes(&mut bytes);
+    let code = hex::encode(bytes);
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    hash_secure_token(&code.trim().to_lowercase())
+}
+
+///# Verify Mfa Code
+///
+/// accept a TOTP code that was not used before or an unused recovery code
+pub async fn verify_mfa_code(
+    pool: &PgPool,
+    user: &User,
+    user_mfa: &UserMfa,
+    code: &str,
+    unix_time: u64,
+) -> Result<bool, ApplicationError> {
+    let code = code.trim();
+    if is_totp_code(code) {
+        let totp = build_totp(&user_mfa.secret, &user.email)?;
+        return match matching_totp_step(&totp, code, unix_time) {
+            Some(step) => record_used_mfa_step(pool, &user.id, step).await,
+            None => Ok(false),
+        };
+    }
+    if user_mfa.is_enabled
+        && consume_recovery_code(pool, &user.id, &hash_recovery_code(code)).await?
+    {
+        warn!("recovery code used by user {}", user.id);
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+pub async fn is_mfa_enabled(pool: &PgPool, user: &User) -> Result<bool, ApplicationError> {
+    Ok(get_user_mfa(pool, &user.id)
+        .await?
+        .is_some_and(|user_mfa| user_mfa.is_enabled))
+}
+
+///# Start Mfa Challenge
+///
+/// short lived single use token standing in for the session until the second factor is verified
+pub async fn start_mfa_challenge(
+    pool: &PgPool,
+    user: &User,
+) -> Result<MfaChallengeResponse, ApplicationError> {
+    let expiration: i64 = env::var("MFA_CHALLENGE_EXPIRATION")
+        .unwrap_or(String::from("300000"))
+        .parse()?;
+    let token = generate_secure_token();
+    persist_mfa_challenge(
+        pool,
+        &user.id,
+        &hash_secure_token(&token),
+        OffsetDateTime::now_utc() + Duration::milliseconds(expiration),
+    )
+    .await?;
+    Ok(MfaChallengeResponse {
+        mfa_required: true,
+        mfa_token: token,
+        expires_in: expiration / 1000,
+    })
+}
+
+fn mfa_error(description: &str) -> (StatusCode, Json<ApplicationError>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ApplicationError::new("MFA Error", description)),
+    )
+}
+
+fn internal_error(error: ApplicationError) -> (StatusCode, Json<ApplicationError>) {
+    error!("{:?}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(error))
+}
+
+///# Enroll Mfa
+///
+/// create a new TOTP secret, it only takes effect once confirmed with a code
+pub async fn enroll_mfa(
+    user: User,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<MfaEnrollmentResponse>, (StatusCode, Json<ApplicationError>)> {
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = build_totp(&secret, &user.email).map_err(internal_error)?;
+
+    match persist_pending_mfa_secret(&state.pool, &user.id, &secret).await {
+        Ok(_) => Ok(Json(MfaEnrollmentResponse {
+            secret,
+            otpauth_uri: totp.get_url(),
+        })),
+        Err(error) => Err((StatusCode::CONFLICT, Json(error))),
+    }
+}
+
+///# Confirm Mfa
+///
+/// enable two-factor authentication with a code from the authenticator
+///
+/// the recovery codes are only ever returned here
+pub async fn confirm_mfa(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    request: Json<MfaCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, (StatusCode, Json<ApplicationError>)> {
+    let user_mfa = match get_user_mfa(&state.pool, &user.id).await {
+        Ok(Some(user_mfa)) if !user_mfa.is_enabled => user_mfa,
+        Ok(_) => return Err(mfa_error("No pending two-factor enrolment")),
+        Err(error) => return Err(internal_error(error)),
+    };
+
+    match verify_mfa_code(
+        &state.pool,
+        &user,
+        &user_mfa,
+        &request.0.code,
+        state.clock.unix_time(),
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(mfa_error("Invalid code")),
+        Err(error) => return Err(internal_error(error)),
+    }
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_recovery_code(code))
+        .collect();
+    enable_mfa(&state.pool, &user.id, &hashes)
+        .await
+        .map_err(internal_error)?;
+
+    info!("two-factor authentication enabled for user {}", user.id);
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+///# Disable Mfa
+///
+/// turn two-factor authentication off, a current code or a recovery code is required
+pub async fn disable_mfa(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    request: Json<MfaCodeRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    let user_mfa = match get_user_mfa(&state.pool, &user.id).await {
+        Ok(Some(user_mfa)) if user_mfa.is_enabled => user_mfa,
+        Ok(_) => return Err(mfa_error("Two-factor authentication is not enabled")),
+        Err(error) => return Err(internal_error(error)),
+    };
+
+    match verify_mfa_code(
+        &state.pool,
+        &user,
+        &user_mfa,
+        &request.0.code,
+        state.clock.unix_time(),
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(mfa_error("Invalid code")),
+        Err(error) => return Err(internal_error(error)),
+    }
+
+    delete_user_mfa(&state.pool, &user.id)
+        .await
+        .map_err(internal_error)?;
+    info!("two-factor authentication disabled for user {}", user.id);
+    Ok(Json(MessageResponse::new(
+        "Two-factor authentication disabled",
+    )))
+}
+
+///# Verify Mfa
+///
+/// second sign-in step, trade the mfa token and a code for the session
+///
+/// a challenge allows a limited number of attempts
+pub async fn verify_mfa(
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    request: Json<MfaVerifyRequest>,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ApplicationError>)> {
+    let max_attempts: i32 = env::var("MFA_MAX_ATTEMPTS")
+        .unwrap_or(String::from("5"))
+        .parse()
+        .map_err(|error| internal_error(ApplicationError::from(error)))?;
+    let unauthorized = |description: &str| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ApplicationError::new("MFA Error", description)),
+        )
+    };
+
+    let challenge =
+        match register_mfa_challenge_attempt(&state.pool, &hash_secure_token(&request.0.mfa_token))
+            .await
+        {
+            Ok(Some(challenge)) if challenge.attempts <= max_attempts => challenge,
+            Ok(Some(_)) => return Err(unauthorized("Too many attempts, please sign in again")),
+            Ok(None) => return Err(unauthorized("Invalid or expired mfa token")),
+            Err(error) => return Err(internal_error(error)),
+        };
+
+    let user = get_user_by_id(&state.pool, &challenge.user_id)
+        .await
+        .map_err(internal_error)?;
+    check_account_status(&user).map_err(|error| (StatusCode::UNAUTHORIZED, Json(error)))?;
+
+    let user_mfa = match get_user_mfa(&state.pool, &user.id).await {
+        Ok(Some(user_mfa)) if user_mfa.is_enabled => user_mfa,
+        Ok(_) => return Err(unauthorized("Two-factor authentication is not enabled")),
+        Err(error) => return Err(internal_error(error)),
+    };
+
+    let audit = NewAuditEvent::new(AuditEventType::MFA_VERIFIED, AuditOutcome::FAILURE)
+        .actor(user.id)
+        .context(&context);
+    match verify_mfa_code(
+        &state.pool,
+        &user,
+        &user_mfa,
+        &request.0.code,
+        state.clock.unix_time(),
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            record_audit_event(&state.pool, audit).await;
+            //a wrong code counts like a wrong password, the account locks after too many
+            return match register_failed_login(&state.pool, &user).await {
+                Ok(()) => Err(unauthorized("Invalid code")),
+                Err(error) if is_account_status_error(&error) => {
+                    Err((StatusCode::UNAUTHORIZED, Json(error)))
+                }
+                Err(error) => Err(internal_error(error)),
+            };
+        }
+        Err(error) => return Err(internal_error(error)),
+    }
+
+    match consume_mfa_challenge(&state.pool, &challenge.id).await {
+        Ok(true) => {}
+        Ok(false) => return Err(unauthorized("Invalid or expired mfa token")),
+        Err(error) => return Err(internal_error(error)),
+    }
+
+    match generate_user_session(&user.email, &context, &state.pool).await {
+        Ok(result) => {
+            if let Err(error) = register_successful_login(&state.pool, &user).await {
+                error!("{:?}", error);
+            }
+            let login = NewAuditEvent::new(AuditEventType::LOGIN, AuditOutcome::SUCCESS)
+                .actor(user.id)
+                .context(&context)
+                .metadata(json!({"mfa": true}));
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent {
+                    outcome: AuditOutcome::SUCCESS,
+                    ..audit
+                },
+            )
+            .await;
+            //the sign-in is complete only now
+            record_audit_event(&state.pool, login).await;
+            Ok(Json(result.session))
+        }
+        Err(error) => Err(internal_error(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::password_hasher::PasswordHasher;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::application::time::clock::FixedClock;
+    use crate::users::services::authentication_service::authenticate_user;
+    use crate::users::types::authentication_outcome::AuthenticationOutcome;
+    use crate::users::types::login_request::LoginRequest;
+    use uuid::Uuid;
+
+    //the RFC 6238 SHA1 seed "12345678901234567890" in base32
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn rfc_totp() -> TOTP {
+        build_totp(RFC_SECRET, "ada@x.io").unwrap()
+    }
+
+    #[test]
+    fn codes_match_the_rfc_6238_vectors() {
+        //the last six digits of the eight digit SHA1 vectors from appendix B
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        let totp = rfc_totp();
+        for (unix_time, code) in vectors {
+            assert_eq!(totp.generate(unix_time), code);
+            assert_eq!(
+                matching_totp_step(&totp, code, unix_time),
+                Some((unix_time / TOTP_STEP) as i64)
+            );
+        }
+    }
+
+    #[test]
+    fn one_step_of_drift_is_tolerated() {
+        let totp = rfc_totp();
+        let step = 1234567890 / TOTP_STEP;
+        assert_eq!(
+            matching_totp_step(&totp, "005924", 1234567890 + TOTP_STEP),
+            Some(step as i64)
+        );
+        assert_eq!(
+            matching_totp_step(&totp, "005924", 1234567890 - TOTP_STEP),
+            Some(step as i64)
+        );
+        assert_eq!(
+            matching_totp_step(&totp, "005924", 1234567890 + 2 * TOTP_STEP),
+            None
+        );
+        assert_eq!(matching_totp_step(&totp, "00592", 1234567890), None);
+    }
+
+    #[sqlx::test]
+    async fn a_code_is_accepted_once_at_the_state_clock(pool: PgPool) {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email) values ($1, 'Ada', 'ada@x.io')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1234567890).unwrap();
+        let (state, _) = AppState::for_tests_with_clock(pool, Arc::new(FixedClock(now)));
+        let user = || async { get_user_by_id(&state.pool, &id).await.unwrap() };
+        persist_pending_mfa_secret(&state.pool, &id, RFC_SECRET)
+            .await
+            .unwrap();
+        let request = || {
+            Json(MfaCodeRequest {
+                code: String::from("005924"),
+            })
+        };
+
+        assert!(
+            confirm_mfa(user().await, Extension(state.clone()), request())
+                .await
+                .is_ok()
+        );
+        //replaying the code that enabled mfa must not turn it off again
+        let (status, error) = disable_mfa(user().await, Extension(state.clone()), request())
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.0.description, "Invalid code");
+    }
+
+    #[sqlx::test]
+    async fn wrong_codes_after_correct_passwords_still_lock_the_account(pool: PgPool) {
+        PasswordHasher::install_for_tests();
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', $2, true)",
+            id,
+            bcrypt::hash("Quartz-Lamp-58", 4).unwrap()
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "insert into user_mfa(user_id, secret, is_enabled) values ($1, $2, true)",
+            id,
+            RFC_SECRET
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let (state, _) = AppState::for_tests(pool);
+        let context = SessionContext::default();
+        let sign_in = || {
+            authenticate_user(
+                None,
+                Some(LoginRequest {
+                    email: String::from("ada@x.io"),
+                    password: String::from("Quartz-Lamp-58"),
+                }),
+                &context,
+                &state.pool,
+            )
+        };
+
+        //a fresh challenge for every guess, the correct password must not clear the failures
+        let mut last_error = None;
+        for _ in 0..5 {
+            let Ok(AuthenticationOutcome::MfaRequired { challenge, .. }) = sign_in().await else {
+                panic!("expected an mfa challenge");
+            };
+            let request = MfaVerifyRequest {
+                mfa_token: challenge.mfa_token,
+                code: String::from("wrong-code"),
+            };
+            let (status, Json(error)) = verify_mfa(
+                Extension(state.clone()),
+                SessionContext::default(),
+                Json(request),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            last_error = Some(error.error);
+        }
+
+        assert_eq!(last_error.as_deref(), Some("Account Locked"));
+        let Err(error) = sign_in().await else {
+            panic!("a locked account must not get a challenge");
+        };
+        assert_eq!(error.error, "Account Locked");
+    }
+}
@@ -1,3 +1,35 @@
 pub mod authentication_service;
 
 pub mod jwt_service;
+
+pub mod verification_service;
+
+pub mod password_reset_service;
+
+pub mod google_authentication_service;
+pub mod google_oidc_service;
+
+pub mod login_attempt_service;
+
+pub mod account_status_service;
+pub mod admin_service;
+
+pub mod mfa_service;
+
+pub mod api_key_service;
+
+pub mod oauth_client_service;
+
+pub mod avatar_service;
+pub mod profile_service;
+pub mod session_service;
+
+pub mod token_purge_service;
+
+pub mod account_deletion_service;
+pub mod account_export_service;
+pub mod audit_service;
+
+pub mod password_policy_service;
+
+pub mod magic_link_service;
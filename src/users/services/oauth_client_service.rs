@@ -0,0 +1,565 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::password_hasher::hash_password;
+use crate::application::security::secure_token::{
+    generate_secure_token, hash_secure_token, secure_tokens_equal,
+};
+use crate::users::repositories::oauth_client_repository::{
+    get_active_oauth_client, get_oauth_clients, revoke_oauth_client,
+    save_oauth_client_with_service_user,
+};
+use crate::users::repositories::user_repository::get_user_by_id;
+use crate::users::services::account_status_service::check_account_status;
+use crate::users::services::api_key_service::{validate_name, validate_scopes};
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::jwt_service::generate_persisted_client_token;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::created_oauth_client_response::CreatedOAuthClientResponse;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::oauth_client::OAuthClient;
+use crate::users::types::oauth_client_request::OAuthClientRequest;
+use crate::users::types::oauth_client_response::OAuthClientResponse;
+use crate::users::types::oauth_error_response::OAuthErrorResponse;
+use crate::users::types::oauth_token_request::OAuthTokenRequest;
+use crate::users::types::oauth_token_response::OAuthTokenResponse;
+use crate::users::types::require_role::{Admin, RequireRole};
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use crate::users::types::user_source::UserSource;
+use axum::extract::Path;
+use axum::http::{HeaderMap, header};
+use axum::{Extension, Form, Json};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use log::{error, info, warn};
+use percent_encoding::percent_decode_str;
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+const CLIENT_ID_PREFIX: &str = "vic_";
+
+type OAuthError = (StatusCode, Json<OAuthErrorResponse>);
+
+fn oauth_error(status: StatusCode, error: &str, description: &str) -> OAuthError {
+    (status, Json(OAuthErrorResponse::new(error, description)))
+}
+
+fn invalid_client() -> OAuthError {
+    oauth_error(
+        StatusCode::UNAUTHORIZED,
+        "invalid_client",
+        "Client authentication failed",
+    )
+}
+
+fn server_error(error: ApplicationError) -> OAuthError {
+    error!("{:?}", error);
+    oauth_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "server_error",
+        "Cannot issue token please try again",
+    )
+}
+
+///credentials of an `Authorization: Basic` header
+///
+/// RFC 6749 form-urlencodes the id and the secret before they are joined with `:`
+fn extract_basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded = headers
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (client_id, client_secret) = decoded.split_once(':')?;
+    Some((form_decode(client_id)?, form_decode(client_secret)?))
+}
+
+fn form_decode(value: &str) -> Option<String> {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .ok()
+        .map(|value| value.into_owned())
+}
+
+///# Client Credentials
+///
+/// the client authenticates either with HTTP Basic or with the form body, never both
+fn client_credentials(
+    headers: &HeaderMap,
+    request: &OAuthTokenRequest,
+) -> Result<(String, String), OAuthError> {
+    let basic = extract_basic_credentials(headers);
+    let body = match (&request.client_id, &request.client_secret) {
+        (Some(client_id), Some(client_secret)) => Some((client_id.clone(), client_secret.clone())),
+        _ => None,
+    };
+    match (basic, body) {
+        (Some(_), Some(_)) => Err(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "Use a single client authentication method",
+        )),
+        (Some(credentials), None) | (None, Some(credentials)) => Ok(credentials),
+        (None, None) => Err(invalid_client()),
+    }
+}
+
+///# Granted Scopes
+///
+/// the requested scopes must all be allowed for the client, no request means every allowed scope
+fn granted_scopes(
+    client: &OAuthClient,
+    requested: Option<&str>,
+) -> Result<Vec<String>, OAuthError> {
+    let Some(requested) = requested.filter(|scope| !scope.trim().is_empty()) else {
+        return Ok(client.scopes.clone());
+    };
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in requested.split_whitespace() {
+        if !client.scopes.iter().any(|allowed| allowed == scope) {
+            return Err(oauth_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_scope",
+                &format!("Scope {} is not allowed for this client", scope),
+            ));
+        }
+        if !scopes.iter().any(|granted| granted == scope) {
+            scopes.push(scope.to_owned());
+        }
+    }
+    Ok(scopes)
+}
+
+///# Token
+///
+/// RFC 6749 token endpoint, only the `client_credentials` grant is supported
+///
+/// the token is a regular access token of the client's service user with the granted scopes
+pub async fn token(
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    headers: HeaderMap,
+    Form(request): Form<OAuthTokenRequest>,
+) -> Result<
+    (
+        [(header::HeaderName, &'static str); 1],
+        Json<OAuthTokenResponse>,
+    ),
+    OAuthError,
+> {
+    if request.grant_type != "client_credentials" {
+        return Err(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "Only client_credentials is supported",
+        ));
+    }
+
+    let (client_id, client_secret) = client_credentials(&headers, &request)?;
+    let audit = NewAuditEvent::new(AuditEventType::CLIENT_TOKEN_ISSUED, AuditOutcome::FAILURE)
+        .context(&context);
+    let client = match get_active_oauth_client(&state.pool, &client_id).await {
+        Ok(Some(client))
+            if secure_tokens_equal(
+                &client.client_secret_hash,
+                &hash_secure_token(&client_secret),
+            ) =>
+        {
+            client
+        }
+        Ok(_) => {
+            warn!("failed client authentication for {}", client_id);
+            record_audit_event(
+                &state.pool,
+                audit.metadata(json!({"client_id": client_id, "reason": "invalid_client"})),
+            )
+            .await;
+            return Err(invalid_client());
+        }
+        Err(error) => return Err(server_error(error)),
+    };
+    let scopes = granted_scopes(&client, request.scope.as_deref())?;
+
+    let user = get_user_by_id(&state.pool, &client.user_id)
+        .await
+        .map_err(server_error)?;
+    let audit = audit.actor(user.id);
+    if let Err(error) = check_account_status(&user) {
+        warn!("client {} refused: {}", client.client_id, error);
+        record_audit_event(
+            &state.pool,
+            audit.metadata(json!({"client_id": client.client_id, "reason": error.error})),
+        )
+        .await;
+        return Err(invalid_client());
+    }
+
+    let (access_token, expires_at) = generate_persisted_client_token(&user, &scopes, &state.pool)
+        .await
+        .map_err(server_error)?;
+    //reported from the exp of the issued token so both always agree
+    let expires_in = (expires_at - OffsetDateTime::now_utc())
+        .whole_seconds()
+        .max(0);
+
+    info!("token issued to client {}", client.client_id);
+    record_audit_event(
+        &state.pool,
+        NewAuditEvent {
+            outcome: AuditOutcome::SUCCESS,
+            ..audit
+        }
+        .metadata(json!({"client_id": client.client_id, "scope": scopes.join(" ")})),
+    )
+    .await;
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(OAuthTokenResponse {
+            access_token,
+            token_type: String::from("Bearer"),
+            expires_in,
+            scope: scopes.join(" "),
+        }),
+    ))
+}
+
+///# Register OAuth Client
+///
+/// admin only, the client gets its own service user holding the APPLICATION role
+///
+/// the secret is returned once, only its digest is stored
+pub async fn register_oauth_client(
+    RequireRole { user, .. }: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    request: Json<OAuthClientRequest>,
+) -> Result<(StatusCode, Json<CreatedOAuthClientResponse>), (StatusCode, Json<ApplicationError>)> {
+    let request = request.0;
+    let name = validate_name(&request.name)?;
+    let scopes = validate_scopes(request.scopes)?;
+
+    let mut id_bytes = [0u8; 8];
+    rand::rng().fill_bytes(&mut id_bytes);
+    let client_id = format!("{}{}", CLIENT_ID_PREFIX, hex::encode(id_bytes));
+    let client_secret = generate_secure_token();
+    //service users never sign in with a password, they get an unusable random one
+    let password = match hash_password(&generate_secure_token()).await {
+        Ok(password) => password,
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    //tokens carry the email as subject, the reserved .invalid domain can never receive mail
+    let service_user = User {
+        id: Uuid::new_v4(),
+        name: name.clone(),
+        email: format!("{}@oauth-clients.invalid", client_id),
+        is_enabled: Some(true),
+        is_account_non_expired: Some(true),
+        is_account_non_locked: Some(true),
+        password: Some(password),
+        image_url: None,
+        created_at: None,
+        updated_at: None,
+        source: UserSource::CLIENT,
+        failed_login_attempts: 0,
+        lockout_count: 0,
+        locked_until: None,
+        account_expires_at: None,
+        avatar_id: None,
+        deletion_scheduled_at: None,
+        //a service user has no mailbox to verify
+        email_verified: true,
+    };
+    let client = OAuthClient {
+        id: Uuid::new_v4(),
+        client_id,
+        client_secret_hash: hash_secure_token(&client_secret),
+        name,
+        scopes,
+        user_id: service_user.id,
+        created_at: None,
+        revoked_at: None,
+    };
+
+    match save_oauth_client_with_service_user(&state.pool, &service_user, &client).await {
+        Ok(client) => {
+            info!(
+                "oauth client {} registered by admin {}",
+                client.client_id, user.id
+            );
+            Ok((
+                StatusCode::CREATED,
+                Json(CreatedOAuthClientResponse {
+                    client_secret,
+                    client: client.into(),
+                }),
+            ))
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+pub async fn list_oauth_clients(
+    _: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<Vec<OAuthClientResponse>>, (StatusCode, Json<ApplicationError>)> {
+    match get_oauth_clients(&state.pool).await {
+        Ok(clients) => Ok(Json(clients.into_iter().map(Into::into).collect())),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Delete OAuth Client
+///
+/// admin only, revoke the client together with the tokens it holds
+pub async fn delete_oauth_client(
+    RequireRole { user, .. }: RequireRole<Admin>,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match revoke_oauth_client(&state.pool, &id).await {
+        Ok(Some(client)) => {
+            info!(
+                "oauth client {} revoked by admin {}",
+                client.client_id, user.id
+            );
+            Ok(Json(MessageResponse::new("OAuth client revoked")))
+        }
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApplicationError::new("Not Found", "OAuth client not found")),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::password_hasher::PasswordHasher;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::repositories::role_repository::grant_role;
+    use crate::users::services::jwt_service::generate_persisted_user_token;
+    use crate::users::types::require_scope::{MembersRead, RequireScope};
+    use crate::users::types::role_type::RoleType;
+    use axum::extract::FromRequestParts;
+    use axum::http::request::Parts;
+    use axum::http::{HeaderValue, Request};
+    use sqlx::PgPool;
+
+    fn bearer_parts(state: &Arc<AppState>, token: &str) -> Parts {
+        let (mut parts, _) = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.extensions.insert(state.clone());
+        parts
+    }
+
+    //register a client as a fresh admin, returns its row id, client id and secret
+    async fn register_client(state: &Arc<AppState>, scopes: &[&str]) -> (Uuid, String, String) {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        PasswordHasher::install_for_tests();
+        let id = Uuid::new_v4();
+        let email = format!("{}@x.io", id);
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', $2, 'hash', true)",
+            id,
+            email
+        )
+        .execute(&state.pool)
+        .await
+        .unwrap();
+        grant_role(&state.pool, &id, RoleType::ADMIN).await.unwrap();
+        let tokens = generate_persisted_user_token(&email, &SessionContext::default(), &state.pool)
+            .await
+            .unwrap();
+        let admin =
+            RequireRole::<Admin>::from_request_parts(&mut bearer_parts(state, &tokens.access), &())
+                .await
+                .ok()
+                .unwrap();
+        let request = OAuthClientRequest {
+            name: String::from("reporting"),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        };
+        let (_, Json(created)) =
+            register_oauth_client(admin, Extension(state.clone()), Json(request))
+                .await
+                .ok()
+                .unwrap();
+        (
+            created.client.id,
+            created.client.client_id,
+            created.client_secret,
+        )
+    }
+
+    async fn request_token(
+        state: &Arc<AppState>,
+        client_id: &str,
+        client_secret: &str,
+        scope: Option<&str>,
+    ) -> Result<OAuthTokenResponse, OAuthError> {
+        let request = OAuthTokenRequest {
+            grant_type: String::from("client_credentials"),
+            client_id: Some(client_id.to_owned()),
+            client_secret: Some(client_secret.to_owned()),
+            scope: scope.map(str::to_owned),
+        };
+        token(
+            Extension(state.clone()),
+            SessionContext::default(),
+            HeaderMap::new(),
+            Form(request),
+        )
+        .await
+        .map(|(_, Json(response))| response)
+    }
+
+    #[sqlx::test]
+    async fn granted_scopes_never_exceed_the_allowed_ones(pool: PgPool) {
+        let (state, _) = AppState::for_tests(pool);
+        let (_, client_id, secret) =
+            register_client(&state, &["members:read", "videos:read"]).await;
+
+        let all = request_token(&state, &client_id, &secret, None)
+            .await
+            .unwrap();
+        assert_eq!(all.scope, "members:read videos:read");
+        let narrowed = request_token(&state, &client_id, &secret, Some("videos:read videos:read"))
+            .await
+            .unwrap();
+        assert_eq!(narrowed.scope, "videos:read");
+
+        let (status, Json(error)) = request_token(
+            &state,
+            &client_id,
+            &secret,
+            Some("videos:read videos:write"),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, "invalid_scope");
+
+        //the narrowed token no longer passes a route asking for the dropped scope
+        let mut parts = bearer_parts(&state, &narrowed.access_token);
+        let (status, _) = RequireScope::<MembersRead>::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let mut parts = bearer_parts(&state, &all.access_token);
+        assert!(
+            RequireScope::<MembersRead>::from_request_parts(&mut parts, &())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[sqlx::test]
+    async fn wrong_secrets_and_revoked_or_disabled_clients_are_refused(pool: PgPool) {
+        let (state, _) = AppState::for_tests(pool);
+        let (revoked, revoked_id, revoked_secret) = register_client(&state, &[]).await;
+        let (_, disabled_id, disabled_secret) = register_client(&state, &[]).await;
+        let (_, client_id, secret) = register_client(&state, &[]).await;
+        sqlx::query!(
+            "update users set is_enabled = false
+            where id = (select user_id from oauth_clients where client_id = $1)",
+            disabled_id
+        )
+        .execute(&state.pool)
+        .await
+        .unwrap();
+        let issued = request_token(&state, &revoked_id, &revoked_secret, None)
+            .await
+            .unwrap();
+        assert!(
+            revoke_oauth_client(&state.pool, &revoked)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        for (client_id, secret) in [
+            (client_id.as_str(), "wrong-secret"),
+            (revoked_id.as_str(), revoked_secret.as_str()),
+            (disabled_id.as_str(), disabled_secret.as_str()),
+        ] {
+            let (status, Json(error)) = request_token(&state, client_id, secret, None)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(error.error, "invalid_client");
+        }
+        assert!(
+            request_token(&state, &client_id, &secret, None)
+                .await
+                .is_ok()
+        );
+
+        //tokens issued before the client was revoked stop working with it
+        let mut parts = bearer_parts(&state, &issued.access_token);
+        let (status, _) = RequireScope::<MembersRead>::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn client_tokens_are_refused_on_user_routes(pool: PgPool) {
+        let (state, _) = AppState::for_tests(pool);
+        let (_, client_id, secret) = register_client(&state, &[]).await;
+        let issued = request_token(&state, &client_id, &secret, None)
+            .await
+            .unwrap();
+
+        let mut parts = bearer_parts(&state, &issued.access_token);
+        let (status, Json(error)) = User::from_request_parts(&mut parts, &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(error.description, "invalid token");
+    }
+
+    #[test]
+    fn basic_credentials_are_form_urldecoded() {
+        let mut headers = HeaderMap::new();
+        let encoded = STANDARD.encode("vic_a%3Ab:s%2Bc+d%25");
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Basic {}", encoded)).unwrap(),
+        );
+        assert_eq!(
+            extract_basic_credentials(&headers),
+            Some((String::from("vic_a:b"), String::from("s+c d%")))
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::password_policy::password_policy;
+use axum::Json;
+use log::error;
+use reqwest::StatusCode;
+
+///# Validate Password
+///
+/// check a new password against the password policy, every broken rule is returned as a field error
+///
+/// `context` holds what is known about the user, such as the name and email
+pub fn validate_password(
+    password: &str,
+    context: &[&str],
+) -> Result<(), (StatusCode, Json<ApplicationError>)> {
+    let policy = match password_policy() {
+        Ok(policy) => policy,
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    let fields = policy.check("password", password, context);
+    if fields.is_empty() {
+        return Ok(());
+    }
+    Err((
+        StatusCode::BAD_REQUEST,
+        Json(
+            ApplicationError::new(
+                "Password Policy Error",
+                "Password does not meet the password policy",
+            )
+            .with_fields(fields),
+        ),
+    ))
+}
@@ -0,0 +1,356 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::security::password_hasher::hash_password;
+use crate::application::security::secure_token::{generate_secure_token, hash_secure_token};
+use crate::users::repositories::password_reset_token_repository::{
+    find_user_by_password_reset_token, persist_password_reset_token, reset_password_with_token,
+};
+use crate::users::repositories::user_repository::get_user_by_email;
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::password_policy_service::validate_password;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::email_request::EmailRequest;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::reset_password_request::ResetPasswordRequest;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::user::User;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+
+///# Send Password Reset Email
+///
+/// generate a single use token, persist its hash and mail the reset link to the user
+async fn send_password_reset_email(state: &AppState, user: &User) -> Result<(), ApplicationError> {
+    let expiration: i64 = env::var("PASSWORD_RESET_EXPIRATION")
+        .unwrap_or(String::from("1800000"))
+        .parse()?;
+    let url = env::var("PASSWORD_RESET_URL")
+        .unwrap_or(String::from("http://localhost:3000/reset-password"));
+
+    let token = generate_secure_token();
+    persist_password_reset_token(
+        &state.pool,
+        &user.id,
+        &hash_secure_token(&token),
+        OffsetDateTime::now_utc() + Duration::milliseconds(expiration),
+    )
+    .await?;
+
+    state
+        .mailer
+        .send(MailMessage {
+            to: user.email.clone(),
+            subject: String::from("Reset your password"),
+            body: format!(
+                "Hi {},\n\nA password reset was requested for your account. Open the link below to choose a new password.\n\
+                The link can only be used once and expires in {} minutes.\n\n{url}?token={token}\n\n\
+                If you did not request this, you can ignore this email.\n",
+                user.name,
+                expiration / 60_000
+            ),
+        })
+        .await
+}
+
+///# Forgot Password
+///
+/// mail a password reset link when the account exists
+///
+/// the response is the same whether the email exists or not, the lookup and the mail happen in the
+/// background so the response time doesn't tell either
+pub async fn forgot_password(
+    Extension(state): Extension<Arc<AppState>>,
+    context: SessionContext,
+    request: Json<EmailRequest>,
+) -> Json<MessageResponse> {
+    tokio::spawn(async move {
+        let Ok(user) = get_user_by_email(&state.pool, &request.0.email).await else {
+            return;
+        };
+        let audit = NewAuditEvent::new(
+            AuditEventType::PASSWORD_RESET_REQUESTED,
+            AuditOutcome::SUCCESS,
+        )
+        .actor(user.id)
+        .context(&context);
+        let audit = match send_password_reset_email(&state, &user).await {
+            Ok(()) => audit,
+            Err(error) => {
+                error!("{:?}", error);
+                NewAuditEvent {
+                    outcome: AuditOutcome::FAILURE,
+                    ..audit
+                }
+                .metadata(json!({"reason": error.error}))
+            }
+        };
+        record_audit_event(&state.pool, audit).await;
+    });
+    Json(MessageResponse::new(
+        "If the account exists, a password reset email has been sent",
+    ))
+}
+
+///# Reset Password
+///
+/// consume the reset token and set the new password
+///
+/// all access and refresh tokens of the user are revoked
+pub async fn reset_password(
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    request: Json<ResetPasswordRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    if !request.0.password.eq(&request.0.confirm_password) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "Password Miss match",
+                "Passwords must match",
+            )),
+        ));
+    }
+
+    let token_hash = hash_secure_token(&request.0.token);
+    //the token is only consumed below, an invalid one is refused there
+    match find_user_by_password_reset_token(&state.pool, &token_hash).await {
+        Ok(Some(user)) => validate_password(&request.0.password, &[&user.name, &user.email])?,
+        Ok(None) => validate_password(&request.0.password, &[])?,
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    }
+
+    let password_hash = match hash_password(&request.0.password).await {
+        Ok(password_hash) => password_hash,
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    let audit =
+        NewAuditEvent::new(AuditEventType::PASSWORD_RESET, AuditOutcome::SUCCESS).context(&context);
+    match reset_password_with_token(&state.pool, &token_hash, &password_hash).await {
+        Ok(token) => {
+            info!("password reset for user {}", token.user_id);
+            record_audit_event(&state.pool, audit.actor(token.user_id)).await;
+            Ok(Json(MessageResponse::new("Password reset successfully")))
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            record_audit_event(
+                &state.pool,
+                NewAuditEvent {
+                    outcome: AuditOutcome::FAILURE,
+                    ..audit
+                }
+                .metadata(json!({"reason": error.error})),
+            )
+            .await;
+            Err((StatusCode::BAD_REQUEST, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mail::memory_mailer::MemoryMailer;
+    use crate::application::security::password_hasher::{
+        PasswordHasher, PasswordVerification, verify_password,
+    };
+    use crate::application::security::password_policy::PasswordPolicy;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    const STRONG_PASSWORD: &str = "Quartz-Lamp-58";
+
+    //the mail is sent in the background
+    async fn wait_for_mail(mailer: &MemoryMailer, count: usize) -> Vec<MailMessage> {
+        for _ in 0..50 {
+            let messages = mailer.messages();
+            if messages.len() >= count {
+                return messages;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        mailer.messages()
+    }
+
+    fn mailed_token(message: &MailMessage) -> String {
+        let (_, link) = message.body.split_once("?token=").unwrap();
+        link.split_whitespace().next().unwrap().to_owned()
+    }
+
+    fn reset_request(token: &str, password: &str) -> Json<ResetPasswordRequest> {
+        Json(ResetPasswordRequest {
+            token: token.to_owned(),
+            password: password.to_owned(),
+            confirm_password: password.to_owned(),
+        })
+    }
+
+    async fn insert_user(pool: &PgPool) -> Uuid {
+        let (id, session_id) = (Uuid::new_v4(), Uuid::new_v4());
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', 'hash', true)",
+            id
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "insert into user_sessions(id, user_id, expires_at) values ($1, $2, now() + interval '1 day')",
+            session_id,
+            id
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "insert into token(id, user_id, is_expired, is_revoked, session_id, token_hash)
+            values ($1, $2, false, false, $3, 'digest')",
+            Uuid::new_v4(),
+            id,
+            session_id
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    #[sqlx::test]
+    async fn mailed_token_resets_the_password_once_and_revokes_every_session(pool: PgPool) {
+        PasswordHasher::install_for_tests();
+        PasswordPolicy::install_for_tests();
+        let id = insert_user(&pool).await;
+        let (state, mailer) = AppState::for_tests(pool);
+
+        let answer = forgot_password(
+            Extension(state.clone()),
+            SessionContext::default(),
+            Json(EmailRequest {
+                email: String::from("ada@x.io"),
+            }),
+        )
+        .await;
+        let messages = wait_for_mail(&mailer, 1).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].to, "ada@x.io");
+        let token = mailed_token(&messages[0]);
+
+        let (status, error) = reset_password(
+            Extension(state.clone()),
+            SessionContext::default(),
+            reset_request(&token, "password"),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, "Password Policy Error");
+
+        let reset = reset_password(
+            Extension(state.clone()),
+            SessionContext::default(),
+            reset_request(&token, STRONG_PASSWORD),
+        )
+        .await
+        .unwrap();
+        assert_eq!(reset.0, MessageResponse::new("Password reset successfully"));
+        let password = sqlx::query_scalar!("select password from users where id = $1", id)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            verify_password(STRONG_PASSWORD, password.as_deref())
+                .await
+                .unwrap(),
+            PasswordVerification::Valid
+        );
+        let live_tokens = sqlx::query_scalar!(
+            "select count(*) from token where user_id = $1 and not is_revoked",
+            id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+        let live_sessions = sqlx::query_scalar!(
+            "select count(*) from user_sessions where user_id = $1 and revoked_at is null",
+            id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+        assert_eq!((live_tokens, live_sessions), (Some(0), Some(0)));
+        let resets = sqlx::query_scalar!(
+            "select count(*) from audit_events
+            where actor_id = $1 and event_type = 'PASSWORD_RESET' and outcome = 'SUCCESS'",
+            id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+        assert_eq!(resets, Some(1));
+
+        let (status, _) = reset_password(
+            Extension(state.clone()),
+            SessionContext::default(),
+            reset_request(&token, STRONG_PASSWORD),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        //an unknown email gets the same answer and no mail
+        let unknown = forgot_password(
+            Extension(state.clone()),
+            SessionContext::default(),
+            Json(EmailRequest {
+                email: String::from("nobody@x.io"),
+            }),
+        )
+        .await;
+        assert_eq!(unknown.0, answer.0);
+        assert_eq!(wait_for_mail(&mailer, 2).await.len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn an_expired_token_is_refused(pool: PgPool) {
+        PasswordHasher::install_for_tests();
+        PasswordPolicy::install_for_tests();
+        let id = insert_user(&pool).await;
+        let (state, mailer) = AppState::for_tests(pool);
+        let user = get_user_by_email(&state.pool, "ada@x.io").await.unwrap();
+
+        send_password_reset_email(&state, &user).await.unwrap();
+        let token = mailed_token(&mailer.messages()[0]);
+        sqlx::query!(
+            "update password_reset_tokens set expires_at = now() - interval '1 second' where user_id = $1",
+            id
+        )
+        .execute(&state.pool)
+        .await
+        .unwrap();
+
+        let (status, _) = reset_password(
+            Extension(state.clone()),
+            SessionContext::default(),
+            reset_request(&token, STRONG_PASSWORD),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
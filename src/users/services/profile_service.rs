@@ -0,0 +1,538 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::password_hasher::{
+    PasswordVerification, hash_password, verify_password,
+};
+use crate::users::repositories::user_repository::{change_user_password, update_user_profile};
+use crate::users::services::audit_service::record_audit_event;
+use crate::users::services::avatar_service::delete_avatar_files;
+use crate::users::services::magic_link_service::confirm_with_magic_link_code;
+use crate::users::services::password_policy_service::validate_password;
+use crate::users::services::session_service::current_session_id;
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::change_password_request::ChangePasswordRequest;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::new_audit_event::NewAuditEvent;
+use crate::users::types::profile_response::ProfileResponse;
+use crate::users::types::session_context::SessionContext;
+use crate::users::types::update_profile_request::UpdateProfileRequest;
+use crate::users::types::user::User;
+use axum::http::HeaderMap;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use url::Url;
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_IMAGE_URL_LENGTH: usize = 2048;
+
+fn validation_error(description: &str) -> (StatusCode, Json<ApplicationError>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ApplicationError::new("Validation Error", description)),
+    )
+}
+
+pub fn validate_profile_name(name: &str) -> Result<String, (StatusCode, Json<ApplicationError>)> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(validation_error(&format!(
+            "Name must be 1 to {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_owned())
+}
+
+///only absolute http(s) urls are stored, an empty value clears the image
+fn validate_image_url(
+    image_url: &str,
+) -> Result<Option<String>, (StatusCode, Json<ApplicationError>)> {
+    let image_url = image_url.trim();
+    if image_url.is_empty() {
+        return Ok(None);
+    }
+    if image_url.len() > MAX_IMAGE_URL_LENGTH {
+        return Err(validation_error(&format!(
+            "Image url must be at most {} characters",
+            MAX_IMAGE_URL_LENGTH
+        )));
+    }
+    match Url::parse(image_url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(Some(image_url.to_owned())),
+        _ => Err(validation_error("Image url must be an http or https url")),
+    }
+}
+
+///# Verify Current Password
+///
+/// sensitive account changes are confirmed with the current password
+///
+/// passwordless accounts have none, they confirm with a fresh code mailed by /auth/magic-link so a
+/// stolen access token alone can't set a password or delete the account
+pub async fn verify_current_password(
+    pool: &PgPool,
+    user: &User,
+    password: &str,
+    code: Option<&str>,
+) -> Result<(), (StatusCode, Json<ApplicationError>)> {
+    let Some(password_hash) = user.password.as_deref() else {
+        let Some(code) = code else {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ApplicationError::new(
+                    "Authentication Error",
+                    "Enter the code mailed by /auth/magic-link to confirm",
+                )),
+            ));
+        };
+        return match confirm_with_magic_link_code(pool, &user.email, code).await {
+            Ok(()) => Ok(()),
+            Err(error) if error.error == "Magic Link Error" => {
+                Err((StatusCode::BAD_REQUEST, Json(error)))
+            }
+            Err(error) => {
+                error!("{:?}", error);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+            }
+        };
+    };
+    match verify_password(password, Some(password_hash)).await {
+        Ok(PasswordVerification::Valid | PasswordVerification::Outdated) => Ok(()),
+        Ok(PasswordVerification::Invalid) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "Authentication Error",
+                "Current password is incorrect",
+            )),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+pub async fn profile_response(
+    pool: &PgPool,
+    user: &User,
+) -> Result<ProfileResponse, ApplicationError> {
+    Ok(ProfileResponse {
+        user: user.to_response(pool).await?,
+        image_url: user.image_url.clone(),
+        source: user.source.clone(),
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+        deletion_scheduled_at: user.deletion_scheduled_at,
+    })
+}
+
+///# Get Profile
+///
+/// the signed in user's account and profile
+pub async fn get_profile(
+    user: User,
+    state: Extension<Arc<AppState>>,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<ApplicationError>)> {
+    match profile_response(&state.pool, &user).await {
+        Ok(response) => Ok(Json(response)),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Update Profile
+///
+/// change the name and/or image url of the signed in user
+pub async fn update_profile(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    request: Json<UpdateProfileRequest>,
+) -> Result<Json<ProfileResponse>, (StatusCode, Json<ApplicationError>)> {
+    let name = match &request.0.name {
+        Some(name) => Some(validate_profile_name(name)?),
+        None => None,
+    };
+    //a new image url replaces an uploaded avatar
+    let image_url = match &request.0.image_url {
+        Some(image_url) => Some(validate_image_url(image_url)?),
+        None => None,
+    };
+
+    let result = match update_user_profile(
+        &state.pool,
+        &user.id,
+        name.as_deref(),
+        image_url.as_ref().map(|image_url| image_url.as_deref()),
+    )
+    .await
+    {
+        Ok((updated, previous)) => {
+            //the avatar is read from the row, the one loaded with the request may be stale
+            if let Some(previous) = previous
+                && updated.avatar_id.is_none()
+            {
+                delete_avatar_files(&state.storage, &user.id, &previous).await;
+            }
+            profile_response(&state.pool, &updated).await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(response) => Ok(Json(response)),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Change Password
+///
+/// verify the current password and set the new one, passwordless accounts set their first password here
+/// with a code from /auth/magic-link
+///
+/// every other session of the user is revoked, the one making the request stays logged in
+pub async fn change_password(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    context: SessionContext,
+    headers: HeaderMap,
+    request: Json<ChangePasswordRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    if !request.0.password.eq(&request.0.confirm_password) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApplicationError::new(
+                "Password Miss match",
+                "Passwords must match",
+            )),
+        ));
+    }
+
+    let audit = NewAuditEvent::new(AuditEventType::PASSWORD_CHANGED, AuditOutcome::SUCCESS)
+        .actor(user.id)
+        .context(&context);
+    if let Err((status, error)) = verify_current_password(
+        &state.pool,
+        &user,
+        &request.0.current_password,
+        request.0.code.as_deref(),
+    )
+    .await
+    {
+        record_audit_event(
+            &state.pool,
+            NewAuditEvent {
+                outcome: AuditOutcome::FAILURE,
+                ..audit
+            }
+            .metadata(json!({"reason": error.error})),
+        )
+        .await;
+        return Err((status, error));
+    }
+    validate_password(&request.0.password, &[&user.name, &user.email])?;
+
+    let password_hash = match hash_password(&request.0.password).await {
+        Ok(password_hash) => password_hash,
+        Err(error) => {
+            error!("{:?}", error);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)));
+        }
+    };
+
+    let current = current_session_id(&state.pool, &headers).await;
+    match change_user_password(&state.pool, &user.id, &password_hash, current).await {
+        Ok(revoked) => {
+            info!(
+                "password changed for user {}, {} other sessions revoked",
+                user.id, revoked
+            );
+            record_audit_event(
+                &state.pool,
+                audit.metadata(json!({"revoked_sessions": revoked})),
+            )
+            .await;
+            Ok(Json(MessageResponse::new("Password changed successfully")))
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mail::mail_message::MailMessage;
+    use crate::application::mail::memory_mailer::MemoryMailer;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::password_hasher::PasswordHasher;
+    use crate::application::security::password_policy::PasswordPolicy;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::repositories::user_repository::get_user_by_id;
+    use crate::users::services::avatar_service::{AVATAR_SIZES, avatar_key};
+    use crate::users::services::jwt_service::{
+        TokenType, UserTokenResponse, generate_persisted_user_token, verify_token,
+    };
+    use crate::users::services::magic_link_service::request_magic_link;
+    use crate::users::types::magic_link_request::MagicLinkRequest;
+    use axum::http::header;
+    use uuid::Uuid;
+
+    const STRONG_PASSWORD: &str = "Quartz-Lamp-58";
+
+    async fn insert_user(pool: &PgPool, password: Option<&str>) -> User {
+        PasswordHasher::install_for_tests();
+        PasswordPolicy::install_for_tests();
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        let id = Uuid::new_v4();
+        let password = match password {
+            Some(password) => Some(hash_password(password).await.unwrap()),
+            None => None,
+        };
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', $2, true)",
+            id,
+            password
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        get_user_by_id(pool, &id).await.unwrap()
+    }
+
+    async fn sign_in(pool: &PgPool) -> UserTokenResponse {
+        generate_persisted_user_token("ada@x.io", &SessionContext::default(), pool)
+            .await
+            .unwrap()
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        headers
+    }
+
+    fn password_request(current_password: &str, code: Option<&str>) -> Json<ChangePasswordRequest> {
+        Json(ChangePasswordRequest {
+            current_password: current_password.to_owned(),
+            code: code.map(str::to_owned),
+            password: String::from("Velvet-Harbor-93"),
+            confirm_password: String::from("Velvet-Harbor-93"),
+        })
+    }
+
+    //the mail is sent in the background
+    async fn wait_for_mail(mailer: &MemoryMailer) -> Vec<MailMessage> {
+        for _ in 0..50 {
+            let messages = mailer.messages();
+            if !messages.is_empty() {
+                return messages;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        mailer.messages()
+    }
+
+    #[test]
+    fn names_and_image_urls_are_validated() {
+        assert_eq!(validate_profile_name("  Ada  ").unwrap(), "Ada");
+        assert!(validate_profile_name("   ").is_err());
+        assert!(validate_profile_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        assert!(validate_profile_name(&"é".repeat(MAX_NAME_LENGTH)).is_ok());
+
+        assert_eq!(validate_image_url("  ").unwrap(), None);
+        assert_eq!(
+            validate_image_url(" https://x.io/ada.png ").unwrap(),
+            Some(String::from("https://x.io/ada.png"))
+        );
+        for image_url in [
+            "ftp://x.io/ada.png",
+            "javascript:alert(1)",
+            "/ada.png",
+            &format!("https://x.io/{}", "a".repeat(MAX_IMAGE_URL_LENGTH)),
+        ] {
+            let (status, _) = validate_image_url(image_url).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[sqlx::test]
+    async fn an_empty_image_url_clears_the_uploaded_avatar(pool: PgPool) {
+        let user = insert_user(&pool, Some(STRONG_PASSWORD)).await;
+        let avatar_id = Uuid::new_v4();
+        sqlx::query!(
+            "update users set avatar_id = $2, image_url = 'http://localhost/avatar.png' where id = $1",
+            user.id,
+            avatar_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let (state, _) = AppState::for_tests(pool);
+        for size in AVATAR_SIZES {
+            state
+                .storage
+                .put(&avatar_key(&user.id, &avatar_id, size), vec![1, 2, 3])
+                .await
+                .unwrap();
+        }
+        let update = |name: Option<&str>, image_url: Option<&str>| {
+            update_profile(
+                user.clone(),
+                Extension(state.clone()),
+                Json(UpdateProfileRequest {
+                    name: name.map(str::to_owned),
+                    image_url: image_url.map(str::to_owned),
+                }),
+            )
+        };
+
+        //a new name alone leaves the avatar in place
+        let Json(renamed) = update(Some("Ada Lovelace"), None).await.unwrap();
+        assert_eq!(renamed.user.name, "Ada Lovelace");
+        assert!(renamed.image_url.is_some());
+
+        let Json(cleared) = update(None, Some("")).await.unwrap();
+        assert_eq!(cleared.image_url, None);
+        assert_eq!(cleared.user.name, "Ada Lovelace");
+        assert_eq!(
+            get_user_by_id(&state.pool, &user.id)
+                .await
+                .unwrap()
+                .avatar_id,
+            None
+        );
+        for size in AVATAR_SIZES {
+            let key = avatar_key(&user.id, &avatar_id, size);
+            assert_eq!(state.storage.get(&key).await.unwrap(), None);
+        }
+    }
+
+    #[sqlx::test]
+    async fn a_wrong_current_password_is_refused_and_audited(pool: PgPool) {
+        let user = insert_user(&pool, Some(STRONG_PASSWORD)).await;
+        let tokens = sign_in(&pool).await;
+        let (state, _) = AppState::for_tests(pool);
+
+        let (status, Json(error)) = change_password(
+            user.clone(),
+            Extension(state.clone()),
+            SessionContext::default(),
+            bearer(&tokens.access),
+            password_request("Wrong-Guess-11", None),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.description, "Current password is incorrect");
+        let stored = get_user_by_id(&state.pool, &user.id).await.unwrap();
+        assert_eq!(stored.password, user.password);
+        let failures = sqlx::query_scalar!(
+            "select count(*) from audit_events
+            where actor_id = $1 and event_type = 'PASSWORD_CHANGED' and outcome = 'FAILURE'",
+            user.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+        assert_eq!(failures, Some(1));
+    }
+
+    #[sqlx::test]
+    async fn a_passwordless_account_sets_its_first_password_with_a_mailed_code(pool: PgPool) {
+        let user = insert_user(&pool, None).await;
+        let tokens = sign_in(&pool).await;
+        let (state, mailer) = AppState::for_tests(pool);
+        let change = |code: Option<String>| {
+            change_password(
+                user.clone(),
+                Extension(state.clone()),
+                SessionContext::default(),
+                bearer(&tokens.access),
+                password_request("", code.as_deref()),
+            )
+        };
+
+        let (status, _) = change(None).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        assert!(
+            request_magic_link(
+                Extension(state.clone()),
+                Json(MagicLinkRequest {
+                    email: String::from("ada@x.io"),
+                    name: None,
+                }),
+            )
+            .await
+            .is_ok()
+        );
+        let messages = wait_for_mail(&mailer).await;
+        let (_, rest) = messages[0].body.split_once("enter the code ").unwrap();
+        let code = rest[..6].to_owned();
+
+        assert!(change(Some(code.clone())).await.is_ok());
+        let stored = get_user_by_id(&state.pool, &user.id).await.unwrap();
+        assert_eq!(
+            verify_password("Velvet-Harbor-93", stored.password.as_deref())
+                .await
+                .unwrap(),
+            PasswordVerification::Valid
+        );
+        //the code is used up
+        let (status, _) = change(Some(code)).await.unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn other_sessions_are_revoked_while_the_current_one_survives(pool: PgPool) {
+        let user = insert_user(&pool, Some(STRONG_PASSWORD)).await;
+        let current = sign_in(&pool).await;
+        let other = sign_in(&pool).await;
+        let (state, _) = AppState::for_tests(pool);
+
+        assert!(
+            change_password(
+                user,
+                Extension(state.clone()),
+                SessionContext::default(),
+                bearer(&current.access),
+                password_request(STRONG_PASSWORD, None),
+            )
+            .await
+            .is_ok()
+        );
+
+        for (token, token_type) in [
+            (&current.access, TokenType::ACCESS),
+            (&current.refresh, TokenType::REFRESH),
+        ] {
+            assert!(verify_token(token, token_type, &state.pool).await.is_ok());
+        }
+        for (token, token_type) in [
+            (&other.access, TokenType::ACCESS),
+            (&other.refresh, TokenType::REFRESH),
+        ] {
+            let error = verify_token(token, token_type, &state.pool)
+                .await
+                .unwrap_err();
+            assert_eq!(error.description, "JWT Token is revoked");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::repositories::session_repository::{get_active_sessions, revoke_user_session};
+use crate::users::repositories::token_repository::get_token_by_token;
+use crate::users::services::jwt_service::extract_bearer_token;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::session_response::SessionResponse;
+use crate::users::types::user::User;
+use axum::extract::Path;
+use axum::http::HeaderMap;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+///the session the bearer token of the request belongs to
+pub async fn current_session_id(pool: &PgPool, headers: &HeaderMap) -> Option<Uuid> {
+    match extract_bearer_token(headers) {
+        Some(token) => get_token_by_token(token, pool)
+            .await
+            .ok()
+            .map(|token| token.session_id),
+        None => None,
+    }
+}
+
+///# List Sessions
+///
+/// where the user is logged in, the session of the request is flagged as current
+pub async fn list_sessions(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SessionResponse>>, (StatusCode, Json<ApplicationError>)> {
+    let current = current_session_id(&state.pool, &headers).await;
+
+    match get_active_sessions(&state.pool, &user.id).await {
+        Ok(sessions) => Ok(Json(
+            sessions
+                .into_iter()
+                .map(|session| {
+                    let is_current = current == Some(session.id);
+                    SessionResponse::new(session, is_current)
+                })
+                .collect(),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+///# Revoke Session
+///
+/// log one device out, its refresh token and every access token it issued stop working
+pub async fn revoke_session(
+    user: User,
+    state: Extension<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match revoke_user_session(&state.pool, &user.id, &id).await {
+        Ok(true) => {
+            info!("session {} of user {} revoked", id, user.id);
+            Ok(Json(MessageResponse::new("Session revoked")))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ApplicationError::new("Not Found", "Session not found")),
+        )),
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::repositories::token_repository::persist_refresh_and_access_tokens;
+    use crate::users::repositories::user_repository::get_user_by_email;
+    use crate::users::services::jwt_service::{
+        TokenType, UserTokenResponse, generate_persisted_user_token, verify_token,
+    };
+    use crate::users::types::session_context::SessionContext;
+    use crate::users::types::user_session::UserSession;
+    use axum::http::header;
+    use time::{Duration, OffsetDateTime};
+
+    async fn insert_user(pool: &PgPool, email: &str) -> User {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', $2, 'hash', true)",
+            Uuid::new_v4(),
+            email
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        get_user_by_email(pool, email).await.unwrap()
+    }
+
+    async fn sign_in(pool: &PgPool, email: &str) -> UserTokenResponse {
+        generate_persisted_user_token(email, &SessionContext::default(), pool)
+            .await
+            .unwrap()
+    }
+
+    //a session started `age` ago with placeholder tokens, only their digests are stored
+    async fn start_session(pool: &PgPool, user: &User, age: Duration, cap: i64) -> Uuid {
+        let now = OffsetDateTime::now_utc();
+        let session = UserSession {
+            id: Uuid::new_v4(),
+            user_id: user.id,
+            user_agent: None,
+            ip_address: None,
+            created_at: now - age,
+            last_seen_at: now - age,
+            expires_at: now + Duration::days(1),
+            revoked_at: None,
+        };
+        let tokens = UserTokenResponse {
+            access: Uuid::new_v4().to_string(),
+            refresh: Uuid::new_v4().to_string(),
+        };
+        persist_refresh_and_access_tokens(
+            pool,
+            &tokens,
+            now + Duration::minutes(5),
+            &session,
+            Some(cap),
+        )
+        .await
+        .unwrap();
+        session.id
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        headers
+    }
+
+    async fn active_session_ids(pool: &PgPool, user: &User) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = get_active_sessions(pool, &user.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|session| session.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[sqlx::test]
+    async fn the_oldest_sessions_beyond_the_cap_are_revoked_with_their_tokens(pool: PgPool) {
+        let user = insert_user(&pool, "ada@x.io").await;
+        let oldest = start_session(&pool, &user, Duration::minutes(2), 2).await;
+        let older = start_session(&pool, &user, Duration::minutes(1), 2).await;
+        let newest = start_session(&pool, &user, Duration::ZERO, 2).await;
+
+        let mut kept = vec![older, newest];
+        kept.sort();
+        assert_eq!(active_session_ids(&pool, &user).await, kept);
+        let live_tokens = sqlx::query_scalar!(
+            "select count(*) from token where session_id = $1 and is_revoked = false",
+            oldest
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(live_tokens, Some(0));
+
+        //two sign-ins at once still leave a single session under a cap of one
+        tokio::join!(
+            start_session(&pool, &user, Duration::ZERO, 1),
+            start_session(&pool, &user, Duration::ZERO, 1)
+        );
+        assert_eq!(active_session_ids(&pool, &user).await.len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn listed_sessions_flag_the_current_one_and_skip_revoked_ones(pool: PgPool) {
+        let user = insert_user(&pool, "ada@x.io").await;
+        let current = sign_in(&pool, "ada@x.io").await;
+        let other = sign_in(&pool, "ada@x.io").await;
+        let revoked = sign_in(&pool, "ada@x.io").await;
+        let session_of = |tokens: &UserTokenResponse| {
+            let access = tokens.access.clone();
+            let pool = pool.clone();
+            async move { get_token_by_token(&access, &pool).await.unwrap().session_id }
+        };
+        let (current_id, other_id) = (session_of(&current).await, session_of(&other).await);
+        revoke_user_session(&pool, &user.id, &session_of(&revoked).await)
+            .await
+            .unwrap();
+        let (state, _) = AppState::for_tests(pool);
+
+        let Json(sessions) = list_sessions(user, Extension(state), bearer(&current.access))
+            .await
+            .unwrap();
+
+        assert_eq!(sessions.len(), 2);
+        for session in sessions {
+            assert!(session.id == current_id || session.id == other_id);
+            assert_eq!(session.current, session.id == current_id);
+        }
+    }
+
+    #[sqlx::test]
+    async fn revoking_a_session_only_logs_that_device_out(pool: PgPool) {
+        let user = insert_user(&pool, "ada@x.io").await;
+        let stranger = insert_user(&pool, "eve@x.io").await;
+        let kept = sign_in(&pool, "ada@x.io").await;
+        let revoked = sign_in(&pool, "ada@x.io").await;
+        let strangers = sign_in(&pool, "eve@x.io").await;
+        let revoked_id = get_token_by_token(&revoked.access, &pool)
+            .await
+            .unwrap()
+            .session_id;
+        let strangers_id = get_token_by_token(&strangers.access, &pool)
+            .await
+            .unwrap()
+            .session_id;
+        let (state, _) = AppState::for_tests(pool);
+        let revoke = |id: Uuid| revoke_session(user.clone(), Extension(state.clone()), Path(id));
+
+        assert!(revoke(revoked_id).await.is_ok());
+        for (token, token_type) in [
+            (&revoked.access, TokenType::ACCESS),
+            (&revoked.refresh, TokenType::REFRESH),
+        ] {
+            let error = verify_token(token, token_type, &state.pool)
+                .await
+                .unwrap_err();
+            assert_eq!(error.description, "JWT Token is revoked");
+        }
+        assert!(
+            verify_token(&kept.access, TokenType::ACCESS, &state.pool)
+                .await
+                .is_ok()
+        );
+
+        //an already revoked session and another user's session are not found
+        for id in [revoked_id, strangers_id] {
+            let (status, _) = revoke(id).await.err().unwrap();
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+        assert!(
+            verify_token(&strangers.access, TokenType::ACCESS, &state.pool)
+                .await
+                .is_ok()
+        );
+        assert_eq!(
+            get_active_sessions(&state.pool, &stranger.id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
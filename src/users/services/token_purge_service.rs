@@ -0,0 +1,254 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::repositories::session_repository::delete_stale_sessions;
+use crate::users::repositories::token_repository::{delete_expired_tokens, mark_expired_tokens};
+use crate::users::services::jwt_service::{TokenType, token_lifetime};
+use log::{error, info};
+use sqlx::PgPool;
+use std::env;
+use time::{Duration, OffsetDateTime};
+
+///# Token Purge Policy
+///
+/// TOKEN_RETENTION: how long expired rows are kept, e.g. for audits of reuse (ms)
+///
+/// TOKEN_PURGE_BATCH_SIZE: rows deleted per statement so the table is never locked for long
+///
+/// TOKEN_PURGE_INTERVAL: pause between two runs of the background task (ms)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenPurgePolicy {
+    pub retention: Duration,
+    pub batch_size: i64,
+    pub interval: u64,
+}
+
+impl TokenPurgePolicy {
+    pub fn from_env() -> Result<Self, ApplicationError> {
+        let policy = Self {
+            retention: Duration::milliseconds(
+                env::var("TOKEN_RETENTION")
+                    .unwrap_or(String::from("604800000"))
+                    .parse()?,
+            ),
+            batch_size: env::var("TOKEN_PURGE_BATCH_SIZE")
+                .unwrap_or(String::from("1000"))
+                .parse()?,
+            interval: env::var("TOKEN_PURGE_INTERVAL")
+                .unwrap_or(String::from("3600000"))
+                .parse()?,
+        };
+        if policy.batch_size <= 0 {
+            return Err(ApplicationError::new(
+                "Configuration Error",
+                "TOKEN_PURGE_BATCH_SIZE must be positive",
+            ));
+        }
+        //tokio panics on a zero period
+        if policy.interval == 0 {
+            return Err(ApplicationError::new(
+                "Configuration Error",
+                "TOKEN_PURGE_INTERVAL must be positive",
+            ));
+        }
+        Ok(policy)
+    }
+}
+
+///what one purge run did
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenPurgeReport {
+    pub expired: u64,
+    pub deleted_tokens: u64,
+    pub deleted_sessions: u64,
+}
+
+///# Purge Tokens
+///
+/// mark expired tokens, then delete expired or revoked tokens and sessions past the retention window
+/// batch by batch
+///
+/// tokens issued before expiry was stored are aged by the refresh token lifetime
+pub async fn purge_tokens(
+    pool: &PgPool,
+    policy: &TokenPurgePolicy,
+) -> Result<TokenPurgeReport, ApplicationError> {
+    let now = OffsetDateTime::now_utc();
+    let legacy_lifetime = token_lifetime(&TokenType::REFRESH)?;
+    let expired_before = now - policy.retention;
+
+    let mut report = TokenPurgeReport {
+        expired: mark_expired_tokens(pool, now - legacy_lifetime).await?,
+        ..Default::default()
+    };
+
+    loop {
+        let deleted = delete_expired_tokens(
+            pool,
+            expired_before,
+            expired_before - legacy_lifetime,
+            policy.batch_size,
+        )
+        .await?;
+        report.deleted_tokens += deleted;
+        if deleted < policy.batch_size as u64 {
+            break;
+        }
+    }
+
+    loop {
+        let deleted = delete_stale_sessions(pool, expired_before, policy.batch_size).await?;
+        report.deleted_sessions += deleted;
+        if deleted < policy.batch_size as u64 {
+            break;
+        }
+    }
+
+    info!(
+        "token purge: {} tokens marked expired, {} tokens and {} sessions deleted",
+        report.expired, report.deleted_tokens, report.deleted_sessions
+    );
+    Ok(report)
+}
+
+///# Spawn Token Purge Task
+///
+/// run the purge periodically for as long as the server is up
+pub fn spawn_token_purge_task(pool: PgPool) -> Result<(), ApplicationError> {
+    let policy = TokenPurgePolicy::from_env()?;
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_millis(policy.interval));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = purge_tokens(&pool, &policy).await {
+                error!("{:?}", error);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::session_policy::SessionPolicy;
+    use uuid::Uuid;
+
+    async fn insert_token(
+        pool: &PgPool,
+        user_id: &Uuid,
+        created_at: OffsetDateTime,
+        expires_at: Option<OffsetDateTime>,
+        is_revoked: bool,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into token(id, user_id, token_hash, session_id, is_expired, is_revoked,
+                               created_at, expires_at)
+            values ($1, $2, $3, $4, false, $5, $6, $7)",
+            id,
+            user_id,
+            id.to_string(),
+            Uuid::new_v4(),
+            is_revoked,
+            created_at,
+            expires_at
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    async fn insert_session(
+        pool: &PgPool,
+        user_id: &Uuid,
+        expires_at: OffsetDateTime,
+        revoked_at: Option<OffsetDateTime>,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into user_sessions(id, user_id, expires_at, revoked_at) values ($1, $2, $3, $4)",
+            id,
+            user_id,
+            expires_at,
+            revoked_at
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        id
+    }
+
+    #[sqlx::test]
+    async fn only_rows_past_the_retention_window_are_deleted(pool: PgPool) {
+        //legacy tokens without an expiry are aged by the 7 day refresh lifetime of the test policy
+        SessionPolicy::install_for_tests();
+        let user_id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email) values ($1, 'Ada', 'ada@x.io')",
+            user_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let now = OffsetDateTime::now_utc();
+        let (hour, days) = (Duration::hours(1), Duration::days);
+
+        let live = insert_token(&pool, &user_id, now, Some(now + hour), false).await;
+        let recently_expired =
+            insert_token(&pool, &user_id, now - hour, Some(now - hour), false).await;
+        let recently_revoked =
+            insert_token(&pool, &user_id, now - hour, Some(now + hour), true).await;
+        let legacy = insert_token(&pool, &user_id, now - days(2), None, false).await;
+        //expired, revoked and legacy tokens past the retention window
+        insert_token(&pool, &user_id, now - days(3), Some(now - days(2)), false).await;
+        insert_token(&pool, &user_id, now - days(2), Some(now + days(5)), true).await;
+        insert_token(&pool, &user_id, now - days(10), None, false).await;
+
+        let live_session = insert_session(&pool, &user_id, now + days(1), None).await;
+        let recently_revoked_session =
+            insert_session(&pool, &user_id, now + days(1), Some(now - hour)).await;
+        //expired and revoked sessions past the retention window
+        insert_session(&pool, &user_id, now - days(2), None).await;
+        insert_session(&pool, &user_id, now + days(1), Some(now - days(2))).await;
+
+        //a batch size of two makes the purge loop over several batches
+        let policy = TokenPurgePolicy {
+            retention: days(1),
+            batch_size: 2,
+            interval: 1,
+        };
+        let report = purge_tokens(&pool, &policy).await.unwrap();
+
+        assert_eq!(
+            report,
+            TokenPurgeReport {
+                expired: 3,
+                deleted_tokens: 3,
+                deleted_sessions: 2,
+            }
+        );
+        let mut tokens = sqlx::query_scalar!("select id from token")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        tokens.sort();
+        let mut kept = vec![live, recently_expired, recently_revoked, legacy];
+        kept.sort();
+        assert_eq!(tokens, kept);
+        let marked = sqlx::query_scalar!("select id from token where is_expired")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(marked, vec![recently_expired]);
+
+        let mut sessions = sqlx::query_scalar!("select id from user_sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        sessions.sort();
+        let mut kept = vec![live_session, recently_revoked_session];
+        kept.sort();
+        assert_eq!(sessions, kept);
+    }
+}
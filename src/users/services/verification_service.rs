@@ -0,0 +1,152 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::mail::mail_message::MailMessage;
+use crate::application::security::secure_token::{generate_secure_token, hash_secure_token};
+use crate::users::repositories::user_repository::get_user_by_email;
+use crate::users::repositories::verification_token_repository::{
+    consume_verification_token, persist_verification_token,
+};
+use crate::users::types::email_request::EmailRequest;
+use crate::users::types::message_response::MessageResponse;
+use crate::users::types::verify_email_request::VerifyEmailRequest;
+use axum::{Extension, Json};
+use log::{error, info};
+use reqwest::StatusCode;
+use std::env;
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+///# Send Verification Email
+///
+/// generate a single use token, persist its hash and mail the link to the user
+pub async fn send_verification_email(
+    state: &AppState,
+    user_id: &Uuid,
+    name: &str,
+    email: &str,
+) -> Result<(), ApplicationError> {
+    let expiration: i64 = env::var("EMAIL_VERIFICATION_EXPIRATION")
+        .unwrap_or(String::from("86400000"))
+        .parse()?;
+    let url = env::var("EMAIL_VERIFICATION_URL")
+        .unwrap_or(String::from("http://localhost:3000/verify-email"));
+
+    let token = generate_secure_token();
+    persist_verification_token(
+        &state.pool,
+        user_id,
+        &hash_secure_token(&token),
+        OffsetDateTime::now_utc() + Duration::milliseconds(expiration),
+    )
+    .await?;
+
+    state
+        .mailer
+        .send(MailMessage {
+            to: email.to_owned(),
+            subject: String::from("Verify your email address"),
+            body: format!(
+                "Hi {name},\n\nPlease confirm your email address by opening the link below.\n\
+                The link can only be used once and expires in {} minutes.\n\n{url}?token={token}\n",
+                expiration / 60_000
+            ),
+        })
+        .await
+}
+
+///# Verify Email
+///
+/// consume the token from the verification link and mark the email as verified
+pub async fn verify_email(
+    state: Extension<Arc<AppState>>,
+    request: Json<VerifyEmailRequest>,
+) -> Result<Json<MessageResponse>, (StatusCode, Json<ApplicationError>)> {
+    match consume_verification_token(&state.pool, &hash_secure_token(&request.0.token)).await {
+        Ok(token) => {
+            info!("email verified for user {}", token.user_id);
+            Ok(Json(MessageResponse::new("Email verified successfully")))
+        }
+        Err(error) => {
+            error!("{:?}", error);
+            Err((StatusCode::BAD_REQUEST, Json(error)))
+        }
+    }
+}
+
+///# Resend Verification
+///
+/// send a fresh verification link to an account that is not verified yet
+///
+/// the response is the same whether the email exists or not, the lookup and the mail happen in the
+/// background so the response time doesn't tell either
+pub async fn resend_verification(
+    Extension(state): Extension<Arc<AppState>>,
+    request: Json<EmailRequest>,
+) -> Json<MessageResponse> {
+    tokio::spawn(async move {
+        if let Ok(user) = get_user_by_email(&state.pool, &request.0.email).await
+            && !user.email_verified
+            && let Err(error) =
+                send_verification_email(&state, &user.id, &user.name, &user.email).await
+        {
+            error!("{:?}", error);
+        }
+    });
+    Json(MessageResponse::new(
+        "If the account exists and is not verified, a verification email has been sent",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::repositories::user_repository::get_user_by_id;
+    use sqlx::PgPool;
+
+    #[sqlx::test]
+    async fn mailed_link_verifies_the_account_once(pool: PgPool) {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email) values ($1, 'Ada', 'ada@x.io')",
+            id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let (state, mailer) = AppState::for_tests(pool);
+
+        send_verification_email(&state, &id, "Ada", "ada@x.io")
+            .await
+            .unwrap();
+
+        let messages = mailer.messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].to, "ada@x.io");
+        assert!(messages[0].body.starts_with("Hi Ada,"));
+        assert!(messages[0].body.contains("expires in 1440 minutes"));
+        let token = messages[0].body.split("?token=").nth(1).unwrap().trim();
+
+        let request = || {
+            Json(VerifyEmailRequest {
+                token: token.to_owned(),
+            })
+        };
+        assert!(
+            verify_email(Extension(state.clone()), request())
+                .await
+                .is_ok()
+        );
+        assert!(
+            get_user_by_id(&state.pool, &id)
+                .await
+                .unwrap()
+                .email_verified
+        );
+
+        let (status, _) = verify_email(Extension(state.clone()), request())
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
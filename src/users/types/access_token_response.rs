@@ -2,4 +2,5 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct RefreshTokenResponse {
     pub access_token: String,
-}
\ No newline at end of file
+    pub refresh_token: String,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+///the account is deleted for good at `deletion_scheduled_at` unless the deletion is cancelled before
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AccountDeletionResponse {
+    pub message: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deletion_scheduled_at: OffsetDateTime,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+///expiry date of an account, null removes the expiry
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AccountExpiryRequest {
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
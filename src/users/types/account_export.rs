@@ -0,0 +1,30 @@
+use crate::organizations::types::organization_response::OrganizationResponse;
+use crate::users::types::api_key_response::ApiKeyResponse;
+use crate::users::types::audit_event::AuditEvent;
+use crate::users::types::profile_response::ProfileResponse;
+use crate::users::types::session_response::SessionResponse;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+///# Account Export
+///
+/// everything held about a user, secrets such as password and token hashes are left out
+///
+/// the roles are part of the profile, organizations are listed with the role held in each
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AccountExport {
+    #[serde(with = "time::serde::rfc3339")]
+    pub exported_at: OffsetDateTime,
+    pub profile: ProfileResponse,
+    pub email_verified: bool,
+    pub mfa_enabled: bool,
+    pub failed_login_attempts: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub locked_until: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub account_expires_at: Option<OffsetDateTime>,
+    pub sessions: Vec<SessionResponse>,
+    pub api_keys: Vec<ApiKeyResponse>,
+    pub organizations: Vec<OrganizationResponse>,
+    pub audit_events: Vec<AuditEvent>,
+}
@@ -0,0 +1,69 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::services::account_status_service::is_account_status_error;
+use crate::users::services::api_key_service::{authenticate_api_key, extract_api_key};
+use crate::users::types::api_key::ApiKey;
+use crate::users::types::user::User;
+use axum::Json;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use log::error;
+use reqwest::StatusCode;
+use std::sync::Arc;
+
+///# Api Key Principal
+///
+/// the user owning the api key sent with the request, through `X-Api-Key` or `Authorization: ApiKey ...`
+pub struct ApiKeyPrincipal {
+    pub user: User,
+    pub api_key: ApiKey,
+}
+
+impl<S> FromRequestParts<S> for ApiKeyPrincipal
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let Some(key) = extract_api_key(&parts.headers) else {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ApplicationError::new(
+                    "Authentication Error",
+                    "api key required",
+                )),
+            ));
+        };
+
+        let Some(state) = parts.extensions.get::<Arc<AppState>>() else {
+            error!("Application State Not Found");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApplicationError::generic("Application State Not Found")),
+            ));
+        };
+
+        match authenticate_api_key(key, &state.pool).await {
+            Ok(principal) => Ok(principal),
+            //only the account status is explained, key and database errors stay in the log
+            Err(e) => {
+                error!("{}", e);
+                Err(match e {
+                    _ if is_account_status_error(&e) => (StatusCode::UNAUTHORIZED, Json(e)),
+                    _ if e.is_internal() => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApplicationError::generic("Authentication failed")),
+                    ),
+                    _ => (
+                        StatusCode::UNAUTHORIZED,
+                        Json(ApplicationError::new(
+                            "Authentication Error",
+                            "invalid api key",
+                        )),
+                    ),
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use crate::users::types::api_key_response::ApiKeyResponse;
+use crate::users::types::user_response::UserResponse;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ApiKeyPrincipalResponse {
+    pub user: UserResponse,
+    pub api_key: ApiKeyResponse,
+}
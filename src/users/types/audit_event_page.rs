@@ -0,0 +1,11 @@
+use crate::users::types::audit_event::AuditEvent;
+use serde::{Deserialize, Serialize};
+
+///one page of audit events, newest first
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AuditEventPage {
+    pub events: Vec<AuditEvent>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
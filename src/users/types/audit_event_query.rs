@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///# Audit Event Query
+///
+/// filters of the admin audit log, all optional and combined with `and`
+///
+/// `from` is inclusive and `to` exclusive, both rfc3339
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct AuditEventQuery {
+    pub actor_id: Option<Uuid>,
+    pub event_type: Option<String>,
+    pub outcome: Option<String>,
+    pub ip_address: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
@@ -0,0 +1,91 @@
+use crate::application::errors::application_error::ApplicationError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+pub enum AuditEventType {
+    SIGNUP,
+    LOGIN,
+    LOGOUT,
+    LOGOUT_ALL,
+    TOKEN_REFRESHED,
+    REFRESH_TOKEN_REUSED,
+    //first factor accepted, the login waits for the second
+    MFA_CHALLENGE,
+    MFA_VERIFIED,
+    ACCOUNT_DELETION_REQUESTED,
+    ACCOUNT_DELETION_CANCELLED,
+    ACCOUNT_DELETED,
+    ACCOUNT_EXPORTED,
+    PASSWORD_RESET_REQUESTED,
+    PASSWORD_RESET,
+    PASSWORD_CHANGED,
+    //admin actions, the target account goes in the metadata
+    ACCOUNT_UNLOCKED,
+    ACCOUNT_EXPIRY_UPDATED,
+    ROLE_GRANTED,
+    ROLE_REVOKED,
+    //client credentials grant, the actor is the service user of the client
+    CLIENT_TOKEN_ISSUED,
+}
+
+impl fmt::Display for AuditEventType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditEventType::SIGNUP => write!(f, "SIGNUP"),
+            AuditEventType::LOGIN => write!(f, "LOGIN"),
+            AuditEventType::LOGOUT => write!(f, "LOGOUT"),
+            AuditEventType::LOGOUT_ALL => write!(f, "LOGOUT_ALL"),
+            AuditEventType::TOKEN_REFRESHED => write!(f, "TOKEN_REFRESHED"),
+            AuditEventType::REFRESH_TOKEN_REUSED => write!(f, "REFRESH_TOKEN_REUSED"),
+            AuditEventType::MFA_CHALLENGE => write!(f, "MFA_CHALLENGE"),
+            AuditEventType::MFA_VERIFIED => write!(f, "MFA_VERIFIED"),
+            AuditEventType::ACCOUNT_DELETION_REQUESTED => write!(f, "ACCOUNT_DELETION_REQUESTED"),
+            AuditEventType::ACCOUNT_DELETION_CANCELLED => write!(f, "ACCOUNT_DELETION_CANCELLED"),
+            AuditEventType::ACCOUNT_DELETED => write!(f, "ACCOUNT_DELETED"),
+            AuditEventType::ACCOUNT_EXPORTED => write!(f, "ACCOUNT_EXPORTED"),
+            AuditEventType::PASSWORD_RESET_REQUESTED => write!(f, "PASSWORD_RESET_REQUESTED"),
+            AuditEventType::PASSWORD_RESET => write!(f, "PASSWORD_RESET"),
+            AuditEventType::PASSWORD_CHANGED => write!(f, "PASSWORD_CHANGED"),
+            AuditEventType::ACCOUNT_UNLOCKED => write!(f, "ACCOUNT_UNLOCKED"),
+            AuditEventType::ACCOUNT_EXPIRY_UPDATED => write!(f, "ACCOUNT_EXPIRY_UPDATED"),
+            AuditEventType::ROLE_GRANTED => write!(f, "ROLE_GRANTED"),
+            AuditEventType::ROLE_REVOKED => write!(f, "ROLE_REVOKED"),
+            AuditEventType::CLIENT_TOKEN_ISSUED => write!(f, "CLIENT_TOKEN_ISSUED"),
+        }
+    }
+}
+
+impl FromStr for AuditEventType {
+    type Err = ApplicationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "SIGNUP" => Ok(Self::SIGNUP),
+            "LOGIN" => Ok(Self::LOGIN),
+            "LOGOUT" => Ok(Self::LOGOUT),
+            "LOGOUT_ALL" => Ok(Self::LOGOUT_ALL),
+            "TOKEN_REFRESHED" => Ok(Self::TOKEN_REFRESHED),
+            "REFRESH_TOKEN_REUSED" => Ok(Self::REFRESH_TOKEN_REUSED),
+            "MFA_CHALLENGE" => Ok(Self::MFA_CHALLENGE),
+            "MFA_VERIFIED" => Ok(Self::MFA_VERIFIED),
+            "ACCOUNT_DELETION_REQUESTED" => Ok(Self::ACCOUNT_DELETION_REQUESTED),
+            "ACCOUNT_DELETION_CANCELLED" => Ok(Self::ACCOUNT_DELETION_CANCELLED),
+            "ACCOUNT_DELETED" => Ok(Self::ACCOUNT_DELETED),
+            "ACCOUNT_EXPORTED" => Ok(Self::ACCOUNT_EXPORTED),
+            "PASSWORD_RESET_REQUESTED" => Ok(Self::PASSWORD_RESET_REQUESTED),
+            "PASSWORD_RESET" => Ok(Self::PASSWORD_RESET),
+            "PASSWORD_CHANGED" => Ok(Self::PASSWORD_CHANGED),
+            "ACCOUNT_UNLOCKED" => Ok(Self::ACCOUNT_UNLOCKED),
+            "ACCOUNT_EXPIRY_UPDATED" => Ok(Self::ACCOUNT_EXPIRY_UPDATED),
+            "ROLE_GRANTED" => Ok(Self::ROLE_GRANTED),
+            "ROLE_REVOKED" => Ok(Self::ROLE_REVOKED),
+            "CLIENT_TOKEN_ISSUED" => Ok(Self::CLIENT_TOKEN_ISSUED),
+            _ => Err(ApplicationError::new(
+                "Audit Error",
+                format!("Unknown audit event type {}", value),
+            )),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use crate::application::errors::application_error::ApplicationError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum AuditOutcome {
+    SUCCESS,
+    FAILURE,
+}
+
+impl fmt::Display for AuditOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditOutcome::SUCCESS => write!(f, "SUCCESS"),
+            AuditOutcome::FAILURE => write!(f, "FAILURE"),
+        }
+    }
+}
+
+impl FromStr for AuditOutcome {
+    type Err = ApplicationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "SUCCESS" => Ok(Self::SUCCESS),
+            "FAILURE" => Ok(Self::FAILURE),
+            _ => Err(ApplicationError::new(
+                "Audit Error",
+                format!("Unknown audit outcome {}", value),
+            )),
+        }
+    }
+}
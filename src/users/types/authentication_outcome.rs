@@ -0,0 +1,13 @@
+use crate::users::types::authentication_result::AuthenticationResult;
+use crate::users::types::mfa_challenge_response::MfaChallengeResponse;
+use uuid::Uuid;
+
+///result of a password sign-in, accounts with two-factor authentication get a challenge first
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthenticationOutcome {
+    Authenticated(Box<AuthenticationResult>),
+    MfaRequired {
+        user_id: Uuid,
+        challenge: MfaChallengeResponse,
+    },
+}
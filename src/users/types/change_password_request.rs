@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ChangePasswordRequest {
+    //left out by passwordless accounts
+    #[serde(default)]
+    pub current_password: String,
+    //passwordless accounts confirm with a code requested from /auth/magic-link
+    pub code: Option<String>,
+    pub password: String,
+    pub confirm_password: String,
+}
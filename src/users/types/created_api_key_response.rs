@@ -0,0 +1,10 @@
+use crate::users::types::api_key_response::ApiKeyResponse;
+use serde::{Deserialize, Serialize};
+
+///the full key is only returned once, when it is created
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CreatedApiKeyResponse {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+}
@@ -0,0 +1,10 @@
+use crate::users::types::oauth_client_response::OAuthClientResponse;
+use serde::{Deserialize, Serialize};
+
+///the client secret is only returned once, when the client is registered
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CreatedOAuthClientResponse {
+    pub client_secret: String,
+    #[serde(flatten)]
+    pub client: OAuthClientResponse,
+}
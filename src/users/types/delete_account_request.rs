@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct DeleteAccountRequest {
+    //left out by passwordless accounts
+    #[serde(default)]
+    pub password: String,
+    //passwordless accounts confirm with a code requested from /auth/magic-link
+    pub code: Option<String>,
+}
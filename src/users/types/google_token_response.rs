@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+///token endpoint response, only the id token is used
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct GoogleTokenResponse {
+    pub id_token: String,
+}
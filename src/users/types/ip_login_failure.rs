@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct IpLoginFailure {
+    pub ip: String,
+    pub failed_attempts: i32,
+    pub lockout_count: i32,
+    pub blocked_until: Option<OffsetDateTime>,
+    pub updated_at: OffsetDateTime,
+}
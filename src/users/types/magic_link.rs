@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MagicLink {
+    pub id: Uuid,
+    pub email: String,
+    pub name: Option<String>,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub expires_at: OffsetDateTime,
+    pub consumed_at: Option<OffsetDateTime>,
+    pub created_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MagicLinkRequest {
+    pub email: String,
+    //only used when the link creates the account
+    pub name: Option<String>,
+}
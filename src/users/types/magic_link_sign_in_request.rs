@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+///either the token of the link, or the email together with the code
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MagicLinkSignInRequest {
+    pub token: Option<String>,
+    pub email: Option<String>,
+    pub code: Option<String>,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+///returned by sign-in instead of a session when the account has two-factor authentication on
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
@@ -0,0 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MfaEnrollmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+///second sign-in step, the code is a TOTP code or a recovery code
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MfaVerifyRequest {
+    pub mfa_token: String,
+    pub code: String,
+}
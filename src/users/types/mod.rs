@@ -1,3 +1,5 @@
+pub mod require_role;
+pub mod require_scope;
 pub mod role;
 pub mod role_type;
 pub mod user;
@@ -8,6 +10,71 @@ pub mod login_request;
 pub mod login_response;
 pub mod user_response;
 
+pub mod access_token_response;
 pub mod authentication_result;
 pub mod token;
-pub mod access_token_response;
+
+pub mod email_request;
+pub mod message_response;
+pub mod verification_token;
+pub mod verify_email_request;
+
+pub mod password_reset_token;
+pub mod reset_password_request;
+
+pub mod google_callback_query;
+pub mod google_id_token_claims;
+pub mod google_token_response;
+pub mod oauth_state;
+
+pub mod ip_login_failure;
+
+pub mod account_expiry_request;
+
+pub mod authentication_outcome;
+pub mod mfa_challenge;
+pub mod mfa_challenge_response;
+pub mod mfa_code_request;
+pub mod mfa_enrollment_response;
+pub mod mfa_verify_request;
+pub mod recovery_codes_response;
+pub mod user_mfa;
+
+pub mod api_key;
+pub mod api_key_principal;
+pub mod api_key_principal_response;
+pub mod api_key_request;
+pub mod api_key_response;
+pub mod created_api_key_response;
+pub mod rename_api_key_request;
+
+pub mod created_oauth_client_response;
+pub mod oauth_client;
+pub mod oauth_client_request;
+pub mod oauth_client_response;
+pub mod oauth_error_response;
+pub mod oauth_token_request;
+pub mod oauth_token_response;
+
+pub mod session_context;
+pub mod session_response;
+pub mod user_session;
+
+pub mod change_password_request;
+pub mod profile_response;
+pub mod update_profile_request;
+
+pub mod account_deletion_response;
+pub mod account_export;
+pub mod delete_account_request;
+
+pub mod audit_event;
+pub mod audit_event_page;
+pub mod audit_event_query;
+pub mod audit_event_type;
+pub mod audit_outcome;
+pub mod new_audit_event;
+
+pub mod magic_link;
+pub mod magic_link_request;
+pub mod magic_link_sign_in_request;
@@ -0,0 +1,47 @@
+use crate::users::types::audit_event_type::AuditEventType;
+use crate::users::types::audit_outcome::AuditOutcome;
+use crate::users::types::session_context::SessionContext;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+///# New Audit Event
+///
+/// an event about to be recorded, metadata must never carry tokens, passwords or other secrets
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewAuditEvent {
+    pub actor_id: Option<Uuid>,
+    pub event_type: AuditEventType,
+    pub outcome: AuditOutcome,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub metadata: Value,
+}
+
+impl NewAuditEvent {
+    pub fn new(event_type: AuditEventType, outcome: AuditOutcome) -> Self {
+        Self {
+            actor_id: None,
+            event_type,
+            outcome,
+            ip_address: None,
+            user_agent: None,
+            metadata: json!({}),
+        }
+    }
+
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn context(mut self, context: &SessionContext) -> Self {
+        self.ip_address = context.ip_address.clone();
+        self.user_agent = context.user_agent.clone();
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
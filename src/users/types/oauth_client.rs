@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+///registered service client, it acts through its own service user holding the APPLICATION role
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthClient {
+    pub id: Uuid,
+    pub client_id: String,
+    pub client_secret_hash: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub user_id: Uuid,
+    pub created_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+}
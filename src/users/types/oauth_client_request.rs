@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthClientRequest {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
@@ -0,0 +1,29 @@
+use crate::users::types::oauth_client::OAuthClient;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthClientResponse {
+    pub id: Uuid,
+    pub client_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+impl From<OAuthClient> for OAuthClientResponse {
+    fn from(value: OAuthClient) -> Self {
+        Self {
+            id: value.id,
+            client_id: value.client_id,
+            name: value.name,
+            scopes: value.scopes,
+            created_at: value.created_at,
+            revoked_at: value.revoked_at,
+        }
+    }
+}
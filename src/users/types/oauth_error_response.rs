@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+///RFC 6749 error body, the token endpoint answers in the OAuth format rather than `ApplicationError`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    pub error_description: String,
+}
+
+impl OAuthErrorResponse {
+    pub fn new(error: &str, error_description: &str) -> Self {
+        Self {
+            error: error.to_owned(),
+            error_description: error_description.to_owned(),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthState {
+    pub id: Uuid,
+    pub state_hash: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: OffsetDateTime,
+    pub created_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+///form body of `POST /oauth/token`, the client may authenticate with HTTP Basic instead of the body
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthTokenRequest {
+    pub grant_type: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+///RFC 6749 access token response, client credentials never get a refresh token
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub scope: String,
+}
@@ -0,0 +1,20 @@
+use crate::users::types::user_response::UserResponse;
+use crate::users::types::user_source::UserSource;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+///the signed in user's own account, the user response plus profile fields
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ProfileResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub image_url: Option<String>,
+    pub source: UserSource,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+    //set while a requested account deletion waits out its grace period
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub deletion_scheduled_at: Option<OffsetDateTime>,
+}
@@ -0,0 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,168 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::repositories::role_repository::user_has_role;
+use crate::users::types::role_type::RoleType;
+use crate::users::types::user::User;
+use axum::Json;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use log::error;
+use reqwest::StatusCode;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+///a role a route can ask for through `RequireRole`
+pub trait RequiredRole: Send + Sync {
+    const ROLE: RoleType;
+}
+
+pub struct Admin;
+
+impl RequiredRole for Admin {
+    const ROLE: RoleType = RoleType::ADMIN;
+}
+
+pub struct Application;
+
+impl RequiredRole for Application {
+    const ROLE: RoleType = RoleType::APPLICATION;
+}
+
+///# Require Role
+///
+/// authenticated user holding the role R, e.g. `RequireRole<Admin>`
+///
+/// 401 without a valid token, 403 when the role is missing
+pub struct RequireRole<R: RequiredRole> {
+    pub user: User,
+    role: PhantomData<R>,
+}
+
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    R: RequiredRole,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = User::from_request_parts(parts, state).await?;
+
+        let Some(app_state) = parts.extensions.get::<Arc<AppState>>() else {
+            error!("Application State Not Found");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApplicationError::generic("Application State Not Found")),
+            ));
+        };
+
+        match user_has_role(&app_state.pool, &user.id, R::ROLE).await {
+            Ok(true) => Ok(Self {
+                user,
+                role: PhantomData,
+            }),
+            Ok(false) => Err((
+                StatusCode::FORBIDDEN,
+                Json(ApplicationError::new(
+                    "Authorization Error",
+                    format!("{} role required", R::ROLE),
+                )),
+            )),
+            Err(error) => {
+                error!("{:?}", error);
+                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::repositories::role_repository::{get_roles_by_user_id, grant_role};
+    use crate::users::services::jwt_service::generate_persisted_user_token;
+    use crate::users::types::session_context::SessionContext;
+    use axum::http::{Request, header};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    //a verified user with the given role names, returns its id and an access token
+    async fn user_with_roles(pool: &PgPool, email: &str, roles: &[&str]) -> (Uuid, String) {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', $2, 'hash', true)",
+            id,
+            email
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        for role in roles {
+            sqlx::query!(
+                "insert into roles(id, user_id, role) values ($1, $2, $3)",
+                Uuid::new_v4(),
+                id,
+                role
+            )
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        let tokens = generate_persisted_user_token(email, &SessionContext::default(), pool)
+            .await
+            .unwrap();
+        (id, tokens.access)
+    }
+
+    async fn require_admin(
+        state: &Arc<AppState>,
+        access: &str,
+    ) -> Result<RequireRole<Admin>, (StatusCode, Json<ApplicationError>)> {
+        let (mut parts, _) = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", access))
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.extensions.insert(state.clone());
+        RequireRole::<Admin>::from_request_parts(&mut parts, &()).await
+    }
+
+    #[sqlx::test]
+    async fn only_holders_of_the_role_get_through(pool: PgPool) {
+        let (admin, admin_access) = user_with_roles(&pool, "admin@x.io", &["USER", "ADMIN"]).await;
+        let (_, user_access) = user_with_roles(&pool, "ada@x.io", &["USER"]).await;
+        let (state, _) = AppState::for_tests(pool);
+
+        let allowed = require_admin(&state, &admin_access).await.ok().unwrap();
+        assert_eq!(allowed.user.id, admin);
+
+        let (status, Json(error)) = require_admin(&state, &user_access).await.err().unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error.description, "ADMIN role required");
+    }
+
+    #[sqlx::test]
+    async fn unknown_role_names_in_the_database_are_skipped(pool: PgPool) {
+        let (id, access) = user_with_roles(&pool, "ada@x.io", &["USER", "SUPERVISOR"]).await;
+        let (state, _) = AppState::for_tests(pool);
+
+        assert_eq!(
+            get_roles_by_user_id(&state.pool, &id).await.unwrap(),
+            vec![RoleType::USER]
+        );
+        let (status, _) = require_admin(&state, &access).await.err().unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        grant_role(&state.pool, &id, RoleType::ADMIN).await.unwrap();
+        let allowed = require_admin(&state, &access).await.ok().unwrap();
+        assert_eq!(
+            allowed.user.to_response(&state.pool).await.unwrap().roles,
+            vec![RoleType::ADMIN, RoleType::USER]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use crate::application::configuration::application_state::AppState;
+use crate::application::errors::application_error::ApplicationError;
+use crate::users::services::account_status_service::is_account_status_error;
+use crate::users::services::authentication_service::authenticate_client_token;
+use crate::users::services::jwt_service::extract_bearer_token;
+use crate::users::types::api_key_principal::ApiKeyPrincipal;
+use crate::users::types::user::User;
+use axum::Json;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use log::error;
+use reqwest::StatusCode;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+///a scope a machine route can ask for through `RequireScope`
+pub trait RequiredScope: Send + Sync {
+    const SCOPE: &'static str;
+}
+
+pub struct MembersRead;
+
+impl RequiredScope for MembersRead {
+    const SCOPE: &'static str = "members:read";
+}
+
+///# Require Scope
+///
+/// machine client granted the scope S, e.g. `RequireScope<MembersRead>`
+///
+/// either a service client with a bearer token from `/oauth/token` or an api key
+///
+/// 401 without a valid token or key, 403 when the scope is missing
+pub struct RequireScope<S: RequiredScope> {
+    pub user: User,
+    scope: PhantomData<S>,
+}
+
+impl<S, R> FromRequestParts<S> for RequireScope<R>
+where
+    S: Send + Sync,
+    R: RequiredScope,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let (user, scopes) = match extract_bearer_token(&parts.headers) {
+            Some(token) => {
+                let Some(app_state) = parts.extensions.get::<Arc<AppState>>() else {
+                    error!("Application State Not Found");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApplicationError::generic("Application State Not Found")),
+                    ));
+                };
+                match authenticate_client_token(token, &app_state.pool).await {
+                    Ok(client) => client,
+                    //only the account status is explained, token and database errors stay in the log
+                    Err(e) => {
+                        error!("{}", e);
+                        return Err(match e {
+                            _ if is_account_status_error(&e) => (StatusCode::UNAUTHORIZED, Json(e)),
+                            _ if e.is_internal() => (
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                Json(ApplicationError::generic("Authentication failed")),
+                            ),
+                            _ => (
+                                StatusCode::UNAUTHORIZED,
+                                Json(ApplicationError::new(
+                                    "Authentication Error",
+                                    "invalid token",
+                                )),
+                            ),
+                        });
+                    }
+                }
+            }
+            None => {
+                let principal = ApiKeyPrincipal::from_request_parts(parts, state).await?;
+                (principal.user, principal.api_key.scopes)
+            }
+        };
+
+        match scopes.iter().any(|scope| scope == R::SCOPE) {
+            true => Ok(Self {
+                user,
+                scope: PhantomData,
+            }),
+            false => Err((
+                StatusCode::FORBIDDEN,
+                Json(ApplicationError::new(
+                    "Authorization Error",
+                    format!("{} scope required", R::SCOPE),
+                )),
+            )),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+    pub confirm_password: String,
+}
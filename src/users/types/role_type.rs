@@ -1,36 +1,67 @@
+use crate::application::errors::application_error::ApplicationError;
 use serde::{Deserialize, Serialize};
 use std::fmt;
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+use std::str::FromStr;
 
-pub enum RoleType {
-    USER,
-    APPLICATION,
-}
+//the enum, its name and its parsing come from the one list of roles, a role is stored under its own name
+macro_rules! role_types {
+    ($($role:ident),+ $(,)?) => {
+        #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+        pub enum RoleType {
+            $($role),+
+        }
 
-impl Into<String> for RoleType {
-    fn into(self) -> String {
-        match self {
-            Self::USER => String::from("USER"),
-            Self::APPLICATION => String::from("APPLICATION"),
+        impl RoleType {
+            ///the name stored in the roles table
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$role => stringify!($role)),+
+                }
+            }
         }
-    }
-}
 
-impl From<String> for RoleType {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "USER" => Self::USER,
-            "APPLICATION" => Self::APPLICATION,
-            _ => panic!("unknown role type"),
+        ///fallible parsing for role names coming from the database or a request
+        impl FromStr for RoleType {
+            type Err = ApplicationError;
+
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                match value {
+                    $(stringify!($role) => Ok(Self::$role),)+
+                    _ => Err(ApplicationError::new(
+                        "Role Error",
+                        format!("Unknown role {}", value),
+                    )),
+                }
+            }
         }
+    };
+}
+
+role_types!(USER, APPLICATION, ADMIN);
+
+impl From<RoleType> for String {
+    fn from(role: RoleType) -> Self {
+        role.as_str().to_owned()
     }
 }
 
 impl fmt::Display for RoleType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::USER => write!(f, "USER"),
-            Self::APPLICATION => write!(f, "APPLICATION"),
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_role_parses_back_from_its_name() {
+        for role in [RoleType::USER, RoleType::APPLICATION, RoleType::ADMIN] {
+            assert_eq!(role.to_string().parse::<RoleType>().unwrap(), role);
+            assert_eq!(String::from(role.clone()), role.as_str());
         }
+        assert!("SUPERVISOR".parse::<RoleType>().is_err());
+        assert!("admin".parse::<RoleType>().is_err());
     }
 }
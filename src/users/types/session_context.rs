@@ -0,0 +1,44 @@
+use crate::application::errors::application_error::ApplicationError;
+use crate::application::security::client_ip::ClientIp;
+use axum::Json;
+use axum::extract::FromRequestParts;
+use axum::http::header::USER_AGENT;
+use axum::http::request::Parts;
+use reqwest::StatusCode;
+
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+///# Session Context
+///
+/// device details recorded with a new session, both are best effort
+///
+/// the address is the `ClientIp`, forwarded headers only count behind TRUSTED_PROXY_COUNT proxies
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionContext {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for SessionContext
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+        let ip_address = ClientIp::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ip| ip.to_string());
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
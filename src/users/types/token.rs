@@ -8,6 +8,10 @@ pub struct Token {
     pub is_expired: Option<bool>,
     pub is_revoked: Option<bool>,
     pub user_id: Uuid,
-    pub token: Option<String>,
+    pub token_hash: String,
     pub created_at: Option<OffsetDateTime>,
+    pub session_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub rotated_at: Option<OffsetDateTime>,
+    pub expires_at: Option<OffsetDateTime>,
 }
@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+///fields left out are kept, an empty `image_url` removes the image
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UpdateProfileRequest {
+    pub name: Option<String>,
+    pub image_url: Option<String>,
+}
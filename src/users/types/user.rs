@@ -1,11 +1,13 @@
 use crate::application::configuration::application_state::AppState;
 use crate::application::errors::application_error::ApplicationError;
-use crate::users::repositories::role_repository::get_token_by_user_id;
-use crate::users::services::authentication_service::authenticate_user;
+use crate::users::repositories::role_repository::get_roles_by_user_id;
+use crate::users::services::account_status_service::is_account_status_error;
+use crate::users::services::authentication_service::authenticate_token;
+use crate::users::services::jwt_service::extract_bearer_token;
 use crate::users::types::user_response::UserResponse;
 use crate::users::types::user_source::UserSource;
 use axum::Json;
-use axum::extract::FromRequestParts;
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
 use axum::http::request::Parts;
 use log::error;
 use reqwest::StatusCode;
@@ -28,6 +30,13 @@ pub struct User {
     pub created_at: Option<OffsetDateTime>,
     pub updated_at: Option<OffsetDateTime>,
     pub source: UserSource,
+    pub failed_login_attempts: i32,
+    pub lockout_count: i32,
+    pub locked_until: Option<OffsetDateTime>,
+    pub account_expires_at: Option<OffsetDateTime>,
+    pub avatar_id: Option<Uuid>,
+    pub deletion_scheduled_at: Option<OffsetDateTime>,
+    pub email_verified: bool,
 }
 
 impl User {
@@ -36,7 +45,7 @@ impl User {
             id: self.id,
             name: self.name.clone(),
             email: self.email.clone(),
-            role: get_token_by_user_id(&pool, &self.id).await?.role,
+            roles: get_roles_by_user_id(pool, &self.id).await?,
         })
     }
 }
@@ -56,36 +65,186 @@ where
             )),
         ));
 
-        //check for authorization header
-        if let Some(header_value) = parts.headers.get("Authorization") {
-            //parse the header to string
-            if let Ok(header_str) = &header_value.to_str() {
-                //check for bear token
-                if header_str.starts_with("Bearer ") && header_str.len() > 7 {
-                    //extract the token
-                    let token = &header_str[7..];
-                    //get database connection
-                    return match &parts.extensions.get::<Arc<AppState>>() {
-                        Some(pool) => {
-                            //try to authenticate the user with token
-                            match authenticate_user(Some(token), None, &pool.pool).await {
-                                //return the user
-                                Ok(result) => Ok(result.user),
-
-                                Err(e) => {
-                                    error!("{}", e);
-                                    error
+        //check for bearer token in the authorization header
+        if let Some(token) = extract_bearer_token(&parts.headers) {
+            //get database connection
+            return match &parts.extensions.get::<Arc<AppState>>() {
+                Some(pool) => {
+                    //try to authenticate the user with token
+                    match authenticate_token(token, &pool.pool).await {
+                        //return the user
+                        Ok(user) => Ok(user),
+
+                        //only the account status is explained, token and database errors stay in the log
+                        Err(e) => {
+                            error!("{}", e);
+                            match e {
+                                _ if is_account_status_error(&e) => {
+                                    Err((StatusCode::UNAUTHORIZED, Json(e)))
                                 }
+                                _ if e.is_internal() => Err((
+                                    StatusCode::INTERNAL_SERVER_ERROR,
+                                    Json(ApplicationError::generic("Authentication failed")),
+                                )),
+                                _ => error,
                             }
                         }
-                        None => {
-                            error!("Application State Not Found");
-                            error
-                        }
-                    };
+                    }
                 }
-            }
+                None => {
+                    error!("Application State Not Found");
+                    Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ApplicationError::generic("Application State Not Found")),
+                    ))
+                }
+            };
         }
         error
     }
 }
+
+///a request without a bearer token has no user, an invalid token is still rejected
+impl<S> OptionalFromRequestParts<S> for User
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<ApplicationError>);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        match extract_bearer_token(&parts.headers) {
+            Some(_) => <User as FromRequestParts<S>>::from_request_parts(parts, state)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::security::jwt_key_set::JwtKeySet;
+    use crate::application::security::session_policy::SessionPolicy;
+    use crate::users::services::jwt_service::{
+        UserTokenResponse, generate_persisted_user_token, rotate_refresh_token,
+    };
+    use crate::users::types::session_context::SessionContext;
+    use axum::http::{Request, header};
+
+    //a verified user with a fresh session, returns its id and first token pair
+    async fn sign_in(pool: &PgPool) -> (Uuid, UserTokenResponse) {
+        JwtKeySet::install_for_tests();
+        SessionPolicy::install_for_tests();
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            "insert into users(id, name, email, password, email_verified)
+            values ($1, 'Ada', 'ada@x.io', 'hash', true)",
+            id
+        )
+        .execute(pool)
+        .await
+        .unwrap();
+        let tokens = generate_persisted_user_token("ada@x.io", &SessionContext::default(), pool)
+            .await
+            .unwrap();
+        (id, tokens)
+    }
+
+    async fn extract(
+        state: &Arc<AppState>,
+        access: &str,
+    ) -> Result<User, (StatusCode, Json<ApplicationError>)> {
+        let (mut parts, _) = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", access))
+            .body(())
+            .unwrap()
+            .into_parts();
+        parts.extensions.insert(state.clone());
+        <User as FromRequestParts<()>>::from_request_parts(&mut parts, &()).await
+    }
+
+    #[sqlx::test]
+    async fn the_account_status_is_checked_on_every_token_use_and_refresh(pool: PgPool) {
+        let (state, _) = AppState::for_tests(pool);
+        let (id, tokens) = sign_in(&state.pool).await;
+        assert_eq!(extract(&state, &tokens.access).await.ok().unwrap().id, id);
+
+        for (change, expected) in [
+            (
+                "update users set is_enabled = false where id = $1",
+                "Account Disabled",
+            ),
+            (
+                "update users set email_verified = false where id = $1",
+                "Account Not Verified",
+            ),
+            (
+                "update users set is_account_non_expired = false where id = $1",
+                "Account Expired",
+            ),
+            (
+                "update users set account_expires_at = now() - interval '1 minute' where id = $1",
+                "Account Expired",
+            ),
+            (
+                "update users set is_account_non_locked = false,
+                locked_until = now() + interval '1 hour' where id = $1",
+                "Account Locked",
+            ),
+        ] {
+            sqlx::query(change)
+                .bind(id)
+                .execute(&state.pool)
+                .await
+                .unwrap();
+
+            let (status, Json(error)) = extract(&state, &tokens.access).await.err().unwrap();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(error.error, expected);
+            let error =
+                rotate_refresh_token(&tokens.refresh, &SessionContext::default(), &state.pool)
+                    .await
+                    .unwrap_err();
+            assert_eq!(error.error, expected);
+
+            sqlx::query(
+                "update users set is_enabled = true, email_verified = true,
+                is_account_non_expired = true, account_expires_at = null,
+                is_account_non_locked = true, locked_until = null
+                where id = $1",
+            )
+            .bind(id)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+        }
+
+        //an expiry date still ahead doesn't get in the way
+        sqlx::query!(
+            "update users set account_expires_at = now() + interval '1 day' where id = $1",
+            id
+        )
+        .execute(&state.pool)
+        .await
+        .unwrap();
+        assert!(extract(&state, &tokens.access).await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn a_failing_database_is_not_reported_as_an_invalid_token(pool: PgPool) {
+        let (state, _) = AppState::for_tests(pool);
+        let (_, tokens) = sign_in(&state.pool).await;
+
+        let (status, _) = extract(&state, "not-a-token").await.err().unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        state.pool.close().await;
+        let (status, Json(error)) = extract(&state, &tokens.access).await.err().unwrap();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(error.description, "Authentication failed");
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UserMfa {
+    pub user_id: Uuid,
+    pub secret: String,
+    pub is_enabled: bool,
+    pub last_used_step: Option<i64>,
+    pub confirmed_at: Option<OffsetDateTime>,
+    pub created_at: Option<OffsetDateTime>,
+}
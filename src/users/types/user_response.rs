@@ -7,5 +7,5 @@ pub struct UserResponse {
     pub id: Uuid,
     pub name: String,
     pub email: String,
-    pub role: RoleType,
+    pub roles: Vec<RoleType>,
 }
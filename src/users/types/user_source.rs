@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+//decoding an unknown source from the database fails instead of panicking
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "varchar")]
 pub enum UserSource {
     SYSTEM,
     GOOGLE,
+    CLIENT,
 }
 
 impl Into<String> for UserSource {
@@ -11,6 +14,7 @@ impl Into<String> for UserSource {
         match self {
             Self::SYSTEM => String::from("SYSTEM"),
             Self::GOOGLE => String::from("GOOGLE"),
+            Self::CLIENT => String::from("CLIENT"),
         }
     }
 }
@@ -20,16 +24,7 @@ impl fmt::Display for UserSource {
         match self {
             UserSource::SYSTEM => write!(f, "SYSTEM"),
             UserSource::GOOGLE => write!(f, "GOOGLE"),
-        }
-    }
-}
-
-impl From<String> for UserSource {
-    fn from(value: String) -> UserSource {
-        match value.as_str() {
-            "SYSTEM" => Self::SYSTEM,
-            "GOOGLE" => Self::GOOGLE,
-            _ => panic!("Unknown user source type"),
+            UserSource::CLIENT => write!(f, "CLIENT"),
         }
     }
 }